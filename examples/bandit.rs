@@ -0,0 +1,48 @@
+//! REINFORCE on a four-armed Bernoulli bandit: a softmax policy over four
+//! logits learns to favor the arm that pays out most often, with the running
+//! mean reward as a baseline to reduce the variance of the updates.
+
+use micrograd::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const PAYOUTS: [f64; 4] = [0.2, 0.5, 0.8, 0.3];
+const BATCH: usize = 16;
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let logits: Vec<Scalar<f32>> = (0..PAYOUTS.len())
+        .map(|i| Scalar::new(0.0, &format!("arm{i}")))
+        .collect();
+    let mut optimizer = SGD::new(0.5);
+    let mut baseline = 0.0;
+
+    for episode in 0..200 {
+        let log_probs = log_softmax(&logits, 1.0);
+        let probs = softmax(&logits, 1.0);
+
+        let mut taken = vec![];
+        let mut advantages = vec![];
+
+        for _ in 0..BATCH {
+            let arm = multinomial(&probs, &mut rng).unwrap();
+            let reward = if rng.gen_bool(PAYOUTS[arm]) { 1.0 } else { 0.0 };
+
+            taken.push(log_probs[arm].clone());
+            advantages.push(reward - baseline);
+            baseline += 0.05 * (reward - baseline);
+        }
+
+        let loss = reinforce_loss(&taken, &advantages).unwrap();
+        loss.backward();
+        optimizer.step(&logits);
+
+        if episode % 40 == 39 {
+            let probs: Vec<String> = probs.iter().map(|p| format!("{:.2}", p.data())).collect();
+            println!(
+                "episode {:>3}  baseline {baseline:.2}  policy [{}]",
+                episode + 1,
+                probs.join(", ")
+            );
+        }
+    }
+}
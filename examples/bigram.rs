@@ -0,0 +1,54 @@
+//! A makemore-style character bigram model: an embedding table maps each
+//! character straight to the logits of the next, trained with cross-entropy
+//! and then sampled to generate new names.
+
+use micrograd::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+const NAMES: &str = "emma olivia ava isabella sophia mia amelia harper evelyn abigail \
+emily ella elizabeth camila luna sofia avery mila aria scarlett penelope layla chloe \
+victoria madison eleanor grace nora riley zoey hannah hazel lily ellie violet lillian";
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let vocab = Vocab::from_text(NAMES, '.');
+    let pairs = bigrams(&vocab, NAMES.split_whitespace(), '.');
+    let logits = Embedding::new(vocab.len(), vocab.len(), &mut rng);
+    let mut optimizer = SGD::new(5.0);
+
+    for epoch in 0..50 {
+        let losses: Vec<Scalar<f32>> = pairs
+            .iter()
+            .map(|(current, next)| {
+                cross_entropy(&logits.lookup(*current).unwrap(), *next, 1.0).unwrap()
+            })
+            .collect();
+        let loss = mean(&losses);
+
+        logits.zero_grad();
+        loss.backward();
+        optimizer.step(&logits.parameters());
+
+        if epoch % 10 == 9 {
+            println!("epoch {:>2}  loss {:.4}", epoch + 1, loss.data());
+        }
+    }
+
+    for _ in 0..5 {
+        let mut name = vec![];
+        let mut current = 0;
+
+        loop {
+            let row = logits.lookup(current).unwrap();
+            current = with_temperature(&row, 1.0, &mut rng).unwrap();
+
+            if current == 0 || name.len() >= 12 {
+                break;
+            }
+
+            name.push(current);
+        }
+
+        println!("{}", vocab.decode(&name));
+    }
+}
@@ -0,0 +1,39 @@
+use eframe::egui;
+use micrograd::{dashboard::Dashboard, prelude::*};
+use std::thread;
+
+fn main() -> Result<(), eframe::Error> {
+    let dashboard = Dashboard::new(10);
+    let mut callback = dashboard.clone();
+
+    thread::spawn(move || {
+        let mut rng = rand::thread_rng();
+        let mut trainer = Trainer::new(MLP::new(3, &[4, 4, 1], &mut rng), SGD::new(0.05));
+        let xs = vec![
+            vec![2.0, 3.0, -1.0],
+            vec![3.0, -1.0, 0.5],
+            vec![0.5, 1.0, 1.0],
+            vec![1.0, 1.0, -1.0],
+        ];
+        let ys = vec![vec![1.0], vec![-1.0], vec![-1.0], vec![1.0]];
+
+        trainer.fit(&xs, &ys, 200, &mut [&mut callback]).unwrap();
+    });
+
+    eframe::run_native(
+        "micrograd-rs training dashboard",
+        eframe::NativeOptions::default(),
+        Box::new(|_cc| Box::new(App { dashboard })),
+    )
+}
+
+struct App {
+    dashboard: Dashboard,
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| self.dashboard.ui(ui));
+        ctx.request_repaint();
+    }
+}
@@ -5,8 +5,7 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 use eframe::egui;
-use layout::core::utils::save_to_file;
-use micrograd::{engine::Scalar, nn::MLP};
+use micrograd::prelude::*;
 use resvg::{
     tiny_skia,
     usvg::{FitTo, Options, Tree},
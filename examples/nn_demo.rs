@@ -1,39 +1,34 @@
-use micrograd::{engine::Scalar, nn::MLP};
+use micrograd::prelude::*;
 
 fn main() {
     let mut rng = rand::thread_rng();
     let mut mlp = MLP::new(3, &[4, 4, 1], &mut rng);
 
-    let xs = [
-        [2.0, 3.0, -1.0],
-        [3.0, -1.0, 0.5],
-        [0.5, 1.0, 1.0],
-        [1.0, 1.0, -1.0],
+    let xs = vec![
+        vec![2.0, 3.0, -1.0],
+        vec![3.0, -1.0, 0.5],
+        vec![0.5, 1.0, 1.0],
+        vec![1.0, 1.0, -1.0],
     ];
+    let ys = vec![vec![1.0], vec![-1.0], vec![-1.0], vec![1.0]];
+
+    let graph = StaticGraph::new(&mut mlp, &xs, &ys).unwrap();
 
     for _ in 0..1000 {
-        let xs = xs.map(|x| x.map(|d| Scalar::new(d, "")));
-        let ys = [1.0, -1.0, -1.0, 1.0].map(|d| Scalar::new(d, ""));
-        let ypred = xs.map(|x| mlp.output(x.to_vec()).unwrap());
-        let loss = ypred
-            .iter()
-            .enumerate()
-            .map(|(i, yp)| (yp[0].clone() - ys[i].clone()).powi(2))
-            .fold(Scalar::new(0.0, ""), |acc, s| acc + s);
+        let loss = graph.step(&xs, &ys).unwrap();
 
         println!(
             "Predict: {:?}\nLoss: {:?}",
-            ypred.map(|scalars| { scalars.iter().map(|s| { s.data() }).collect::<Vec<f32>>()[0] }),
-            loss.data(),
+            graph
+                .outputs()
+                .iter()
+                .map(|scalars| scalars[0].data())
+                .collect::<Vec<f32>>(),
+            loss,
         );
 
-        loss.backward();
-
-        for p in mlp.parameters() {
-            let data = p.data();
-
-            p.set_data(data + (-0.001) * p.grad())
-        }
+        mlp.parameters()
+            .apply(|p| p.set_data(p.data() + (-0.001) * p.grad()));
     }
 
     let parameters: Vec<f32> = mlp.parameters().iter().map(|s| s.data()).collect();
@@ -0,0 +1,32 @@
+use micrograd::prelude::*;
+use rand::Rng;
+use std::{thread, time::Instant};
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    let mut mlp = MLP::new(32, &[64, 64, 1], &mut rng);
+    let threads = thread::available_parallelism().map_or(4, |n| n.get());
+
+    let losses: Vec<Scalar<f32>> = (0..16)
+        .map(|_| {
+            let x = (0..32)
+                .map(|_| Scalar::new(rng.gen_range(-1.0..1.0), ""))
+                .collect();
+            let y = mlp.output(x).unwrap();
+
+            (y[0].clone() - 1.0).powi(2)
+        })
+        .collect();
+    let loss = sum(&losses);
+
+    let start = Instant::now();
+    loss.backward();
+    println!("Serial backward: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    loss.backward_parallel(threads);
+    println!(
+        "Parallel backward ({threads} threads): {:?}",
+        start.elapsed()
+    );
+}
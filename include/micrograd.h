@@ -0,0 +1,56 @@
+/* C interface to micrograd, built with
+ * `cargo rustc --lib --crate-type cdylib --release --features ffi`.
+ * Checked against the exports by the tests in src/ffi.rs.
+ *
+ * Scalar and model handles are owned by the caller and released with
+ * mg_scalar_free / mg_mlp_free. Ops return new handles and leave their
+ * operands alive.
+ */
+
+#ifndef MICROGRAD_H
+#define MICROGRAD_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MgScalar MgScalar;
+typedef struct MgMlp MgMlp;
+
+MgScalar *mg_scalar_new(float data);
+void mg_scalar_free(MgScalar *s);
+
+MgScalar *mg_scalar_add(const MgScalar *a, const MgScalar *b);
+MgScalar *mg_scalar_sub(const MgScalar *a, const MgScalar *b);
+MgScalar *mg_scalar_mul(const MgScalar *a, const MgScalar *b);
+MgScalar *mg_scalar_powi(const MgScalar *a, int32_t n);
+MgScalar *mg_scalar_tanh(const MgScalar *a);
+MgScalar *mg_scalar_exp(const MgScalar *a);
+
+void mg_scalar_backward(const MgScalar *s);
+float mg_scalar_data(const MgScalar *s);
+void mg_scalar_set_data(const MgScalar *s, float data);
+float mg_scalar_grad(const MgScalar *s);
+
+/* MLP with `nin` inputs and `n_layers` layers of sizes `nouts`;
+ * NULL if there are no layers, a size is zero or `nouts` is NULL. */
+MgMlp *mg_mlp_new(size_t nin, const size_t *nouts, size_t n_layers, uint64_t seed);
+void mg_mlp_free(MgMlp *m);
+
+/* SGD on `n_samples` row-major rows; returns the last loss, NaN on bad data.
+ * Buffers may only be NULL when their length is zero, here and below. */
+float mg_mlp_train(MgMlp *m, const float *xs, const float *ys, size_t n_samples,
+                   float lr, size_t epochs);
+
+/* Returns the number of outputs written, or -1 on a size mismatch. */
+int64_t mg_mlp_predict(const MgMlp *m, const float *input, size_t input_len,
+                       float *output, size_t output_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MICROGRAD_H */
@@ -0,0 +1,263 @@
+//! Loss landscape analysis around a trained model.
+
+use super::{
+    engine::gaussian,
+    nn::{InferenceSession, NeuronError, MLP},
+};
+use rand::Rng;
+use std::fmt::Write;
+
+/// Square grid of offsets `-extent..=extent` with `steps` points per axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Grid {
+    pub extent: f32,
+    pub steps: usize,
+}
+
+impl Grid {
+    pub fn coords(&self) -> Vec<f32> {
+        let last = self.steps.saturating_sub(1).max(1) as f32;
+
+        (0..self.steps)
+            .map(|i| -self.extent + 2.0 * self.extent * i as f32 / last)
+            .collect()
+    }
+}
+
+/// Losses over a 2-D slice of parameter space: `losses[i][j]` is the loss at
+/// `params + coords[j] * direction1 + coords[i] * direction2`.
+#[derive(Debug, Clone)]
+pub struct LossSurface {
+    pub coords: Vec<f32>,
+    pub losses: Vec<Vec<f32>>,
+}
+
+impl LossSurface {
+    pub fn min(&self) -> f32 {
+        self.losses
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.losses
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    /// Contour plot with `levels` evenly spaced isolines between the lowest
+    /// and highest loss, traced by marching squares, colored from blue (low)
+    /// to red (high). The trained point is marked at the center.
+    pub fn contour_svg(&self, levels: usize, size: f32) -> String {
+        let n = self.coords.len();
+        let (min, max) = (self.min(), self.max());
+        let cell = size / n.saturating_sub(1).max(1) as f32;
+        // Row 0 is the lowest offset along direction2, drawn at the bottom.
+        let point = |i: f32, j: f32| (j * cell, size - i * cell);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\">"
+        );
+
+        for k in 1..=levels {
+            let t = k as f32 / (levels + 1) as f32;
+            let level = min + (max - min) * t;
+            let mut path = String::new();
+
+            for i in 0..n.saturating_sub(1) {
+                for j in 0..n - 1 {
+                    let corners = [
+                        (i as f32, j as f32, self.losses[i][j]),
+                        (i as f32, j as f32 + 1.0, self.losses[i][j + 1]),
+                        (i as f32 + 1.0, j as f32 + 1.0, self.losses[i + 1][j + 1]),
+                        (i as f32 + 1.0, j as f32, self.losses[i + 1][j]),
+                    ];
+
+                    let crossings: Vec<(f32, f32)> = (0..4)
+                        .filter_map(|e| {
+                            let (a, b) = (corners[e], corners[(e + 1) % 4]);
+
+                            if (a.2 < level) == (b.2 < level) {
+                                return None;
+                            }
+
+                            let s = (level - a.2) / (b.2 - a.2);
+                            Some(point(a.0 + s * (b.0 - a.0), a.1 + s * (b.1 - a.1)))
+                        })
+                        .collect();
+
+                    // Saddle cells cross all four edges; pair them up in order.
+                    for pair in crossings.chunks_exact(2) {
+                        write!(
+                            path,
+                            "M{:.2},{:.2}L{:.2},{:.2}",
+                            pair[0].0, pair[0].1, pair[1].0, pair[1].1
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+
+            write!(
+                svg,
+                "<path d=\"{path}\" fill=\"none\" stroke=\"rgb({},0,{})\"/>",
+                (255.0 * t) as u8,
+                (255.0 * (1.0 - t)) as u8,
+            )
+            .unwrap();
+        }
+
+        write!(
+            svg,
+            "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"3\" fill=\"black\"/></svg>",
+            size / 2.0,
+            size / 2.0
+        )
+        .unwrap();
+
+        svg
+    }
+}
+
+/// Random Gaussian direction in parameter space, filter-normalized: each
+/// neuron's slice (weights and bias) is rescaled to the norm of that neuron's
+/// parameters, so the slice stays comparable across layers of different scale.
+pub fn filtered_direction(model: &MLP, rng: &mut impl Rng) -> Vec<f32> {
+    let mut nin = model.input_dim();
+    let mut direction = vec![];
+
+    for layer in model.layer_parameters() {
+        for neuron in layer.chunks(nin + 1) {
+            let d: Vec<f32> = neuron.iter().map(|_| gaussian(rng)).collect();
+            let dn = d.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let pn = neuron.iter().map(|p| p.data().powi(2)).sum::<f32>().sqrt();
+
+            direction.extend(d.iter().map(|x| if dn > 0.0 { x * pn / dn } else { 0.0 }));
+        }
+
+        nin = layer.len() / (nin + 1);
+    }
+
+    direction
+}
+
+/// Evaluates the mean of `loss_fn(prediction, target)` over `data` at every
+/// point of `grid` in the plane spanned by `direction1` and `direction2`
+/// around the model's current parameters. Only a frozen copy of the weights
+/// in an `InferenceSession` is perturbed; the model itself is never written.
+///
+/// Panics if a direction's length differs from the parameter count.
+pub fn loss_surface(
+    model: &MLP,
+    loss_fn: impl Fn(&[f32], &[f32]) -> f32,
+    data: (&[Vec<f32>], &[Vec<f32>]),
+    direction1: &[f32],
+    direction2: &[f32],
+    grid: Grid,
+) -> Result<LossSurface, NeuronError> {
+    let params = model.parameters();
+    assert!(
+        direction1.len() == params.len() && direction2.len() == params.len(),
+        "directions must have one entry per parameter"
+    );
+
+    let (xs, ys) = data;
+    if xs.len() != ys.len() {
+        return Err(NeuronError::BatchLenErr {
+            inputs: xs.len(),
+            targets: ys.len(),
+        });
+    }
+
+    let origin: Vec<f32> = params.iter().map(|p| p.data()).collect();
+    let coords = grid.coords();
+    let mut session = InferenceSession::new(model);
+    let mut output = vec![0.0; session.nout()];
+    let mut point = origin.clone();
+
+    let mut evaluate = |a: f32, b: f32| -> Result<f32, NeuronError> {
+        for (k, p) in point.iter_mut().enumerate() {
+            *p = origin[k] + a * direction1[k] + b * direction2[k];
+        }
+        session.set_parameters(&point);
+
+        let mut total = 0.0;
+        for (x, y) in xs.iter().zip(ys) {
+            session.run(x, &mut output)?;
+            total += loss_fn(&output, y);
+        }
+
+        Ok(total / xs.len().max(1) as f32)
+    };
+
+    let losses: Result<Vec<Vec<f32>>, _> = coords
+        .iter()
+        .map(|b| coords.iter().map(|a| evaluate(*a, *b)).collect())
+        .collect();
+
+    Ok(LossSurface {
+        losses: losses?,
+        coords,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn loss_landscape() {
+        let model = MLP::seeded(2, &[3, 1], 5);
+        let xs = vec![vec![1.0, 0.5], vec![-0.5, 2.0], vec![0.0, -1.0]];
+        let ys = vec![vec![1.0], vec![-1.0], vec![0.5]];
+        let mse = |p: &[f32], t: &[f32]| (p[0] - t[0]).powi(2);
+        let before: Vec<f32> = model.parameters().iter().map(|p| p.data()).collect();
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let d1 = filtered_direction(&model, &mut rng);
+        let d2 = filtered_direction(&model, &mut rng);
+        assert_eq!(d1.len(), before.len());
+        // The first neuron's slice matches its parameters' norm.
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm(&d1[..3]) - norm(&before[..3])).abs() < 1e-5);
+
+        let grid = Grid {
+            extent: 1.0,
+            steps: 5,
+        };
+        let surface = loss_surface(&model, mse, (&xs, &ys), &d1, &d2, grid).unwrap();
+        assert_eq!(surface.coords, vec![-1.0, -0.5, 0.0, 0.5, 1.0]);
+        assert_eq!(surface.losses.len(), 5);
+
+        let center: f32 = xs
+            .iter()
+            .zip(&ys)
+            .map(|(x, y)| mse(&model.predict(x).unwrap(), y))
+            .sum::<f32>()
+            / 3.0;
+        assert!((surface.losses[2][2] - center).abs() < 1e-5);
+
+        let after: Vec<f32> = model.parameters().iter().map(|p| p.data()).collect();
+        assert_eq!(after, before);
+
+        let svg = surface.contour_svg(4, 200.0);
+        assert_eq!(svg.matches("<path").count(), 4);
+        assert!(svg.contains("<circle cx=\"100.00\""));
+
+        assert!(loss_surface(&model, mse, (&xs, &ys[..2]), &d1, &d2, grid).is_err());
+
+        // A panicking loss can't leave the model perturbed.
+        let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let fail = |_: &[f32], _: &[f32]| -> f32 { panic!("loss failed") };
+            loss_surface(&model, fail, (&xs, &ys), &d1, &d2, grid)
+        }));
+        assert!(caught.is_err());
+        let after: Vec<f32> = model.parameters().iter().map(|p| p.data()).collect();
+        assert_eq!(after, before);
+    }
+}
@@ -0,0 +1,105 @@
+//! Trains an MLP on a CSV file and optionally saves a checkpoint.
+//!
+//! Each row holds the inputs followed by the targets: one value per output
+//! with `--loss sum-squared` (or `mse`), or a single class index with
+//! `--loss cross-entropy`.
+//! A header row that doesn't parse as numbers is skipped.
+
+use micrograd::{prelude::*, train::read_csv};
+use std::{env, error::Error, process};
+
+const USAGE: &str = "usage: micrograd-cli <data.csv> [--layers 8,1] [--lr 0.05] \
+[--epochs 100] [--loss sum-squared|cross-entropy] [--seed 0] [--save model.ckpt]";
+
+struct Config {
+    data: String,
+    layers: Vec<usize>,
+    lr: f32,
+    epochs: usize,
+    loss: Loss,
+    seed: u64,
+    save: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Config, Box<dyn Error>> {
+    let mut config = Config {
+        data: args.next().ok_or(USAGE)?,
+        layers: vec![8, 1],
+        lr: 0.05,
+        epochs: 100,
+        loss: Loss::SumSquared,
+        seed: 0,
+        save: None,
+    };
+
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(USAGE)?;
+
+        match flag.as_str() {
+            "--layers" => {
+                config.layers = value.split(',').map(str::parse).collect::<Result<_, _>>()?
+            }
+            "--lr" => config.lr = value.parse()?,
+            "--epochs" => config.epochs = value.parse()?,
+            "--loss" => {
+                config.loss = match value.as_str() {
+                    "sum-squared" | "mse" => Loss::SumSquared,
+                    "cross-entropy" => Loss::CrossEntropy,
+                    _ => return Err(format!("unknown loss {value}").into()),
+                }
+            }
+            "--seed" => config.seed = value.parse()?,
+            "--save" => config.save = Some(value),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    Ok(config)
+}
+
+fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let nout = *config.layers.last().ok_or("--layers is empty")?;
+    let ntargets = match config.loss {
+        Loss::SumSquared => nout,
+        Loss::CrossEntropy => 1,
+    };
+    let (xs, ys) = read_csv(&config.data, ntargets)?;
+    let nin = xs.first().ok_or("no data rows")?.len();
+    MLP::check_shape(nin, &config.layers)?;
+
+    let model = MLP::seeded(nin, &config.layers, config.seed);
+    let mut trainer = Trainer::new(model, SGD::new(config.lr)).with_loss(config.loss);
+    let history = trainer.fit(&xs, &ys, config.epochs, &mut [])?;
+    let every = (config.epochs / 10).max(1);
+
+    for record in &history.epochs {
+        if (record.epoch + 1) % every == 0 {
+            println!("epoch {:>5}  loss {:.6}", record.epoch + 1, record.loss);
+        }
+    }
+
+    if config.loss == Loss::CrossEntropy {
+        let predicted = trainer.model().predict_class(&xs)?;
+        let correct = predicted
+            .iter()
+            .zip(&ys)
+            .filter(|(p, y)| **p == y[0] as usize)
+            .count();
+
+        println!("accuracy {:.4}", correct as f32 / xs.len() as f32);
+    }
+
+    if let Some(path) = &config.save {
+        trainer.save(path)?;
+        println!("saved {path}");
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = parse_args(env::args().skip(1)).and_then(run) {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+}
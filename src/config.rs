@@ -0,0 +1,310 @@
+//! Declarative experiment specifications, loaded from TOML or YAML:
+//!
+//! ```toml
+//! epochs = 200
+//! loss = "cross-entropy"
+//!
+//! [model]
+//! nin = 2
+//! layers = [8, 2]
+//! seed = 7
+//! init = { kind = "orthogonal", gain = 1.0 }
+//!
+//! [optimizer]
+//! kind = "sgd"
+//! lr = 0.1
+//!
+//! [schedule]
+//! kind = "linear"
+//! start = 0.1
+//! end = 0.01
+//! steps = 200
+//!
+//! [data]
+//! train = "xor.csv"
+//! ```
+
+use super::{
+    nn::{NeuronError, MLP},
+    optim::{Exponential, GradientNoise, Linear, Optimizer, Scheduled, SGD},
+    train::{read_csv, DataError, Dataset, Loss, Trainer},
+};
+use rand::SeedableRng;
+use serde::Deserialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrainSpec {
+    pub model: ModelSpec,
+    pub optimizer: OptimizerSpec,
+    #[serde(default)]
+    pub schedule: Option<ScheduleSpec>,
+    #[serde(default)]
+    pub loss: Loss,
+    /// Required: a spec that forgot it would otherwise train for nothing.
+    pub epochs: usize,
+    #[serde(default)]
+    pub data: Option<DataSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelSpec {
+    pub nin: usize,
+    /// Output size of each layer.
+    pub layers: Vec<usize>,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub init: InitSpec,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum InitSpec {
+    /// Uniform weights in `[-1, 1]`, as `MLP::seeded` draws them.
+    #[default]
+    Uniform,
+    Orthogonal {
+        gain: f32,
+    },
+    Identity,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum OptimizerSpec {
+    Sgd {
+        lr: f32,
+    },
+    /// SGD with annealed gradient noise, see `GradientNoise`.
+    NoisySgd {
+        lr: f32,
+        eta: f32,
+        gamma: f32,
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum ScheduleSpec {
+    Linear { start: f32, end: f32, steps: usize },
+    Exponential { initial: f32, gamma: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataSpec {
+    /// CSV of inputs followed by targets, read with `read_csv`.
+    pub train: PathBuf,
+    #[serde(default)]
+    pub validation: Option<PathBuf>,
+}
+
+impl TrainSpec {
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn from_yaml(s: &str) -> Result<Self, ConfigError> {
+        Ok(serde_yaml::from_str(s)?)
+    }
+
+    /// Reads a `.toml`, `.yaml` or `.yml` file. Relative data paths are
+    /// resolved against the file's directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+
+        let mut spec = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&text)?,
+            Some("yaml" | "yml") => Self::from_yaml(&text)?,
+            _ => return Err(ConfigError::ExtensionErr),
+        };
+
+        if let (Some(data), Some(dir)) = (&mut spec.data, path.parent()) {
+            data.train = dir.join(&data.train);
+            data.validation = data.validation.as_ref().map(|v| dir.join(v));
+        }
+
+        Ok(spec)
+    }
+
+    pub fn build_model(&self) -> Result<MLP, ConfigError> {
+        let ModelSpec {
+            nin,
+            layers,
+            seed,
+            init,
+        } = &self.model;
+
+        MLP::check_shape(*nin, layers)?;
+
+        let mut model = MLP::seeded(*nin, layers, *seed);
+
+        match init {
+            InitSpec::Uniform => {}
+            InitSpec::Orthogonal { gain } => {
+                let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(*seed);
+                model
+                    .layers_mut()
+                    .iter_mut()
+                    .for_each(|l| l.init_orthogonal(*gain, &mut rng));
+            }
+            InitSpec::Identity => model
+                .layers_mut()
+                .iter_mut()
+                .for_each(|l| l.init_identity()),
+        }
+
+        Ok(model)
+    }
+
+    pub fn build_optimizer(&self) -> Box<dyn Optimizer> {
+        let optimizer: Box<dyn Optimizer> = match self.optimizer {
+            OptimizerSpec::Sgd { lr } => Box::new(SGD::new(lr)),
+            OptimizerSpec::NoisySgd {
+                lr,
+                eta,
+                gamma,
+                seed,
+            } => Box::new(GradientNoise::new(SGD::new(lr), eta, gamma, seed)),
+        };
+
+        match self.schedule {
+            None => optimizer,
+            Some(ScheduleSpec::Linear { start, end, steps }) => {
+                Box::new(Scheduled::new(optimizer, Linear { start, end, steps }))
+            }
+            Some(ScheduleSpec::Exponential { initial, gamma }) => {
+                Box::new(Scheduled::new(optimizer, Exponential { initial, gamma }))
+            }
+        }
+    }
+
+    /// The model, optimizer and loss wired into a `Trainer`; train it for
+    /// `epochs` on `load_data`.
+    pub fn build(&self) -> Result<Trainer<Box<dyn Optimizer>>, ConfigError> {
+        Ok(Trainer::new(self.build_model()?, self.build_optimizer()).with_loss(self.loss))
+    }
+
+    /// Reads the training and, if given, validation sets.
+    pub fn load_data(&self) -> Result<(Dataset, Option<Dataset>), ConfigError> {
+        let data = self.data.as_ref().ok_or(ConfigError::NoDataErr)?;
+        let ntargets = match self.loss {
+            Loss::SumSquared => self.model.layers.last().cloned().unwrap_or(0),
+            Loss::CrossEntropy => 1,
+        };
+
+        let train = read_csv(&data.train, ntargets)?;
+        let validation = match &data.validation {
+            Some(path) => Some(read_csv(path, ntargets)?),
+            None => None,
+        };
+
+        Ok((train, validation))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("config io error")]
+    IoErr(#[from] io::Error),
+    #[error("invalid TOML spec: {0}")]
+    TomlErr(#[from] toml::de::Error),
+    #[error("invalid YAML spec: {0}")]
+    YamlErr(#[from] serde_yaml::Error),
+    #[error("spec files must end in .toml, .yaml or .yml")]
+    ExtensionErr,
+    #[error(transparent)]
+    ShapeErr(#[from] NeuronError),
+    #[error("spec has no data section")]
+    NoDataErr,
+    #[error(transparent)]
+    DataErr(#[from] DataError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+epochs = 20
+loss = "cross-entropy"
+
+[model]
+nin = 2
+layers = [4, 2]
+seed = 7
+init = { kind = "orthogonal", gain = 1.0 }
+
+[optimizer]
+kind = "sgd"
+lr = 0.1
+
+[schedule]
+kind = "exponential"
+initial = 0.2
+gamma = 0.9
+
+[data]
+train = "train.csv"
+"#;
+
+    const YAML: &str = "
+epochs: 20
+loss: cross-entropy
+model:
+  nin: 2
+  layers: [4, 2]
+  seed: 7
+  init: { kind: orthogonal, gain: 1.0 }
+optimizer: { kind: sgd, lr: 0.1 }
+schedule: { kind: exponential, initial: 0.2, gamma: 0.9 }
+data: { train: train.csv }
+";
+
+    #[test]
+    fn train_spec() {
+        let spec = TrainSpec::from_toml(TOML).unwrap();
+
+        assert_eq!(spec, TrainSpec::from_yaml(YAML).unwrap());
+        assert_eq!(spec.model.init, InitSpec::Orthogonal { gain: 1.0 });
+        let mse = TOML.replace("\"cross-entropy\"", "\"mse\"");
+        assert_eq!(TrainSpec::from_toml(&mse).unwrap().loss, Loss::SumSquared);
+        assert!(
+            TrainSpec::from_toml("epochs = 1\n[model]\nnin = 2\nlayers = []\nextra = 1").is_err()
+        );
+        let missing = TOML.replace("epochs = 20\n", "");
+        assert!(TrainSpec::from_toml(&missing)
+            .unwrap_err()
+            .to_string()
+            .contains("epochs"));
+
+        let dir = std::env::temp_dir().join(format!("micrograd-spec-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("spec.toml"), TOML).unwrap();
+        fs::write(dir.join("train.csv"), "x1,x2,y\n0,1,1\n1,0,0\n").unwrap();
+
+        let spec = TrainSpec::load(dir.join("spec.toml")).unwrap();
+        let ((xs, ys), validation) = spec.load_data().unwrap();
+        assert_eq!(xs, vec![vec![0.0, 1.0], vec![1.0, 0.0]]);
+        assert_eq!(ys, vec![vec![1.0], vec![0.0]]);
+        assert!(validation.is_none());
+
+        let mut trainer = spec.build().unwrap();
+        assert_eq!(trainer.model().describe().layers, vec![4, 2]);
+        let history = trainer.fit(&xs, &ys, spec.epochs, &mut []).unwrap();
+        assert_eq!(history.epochs[1].lr, Some(0.2 * 0.9));
+        assert!(history.losses()[19] < history.losses()[0]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
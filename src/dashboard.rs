@@ -0,0 +1,157 @@
+//! An egui panel following a training run: the loss curve, histograms of each
+//! layer's weights, and a summary of the latest loss graph. The panel is fed
+//! by a `Trainer` callback and can be shown from another thread.
+
+use super::{diagnostics::Histogram, engine::Scalar, nn::MLP, train::Callback};
+use egui::{
+    plot::{Bar, BarChart, Line, Plot, PlotPoints},
+    Grid, ScrollArea, Ui,
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct DashboardState {
+    pub losses: Vec<f32>,
+    /// Weight histogram of each layer after the latest backward pass.
+    pub weights: Vec<Histogram>,
+    pub graph_nodes: usize,
+    /// Number of nodes per op in the latest loss graph, leaves under "leaf".
+    pub graph_ops: BTreeMap<String, usize>,
+}
+
+/// Cheap to clone: clones share the same state, so one can be handed to
+/// `Trainer::fit` as a callback while another is drawn by the UI.
+#[derive(Clone)]
+pub struct Dashboard {
+    bins: usize,
+    state: Arc<Mutex<DashboardState>>,
+}
+
+impl Dashboard {
+    pub fn new(bins: usize) -> Self {
+        Self {
+            bins,
+            state: Arc::new(Mutex::new(DashboardState::default())),
+        }
+    }
+
+    pub fn state(&self) -> DashboardState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn ui(&self, ui: &mut Ui) {
+        let state = self.state();
+
+        ui.heading("Loss");
+        let loss: PlotPoints = state
+            .losses
+            .iter()
+            .enumerate()
+            .map(|(i, l)| [i as f64, *l as f64])
+            .collect();
+        Plot::new("loss")
+            .height(200.0)
+            .show(ui, |plot| plot.line(Line::new(loss)));
+
+        ui.separator();
+        ui.heading("Weights");
+        ui.horizontal(|ui| {
+            for (layer, h) in state.weights.iter().enumerate() {
+                let bars = h
+                    .bin_edges()
+                    .iter()
+                    .zip(&h.counts)
+                    .map(|((start, end), count)| {
+                        Bar::new(((start + end) / 2.0) as f64, *count as f64)
+                            .width((end - start) as f64)
+                    })
+                    .collect();
+
+                ui.vertical(|ui| {
+                    ui.label(format!("layer {layer}"));
+                    Plot::new(("weights", layer))
+                        .width(200.0)
+                        .height(150.0)
+                        .show(ui, |plot| plot.bar_chart(BarChart::new(bars)));
+                });
+            }
+        });
+
+        ui.separator();
+        ui.heading(format!("Graph ({} nodes)", state.graph_nodes));
+        ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("graph_ops").striped(true).show(ui, |ui| {
+                for (op, count) in &state.graph_ops {
+                    ui.label(op);
+                    ui.label(count.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+impl Callback for Dashboard {
+    fn on_graph(&mut self, _epoch: usize, loss: &Scalar<f32>) {
+        let mut ops = BTreeMap::new();
+        let mut nodes = 0;
+
+        for node in loss.iter_topological() {
+            let op = node.op().map_or("leaf".to_string(), |op| format!("{op:?}"));
+            *ops.entry(op).or_insert(0) += 1;
+            nodes += 1;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.graph_nodes = nodes;
+        state.graph_ops = ops;
+    }
+
+    fn on_backward(&mut self, _epoch: usize, model: &MLP) {
+        let weights = model
+            .layer_parameters()
+            .iter()
+            .map(|params| {
+                let data: Vec<f32> = params.iter().map(|p| p.data()).collect();
+                Histogram::new(&data, self.bins)
+            })
+            .collect();
+
+        self.state.lock().unwrap().weights = weights;
+    }
+
+    fn on_epoch_end(&mut self, _epoch: usize, loss: f32) {
+        self.state.lock().unwrap().losses.push(loss);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{optim::SGD, train::Trainer};
+
+    #[test]
+    fn dashboard_state() {
+        let mut rng = rand::thread_rng();
+        let dashboard = Dashboard::new(4);
+        let mut trainer = Trainer::new(MLP::new(2, &[3, 1], &mut rng), SGD::new(0.05));
+
+        trainer
+            .fit(
+                &[vec![1.0, -1.0]],
+                &[vec![1.0]],
+                3,
+                &mut [&mut dashboard.clone()],
+            )
+            .unwrap();
+
+        let state = dashboard.state();
+        assert_eq!(state.losses.len(), 3);
+        assert_eq!(state.weights.len(), 2);
+        assert_eq!(state.graph_ops["TANH"], 3);
+        assert_eq!(state.graph_nodes, state.graph_ops.values().sum::<usize>());
+    }
+}
@@ -0,0 +1,609 @@
+use super::{
+    explain::bars_svg,
+    nn::{json_number, Parameters, MLP},
+    train::Callback,
+};
+use std::{
+    fmt::Write,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradStats {
+    /// L2 norm of the gradients.
+    pub norm: f32,
+    /// Mean absolute gradient.
+    pub mean: f32,
+    /// Largest absolute gradient.
+    pub max: f32,
+    /// Fraction of gradients that are exactly zero.
+    pub zero_fraction: f32,
+}
+
+impl GradStats {
+    pub fn from_grads(grads: &[f32]) -> Self {
+        if grads.is_empty() {
+            return Self {
+                norm: 0.0,
+                mean: 0.0,
+                max: 0.0,
+                zero_fraction: 0.0,
+            };
+        }
+
+        let n = grads.len() as f32;
+
+        Self {
+            norm: grads.iter().map(|g| g * g).sum::<f32>().sqrt(),
+            mean: grads.iter().map(|g| g.abs()).sum::<f32>() / n,
+            max: grads.iter().fold(0.0, |acc, g| acc.max(g.abs())),
+            zero_fraction: grads.iter().filter(|g| **g == 0.0).count() as f32 / n,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GradReport {
+    /// Gradient of every parameter, in `parameters()` order.
+    pub grads: Vec<f32>,
+    pub layers: Vec<GradStats>,
+    pub overall: GradStats,
+}
+
+/// Summarizes the current gradients of parameters grouped by layer, e.g.
+/// `grad_report(&mlp.layer_parameters())` after `loss.backward()`.
+pub fn grad_report(layers: &[Parameters]) -> GradReport {
+    let layer_grads: Vec<Vec<f32>> = layers
+        .iter()
+        .map(|params| params.iter().map(|p| p.grad()).collect())
+        .collect();
+    let grads: Vec<f32> = layer_grads.concat();
+
+    GradReport {
+        layers: layer_grads
+            .iter()
+            .map(|g| GradStats::from_grads(g))
+            .collect(),
+        overall: GradStats::from_grads(&grads),
+        grads,
+    }
+}
+
+/// Mean absolute gradient of each layer's parameters, input layer first.
+/// Call after `backward`; values shrinking towards the input show vanishing
+/// gradients.
+pub fn gradient_flow(mlp: &MLP) -> Vec<f32> {
+    grad_report(&mlp.layer_parameters())
+        .layers
+        .iter()
+        .map(|stats| stats.mean)
+        .collect()
+}
+
+/// Bar chart of a `gradient_flow`, one row per layer.
+pub fn gradient_flow_svg(flow: &[f32], width: f32) -> String {
+    let labels: Vec<String> = (0..flow.len()).map(|i| format!("layer {i}")).collect();
+    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+
+    bars_svg(flow, &labels, width)
+}
+
+/// Trainer callback recording a `GradReport` after every backward pass.
+#[derive(Default)]
+pub struct GradMonitor {
+    pub reports: Vec<GradReport>,
+}
+
+impl Callback for GradMonitor {
+    fn on_backward(&mut self, _epoch: usize, model: &MLP) {
+        self.reports.push(grad_report(&model.layer_parameters()));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Buckets `values` into `bins` equal-width bins spanning their range.
+    pub fn new(values: &[f32], bins: usize) -> Self {
+        let bins = bins.max(1);
+        let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let mut counts = vec![0; bins];
+
+        if values.is_empty() {
+            return Self {
+                min: 0.0,
+                max: 0.0,
+                counts,
+            };
+        }
+
+        let width = (max - min) / bins as f32;
+
+        for v in values {
+            let i = if width > 0.0 {
+                (((v - min) / width) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            counts[i] += 1;
+        }
+
+        Self { min, max, counts }
+    }
+
+    pub fn bin_edges(&self) -> Vec<(f32, f32)> {
+        let width = (self.max - self.min) / self.counts.len() as f32;
+
+        (0..self.counts.len())
+            .map(|i| {
+                (
+                    self.min + width * i as f32,
+                    self.min + width * (i + 1) as f32,
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HistogramRecord {
+    pub epoch: usize,
+    pub layer: usize,
+    pub weights: Histogram,
+    pub grads: Histogram,
+}
+
+/// Trainer callback recording per-layer weight and gradient histograms every epoch.
+pub struct HistogramRecorder {
+    bins: usize,
+    pub records: Vec<HistogramRecord>,
+}
+
+impl HistogramRecorder {
+    pub fn new(bins: usize) -> Self {
+        Self {
+            bins,
+            records: vec![],
+        }
+    }
+
+    pub fn record(&mut self, epoch: usize, model: &MLP) {
+        for (layer, params) in model.layer_parameters().iter().enumerate() {
+            let weights: Vec<f32> = params.iter().map(|p| p.data()).collect();
+            let grads: Vec<f32> = params.iter().map(|p| p.grad()).collect();
+
+            self.records.push(HistogramRecord {
+                epoch,
+                layer,
+                weights: Histogram::new(&weights, self.bins),
+                grads: Histogram::new(&grads, self.bins),
+            });
+        }
+    }
+
+    /// One row per bin: `epoch,layer,kind,bin_start,bin_end,count`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("epoch,layer,kind,bin_start,bin_end,count\n");
+
+        for r in &self.records {
+            for (kind, h) in [("weights", &r.weights), ("grads", &r.grads)] {
+                for ((start, end), count) in h.bin_edges().iter().zip(&h.counts) {
+                    writeln!(
+                        csv,
+                        "{},{},{},{},{},{}",
+                        r.epoch, r.layer, kind, start, end, count
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        let histogram = |h: &Histogram| {
+            format!(
+                "{{\"min\":{},\"max\":{},\"counts\":{:?}}}",
+                json_number(h.min),
+                json_number(h.max),
+                h.counts
+            )
+        };
+
+        let records: Vec<String> = self
+            .records
+            .iter()
+            .map(|r| {
+                format!(
+                    "{{\"epoch\":{},\"layer\":{},\"weights\":{},\"grads\":{}}}",
+                    r.epoch,
+                    r.layer,
+                    histogram(&r.weights),
+                    histogram(&r.grads)
+                )
+            })
+            .collect();
+
+        format!("[{}]", records.join(","))
+    }
+}
+
+impl Callback for HistogramRecorder {
+    fn on_backward(&mut self, epoch: usize, model: &MLP) {
+        self.record(epoch, model);
+    }
+}
+
+/// Trainer callback keeping a flattened copy of the model's parameters every
+/// `every` epochs, taken after the backward pass and before the step, for
+/// analyzing the path training takes through weight space.
+pub struct ParamSnapshots {
+    every: usize,
+    /// `(epoch, parameters)` pairs, in `parameters()` order.
+    pub snapshots: Vec<(usize, Vec<f32>)>,
+}
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"MGPS";
+
+impl ParamSnapshots {
+    pub fn new(every: usize) -> Self {
+        Self {
+            every: every.max(1),
+            snapshots: vec![],
+        }
+    }
+
+    /// Writes the snapshots as little-endian binary: the magic `MGPS`, the
+    /// snapshot count and parameter count as `u32`, then per snapshot its
+    /// epoch as `u64` followed by the parameters as `f32`.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let dim = self.snapshots.first().map_or(0, |(_, p)| p.len());
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend((self.snapshots.len() as u32).to_le_bytes());
+        bytes.extend((dim as u32).to_le_bytes());
+
+        for (epoch, params) in &self.snapshots {
+            if params.len() != dim {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "snapshots differ in length",
+                ));
+            }
+
+            bytes.extend((*epoch as u64).to_le_bytes());
+            bytes.extend(params.iter().flat_map(|p| p.to_le_bytes()));
+        }
+
+        fs::write(path, bytes)
+    }
+
+    /// Reads a file written by `save`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "malformed snapshot file");
+        let word = |at: usize| -> Option<u32> {
+            Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+        };
+
+        if bytes.get(..4) != Some(SNAPSHOT_MAGIC) {
+            return Err(invalid());
+        }
+
+        let (count, dim) = (
+            word(4).ok_or_else(invalid)? as usize,
+            word(8).ok_or_else(invalid)? as usize,
+        );
+        // The header is untrusted: sizes that overflow are as malformed as
+        // ones that disagree with the file length.
+        let record = dim
+            .checked_mul(4)
+            .and_then(|d| d.checked_add(8))
+            .ok_or_else(invalid)?;
+        let len = count
+            .checked_mul(record)
+            .and_then(|l| l.checked_add(12))
+            .ok_or_else(invalid)?;
+
+        if bytes.len() != len {
+            return Err(invalid());
+        }
+
+        let snapshots = bytes[12..]
+            .chunks(record)
+            .map(|r| {
+                let epoch = u64::from_le_bytes(r[..8].try_into().unwrap()) as usize;
+                let params = r[8..]
+                    .chunks(4)
+                    .map(|p| f32::from_le_bytes(p.try_into().unwrap()))
+                    .collect();
+
+                (epoch, params)
+            })
+            .collect();
+
+        Ok(Self {
+            every: 1,
+            snapshots,
+        })
+    }
+
+    /// The snapshots projected onto their first two principal components.
+    pub fn trajectory(&self) -> Vec<(f32, f32)> {
+        let points: Vec<Vec<f32>> = self.snapshots.iter().map(|(_, p)| p.clone()).collect();
+
+        pca_2d(&points)
+    }
+}
+
+impl Callback for ParamSnapshots {
+    fn on_backward(&mut self, epoch: usize, model: &MLP) {
+        if epoch.is_multiple_of(self.every) {
+            let params = model.parameters().iter().map(|p| p.data()).collect();
+            self.snapshots.push((epoch, params));
+        }
+    }
+}
+
+/// Projects equally long `points` onto their two directions of largest
+/// variance, centered on their mean. The components come from power
+/// iteration on the points' Gram matrix, which stays small for few points in
+/// many dimensions; each axis is oriented so its largest coordinate is positive.
+pub fn pca_2d(points: &[Vec<f32>]) -> Vec<(f32, f32)> {
+    let n = points.len();
+    let dim = points.first().map_or(0, |p| p.len());
+    let mean: Vec<f64> = (0..dim)
+        .map(|j| points.iter().map(|p| p[j] as f64).sum::<f64>() / n as f64)
+        .collect();
+    let centered: Vec<Vec<f64>> = points
+        .iter()
+        .map(|p| p.iter().zip(&mean).map(|(x, m)| *x as f64 - m).collect())
+        .collect();
+
+    let mut gram: Vec<Vec<f64>> = centered
+        .iter()
+        .map(|a| {
+            centered
+                .iter()
+                .map(|b| a.iter().zip(b).map(|(x, y)| x * y).sum())
+                .collect()
+        })
+        .collect();
+
+    let mut axes = vec![];
+
+    for _ in 0..2 {
+        let mut u: Vec<f64> = (0..n).map(|i| 1.0 + i as f64).collect();
+        let mut lambda = 0.0;
+
+        for _ in 0..500 {
+            let next: Vec<f64> = gram
+                .iter()
+                .map(|row| row.iter().zip(&u).map(|(g, u)| g * u).sum())
+                .collect();
+            let norm = next.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+            if norm == 0.0 {
+                lambda = 0.0;
+                break;
+            }
+
+            lambda = norm;
+            u = next.iter().map(|x| x / norm).collect();
+        }
+
+        // Gram eigenvector u with eigenvalue λ gives coordinates u * sqrt(λ).
+        let flip = u
+            .iter()
+            .cloned()
+            .fold(0.0f64, |m, x| if x.abs() > m.abs() { x } else { m })
+            .signum();
+        axes.push(
+            u.iter()
+                .map(|x| x * lambda.sqrt() * flip)
+                .collect::<Vec<f64>>(),
+        );
+
+        for (i, row) in gram.iter_mut().enumerate() {
+            for (j, g) in row.iter_mut().enumerate() {
+                *g -= lambda * u[i] * u[j];
+            }
+        }
+    }
+
+    (0..n)
+        .map(|i| (axes[0][i] as f32, axes[1][i] as f32))
+        .collect()
+}
+
+/// ROC curve of binary classifier `scores` against `labels`, as
+/// `(false positive rate, true positive rate)` points from the highest
+/// threshold down, starting at `(0, 0)` and ending at `(1, 1)`. Tied scores
+/// form a single step.
+pub fn roc_curve(scores: &[f32], labels: &[bool]) -> Vec<(f32, f32)> {
+    let mut ranked: Vec<(f32, bool)> = scores.iter().cloned().zip(labels.iter().cloned()).collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let positives = ranked.iter().filter(|(_, l)| *l).count().max(1) as f32;
+    let negatives = ranked.iter().filter(|(_, l)| !*l).count().max(1) as f32;
+    let (mut tp, mut fp) = (0.0, 0.0);
+    let mut points = vec![(0.0, 0.0)];
+
+    for (i, (score, label)) in ranked.iter().enumerate() {
+        if *label {
+            tp += 1.0;
+        } else {
+            fp += 1.0;
+        }
+
+        if ranked.get(i + 1).is_none_or(|next| next.0 != *score) {
+            points.push((fp / negatives, tp / positives));
+        }
+    }
+
+    points
+}
+
+/// Area under a curve given as points sorted by x, by the trapezoidal rule.
+pub fn auc(points: &[(f32, f32)]) -> f32 {
+    points
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0) * (w[0].1 + w[1].1) / 2.0)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Scalar;
+
+    #[test]
+    fn grad_stats() {
+        let stats = GradStats::from_grads(&[3.0, -4.0, 0.0, 0.0]);
+
+        assert_eq!(stats.norm, 5.0);
+        assert_eq!(stats.mean, 1.75);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.zero_fraction, 0.5);
+
+        let a = Scalar::new(2.0, "a");
+        let b = Scalar::new(3.0, "b");
+        let c = a.clone() * b.clone();
+        c.backward();
+
+        let report = grad_report(&[vec![a].into(), vec![b].into()]);
+
+        assert_eq!(report.grads, vec![3.0, 2.0]);
+        assert_eq!(report.layers[0].max, 3.0);
+        assert_eq!(report.overall.norm, 13.0f32.sqrt());
+    }
+
+    #[test]
+    fn gradient_flow_report() {
+        let mut mlp = MLP::seeded(3, &[4, 4, 4, 1], 2);
+        let y = mlp.output(vec![
+            Scalar::new(0.5, ""),
+            Scalar::new(-1.0, ""),
+            Scalar::new(2.0, ""),
+        ]);
+        y.unwrap()[0].backward();
+
+        let flow = gradient_flow(&mlp);
+        assert_eq!(flow.len(), 4);
+        assert_eq!(flow[0], grad_report(&mlp.layer_parameters()).layers[0].mean);
+        assert!(flow.iter().all(|g| *g > 0.0));
+
+        let svg = gradient_flow_svg(&flow, 300.0);
+        assert_eq!(svg.matches("<rect").count(), 4);
+        assert!(svg.contains(">layer 3</text>"));
+    }
+
+    #[test]
+    fn histogram() {
+        let h = Histogram::new(&[0.0, 0.1, 0.5, 0.9, 1.0], 2);
+
+        assert_eq!(h.counts, vec![2, 3]);
+        assert_eq!(h.bin_edges(), vec![(0.0, 0.5), (0.5, 1.0)]);
+        assert_eq!(Histogram::new(&[2.0, 2.0], 3).counts, vec![2, 0, 0]);
+    }
+
+    #[test]
+    fn histogram_json() {
+        let mlp = MLP::seeded(2, &[2, 1], 0);
+        mlp.parameters()[0].set_grad(f32::INFINITY);
+
+        let mut recorder = HistogramRecorder::new(3);
+        recorder.record(0, &mlp);
+        let json = recorder.to_json();
+
+        assert!(json.contains("\"grads\":{\"min\":0,\"max\":null,"));
+        assert!(!json.contains("inf") && !json.contains("NaN"));
+    }
+
+    #[test]
+    fn param_snapshots() {
+        use crate::{optim::SGD, train::Trainer};
+
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0]];
+        let ys = vec![vec![1.0], vec![-1.0]];
+        let mut trainer = Trainer::new(MLP::seeded(2, &[3, 1], 0), SGD::new(0.05));
+        let mut snapshots = ParamSnapshots::new(2);
+
+        trainer.fit(&xs, &ys, 6, &mut [&mut snapshots]).unwrap();
+        assert_eq!(
+            snapshots
+                .snapshots
+                .iter()
+                .map(|(e, _)| *e)
+                .collect::<Vec<_>>(),
+            vec![0, 2, 4]
+        );
+        assert_eq!(snapshots.snapshots[0].1.len(), 13);
+
+        let path = std::env::temp_dir().join(format!("micrograd-snap-{}", std::process::id()));
+        snapshots.save(&path).unwrap();
+        assert_eq!(
+            ParamSnapshots::load(&path).unwrap().snapshots,
+            snapshots.snapshots
+        );
+        fs::write(&path, b"MGPS\x01\0\0\0").unwrap();
+        assert!(ParamSnapshots::load(&path).is_err());
+        // count = dim = u32::MAX overflows the expected length.
+        fs::write(&path, b"MGPS\xff\xff\xff\xff\xff\xff\xff\xff").unwrap();
+        assert!(matches!(
+            ParamSnapshots::load(&path),
+            Err(e) if e.kind() == ErrorKind::InvalidData
+        ));
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(snapshots.trajectory().len(), 3);
+    }
+
+    #[test]
+    fn pca() {
+        // Points on a tilted plane in 4-D keep their pairwise distances.
+        let points: Vec<Vec<f32>> = [(0.0, 0.0), (3.0, 0.0), (0.0, 1.0), (3.0, 1.0), (1.0, 0.5)]
+            .iter()
+            .map(|(a, b)| vec![a + b, a - b, 2.0 * b, 1.0])
+            .collect();
+        let projected = pca_2d(&points);
+
+        let dist = |a: &[f32], b: &[f32]| {
+            a.iter()
+                .zip(b)
+                .map(|(x, y)| (x - y).powi(2))
+                .sum::<f32>()
+                .sqrt()
+        };
+
+        for i in 0..points.len() {
+            for j in 0..points.len() {
+                let (pi, pj) = (projected[i], projected[j]);
+                let d2 = dist(&[pi.0, pi.1], &[pj.0, pj.1]);
+                assert!((dist(&points[i], &points[j]) - d2).abs() < 1e-3);
+            }
+        }
+
+        // The first axis carries the most variance.
+        let spread =
+            |f: fn(&(f32, f32)) -> f32| projected.iter().map(|p| f(p).powi(2)).sum::<f32>();
+        assert!(spread(|p| p.0) > spread(|p| p.1));
+    }
+
+    #[test]
+    fn roc() {
+        let points = roc_curve(&[0.9, 0.8, 0.8, 0.3], &[true, false, true, false]);
+
+        assert_eq!(points, vec![(0.0, 0.0), (0.0, 0.5), (0.5, 1.0), (1.0, 1.0)]);
+        assert_eq!(auc(&points), 0.875);
+        assert_eq!(auc(&roc_curve(&[0.2, 0.1], &[true, false])), 1.0);
+    }
+}
@@ -6,51 +6,339 @@ use layout::{
     topo::layout::VisualGraph,
 };
 use std::{
+    any::Any,
+    cell::{Cell, RefCell},
     cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd},
-    fmt::Display,
-    ops::{Add, AddAssign, Mul, Sub},
-    sync::{Arc, Mutex},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    env,
+    fmt::{self, Debug, Display, Formatter},
+    hash::{Hash, Hasher},
+    mem::size_of,
+    ops::{Add, AddAssign, Deref, Div, Mul, Neg, Sub},
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc, Mutex, OnceLock, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+use rand::Rng;
+use thiserror::Error;
+
+#[cfg(feature = "atomic-grad")]
+use std::marker::PhantomData;
+
 pub use num_traits::{Float, NumAssignOps, Zero};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Op {
     ADD,
     SUB,
     MUL,
-    // DIV,
+    DIV,
+    NEG,
+    MAX,
+    MIN,
     POWI(i32),
+    POWF(f32),
+    POW,
     TANH,
+    SIGMOID,
+    /// Leaky ReLU with the given slope for negative inputs.
+    LEAKYRELU(f32),
+    GELU,
+    SOFTPLUS,
+    SILU,
+    /// ELU with the given scale of the negative branch.
+    ELU(f32),
+    SQRT,
+    SELECT,
+    FLOOR,
+    CEIL,
+    ROUND,
+    /// Sign, with a straight-through gradient when the flag is set.
+    SIGN(bool),
+    /// Rounding to a signed `bits`-bit grid of step `scale`, with a
+    /// straight-through gradient.
+    QUANT {
+        bits: u32,
+        scale: f32,
+    },
+    /// Clipping to `[lo, hi]`, passing the gradient only inside the range.
+    CLAMP {
+        lo: f32,
+        hi: f32,
+    },
+    LN,
+    LOG2,
+    LOG10,
+    EXP,
+    CUSTOM(Arc<dyn CustomOp>),
+}
+
+/// A user-defined differentiable function of any number of inputs, applied
+/// with `Scalar::apply_custom`.
+pub trait CustomOp: Debug + Send + Sync {
+    /// Symbol shown for the node when drawing the graph.
+    fn name(&self) -> &str;
+
+    fn forward(&self, inputs: &[f32]) -> f32;
+
+    /// Gradient with respect to each input, given the upstream gradient `out_grad`.
+    fn backward(&self, inputs: &[f32], output: f32, out_grad: f32) -> Vec<f32>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BackwardOptions {
+    /// Keep the links from each node to its children after backward. When
+    /// false they are dropped as soon as a node has passed its gradient on,
+    /// freeing intermediate nodes no longer referenced elsewhere; the graph
+    /// can then be neither re-run with `forward` nor backpropagated again.
+    pub retain_graph: bool,
+}
+
+impl Default for BackwardOptions {
+    fn default() -> Self {
+        Self { retain_graph: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ParallelOptions {
+    /// Worker threads spawned per graph level. The crate keeps no global
+    /// pool: workers are scoped to the call and joined before it returns.
+    pub threads: usize,
+    /// Apply the workers' gradient contributions in a fixed order instead of
+    /// as they finish, making results bit-identical from run to run and for
+    /// any thread count, at the cost of buffering them.
+    pub deterministic: bool,
+}
+
+impl Default for ParallelOptions {
+    /// `threads` comes from the `MICROGRAD_THREADS` environment variable if
+    /// set, so an application can cap the crate without touching call sites,
+    /// and from the available parallelism otherwise.
+    fn default() -> Self {
+        Self {
+            threads: default_threads(env::var(THREADS_VAR).ok().as_deref()),
+            deterministic: false,
+        }
+    }
+}
+
+impl ParallelOptions {
+    pub fn with_threads(threads: usize) -> Self {
+        Self {
+            threads,
+            ..Self::default()
+        }
+    }
+}
+
+pub const THREADS_VAR: &str = "MICROGRAD_THREADS";
+
+fn default_threads(var: Option<&str>) -> usize {
+    var.and_then(|v| v.trim().parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
 }
 
+#[derive(Debug, Clone)]
+pub struct BackwardReport {
+    pub nodes_visited: usize,
+    /// Non-leaf nodes whose gradient was propagated to their children.
+    pub ops_executed: usize,
+    pub elapsed: Duration,
+    /// Nodes left with a NaN gradient.
+    pub nan_count: usize,
+    /// Largest absolute gradient, ignoring NaNs.
+    pub max_abs_grad: f32,
+}
+
+/// Source of node ids, unique for the life of the process.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug)]
 struct Value<T: Float + NumAssignOps> {
+    id: u64,
     data: T,
-    children: (Option<Scalar<T>>, Option<Scalar<T>>),
+    children: Vec<Child<T>>,
     op: Option<Op>,
     label: String,
-    grad: T,
+    meta: Option<Box<Metadata>>,
+}
+
+/// User data attached to a node, keyed by name.
+#[derive(Default)]
+struct Metadata(HashMap<String, Box<dyn Any + Send>>);
+
+impl Debug for Metadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
 }
 
 impl<T: Float + NumAssignOps> Value<T> {
+    /// One scalar per operand, in order. A weak link to a dropped node is
+    /// replaced by a constant holding its id and last data, once, so the
+    /// operand keeps the same identity on every later call.
+    fn children(&mut self) -> Vec<Scalar<T>> {
+        self.children.iter_mut().map(Child::upgrade).collect()
+    }
+
     fn new(data: T, label: &str) -> Self {
         Self {
+            id: NEXT_ID.fetch_add(1, atomic::Ordering::Relaxed),
             data,
-            children: (None, None),
+            children: vec![],
             op: None,
             label: label.to_string(),
-            grad: Zero::zero(),
+            meta: None,
+        }
+    }
+}
+
+/// Link from a node to one of its inputs. Weak links don't keep the input
+/// alive, but share a `Frozen` copy of it for when it is gone; see
+/// `Scalar::weaken`.
+#[derive(Debug)]
+enum Child<T: Float + NumAssignOps> {
+    Strong(Scalar<T>),
+    Weak(Weak<Node<T>>, Arc<Frozen<T>>),
+}
+
+/// Id and data of a weakly linked node, and the constant standing in for it
+/// once it is dropped. Every link to the node shares one, so they all see the
+/// same stand-in.
+#[derive(Debug)]
+struct Frozen<T: Float + NumAssignOps> {
+    id: u64,
+    data: T,
+    constant: OnceLock<Scalar<T>>,
+}
+
+impl<T: Float + NumAssignOps> Child<T> {
+    fn upgrade(&mut self) -> Scalar<T> {
+        match self {
+            Child::Strong(s) => s.clone(),
+            Child::Weak(w, frozen) => match w.upgrade() {
+                Some(node) => Scalar(node),
+                None => {
+                    // The stand-in takes over the dropped node's id, so it
+                    // keeps its place in id order and id-keyed lookups.
+                    let constant = frozen
+                        .constant
+                        .get_or_init(|| {
+                            let mut value = Value::new(frozen.data, "");
+                            value.id = frozen.id;
+                            Scalar::from_value(value)
+                        })
+                        .clone();
+                    *self = Child::Strong(constant.clone());
+                    constant
+                }
+            },
         }
     }
 }
 
+impl<T: Float + NumAssignOps> From<Scalar<T>> for Child<T> {
+    fn from(s: Scalar<T>) -> Self {
+        Child::Strong(s)
+    }
+}
+
+/// Gradient of a node, kept outside the value's mutex so backward can
+/// accumulate into a child without locking it. With the `atomic-grad`
+/// feature this is a lock-free atomic.
+#[cfg(not(feature = "atomic-grad"))]
+#[derive(Debug)]
+struct Grad<T>(Mutex<T>);
+
+#[cfg(not(feature = "atomic-grad"))]
+impl<T: Float> Grad<T> {
+    fn new() -> Self {
+        Grad(Mutex::new(T::zero()))
+    }
+
+    fn get(&self) -> T {
+        *self.0.lock().unwrap()
+    }
+
+    fn set(&self, grad: T) {
+        *self.0.lock().unwrap() = grad;
+    }
+
+    fn add(&self, grad: T) {
+        let mut g = self.0.lock().unwrap();
+        *g = *g + grad;
+    }
+}
+
+/// Stores the gradient as `f64` bits, which holds both `f32` and `f64` exactly.
+#[cfg(feature = "atomic-grad")]
+#[derive(Debug)]
+struct Grad<T>(AtomicU64, PhantomData<T>);
+
+#[cfg(feature = "atomic-grad")]
+impl<T: Float> Grad<T> {
+    fn new() -> Self {
+        Grad(AtomicU64::new(0f64.to_bits()), PhantomData)
+    }
+
+    fn get(&self) -> T {
+        T::from(f64::from_bits(self.0.load(atomic::Ordering::Acquire))).unwrap()
+    }
+
+    fn set(&self, grad: T) {
+        self.0
+            .store(grad.to_f64().unwrap().to_bits(), atomic::Ordering::Release);
+    }
+
+    fn add(&self, grad: T) {
+        let grad = grad.to_f64().unwrap();
+
+        self.0
+            .fetch_update(
+                atomic::Ordering::AcqRel,
+                atomic::Ordering::Acquire,
+                |bits| Some((f64::from_bits(bits) + grad).to_bits()),
+            )
+            .unwrap();
+    }
+}
+
+#[derive(Debug)]
+struct Node<T: Float + NumAssignOps> {
+    value: Mutex<Value<T>>,
+    grad: Grad<T>,
+}
+
+impl<T: Float + NumAssignOps> Deref for Node<T> {
+    type Target = Mutex<Value<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+/// A node of the computation graph. Cloning shares the node. `Scalar` is
+/// `Send + Sync` whenever `T` is, so graphs can be built and differentiated
+/// across threads.
 #[derive(Debug, Clone)]
-pub struct Scalar<T: Float + NumAssignOps>(Arc<Mutex<Value<T>>>);
+pub struct Scalar<T: Float + NumAssignOps>(Arc<Node<T>>);
+
+const _: () = {
+    const fn assert_send_sync<S: Send + Sync>() {}
+
+    assert_send_sync::<Scalar<f32>>();
+    assert_send_sync::<Scalar<f64>>();
+};
 
 impl Scalar<f32> {
     pub fn new(data: f32, label: &str) -> Self {
-        Scalar(Arc::new(Mutex::new(Value::new(data, label))))
+        Scalar::from_value(Value::new(data, label))
     }
 
     pub fn label(&self, l: &str) {
@@ -60,477 +348,3516 @@ impl Scalar<f32> {
     }
 
     fn cal_grad(&self) {
-        let value = self.0.lock().unwrap();
+        let Some(start) = profile_start() else {
+            return self.cal_grad_with(|c, g| c.add_grad(g));
+        };
+
+        self.cal_grad_with(|c, g| c.add_grad(g));
+
+        if let Some(op) = self.op() {
+            profile_record(&op, |p| {
+                p.backward += 1;
+                p.backward_time += start.elapsed();
+            });
+        }
+    }
+
+    /// Computes this node's contribution to each child's gradient, handing
+    /// each one to `add` instead of accumulating it directly.
+    fn cal_grad_with(&self, mut add: impl FnMut(&Scalar<f32>, f32)) {
+        let mut value = self.0.lock().unwrap();
+        let grad = self.grad();
+        let children = value.children();
 
         match value.op {
             Some(Op::ADD) => {
-                if let (Some(c1), Some(c2)) = &value.children {
-                    let mut v1 = c1.0.lock().unwrap();
-                    v1.grad += value.grad;
-                    drop(v1);
-
-                    let mut v2 = c2.0.lock().unwrap();
-                    v2.grad += value.grad;
+                if let [c1, c2] = children.as_slice() {
+                    add(c1, grad);
+                    add(c2, grad);
                 }
             }
             Some(Op::SUB) => {
-                if let (Some(c1), Some(c2)) = &value.children {
-                    let mut v1 = c1.0.lock().unwrap();
-                    v1.grad += value.grad;
-                    drop(v1);
-
-                    let mut v2 = c2.0.lock().unwrap();
-                    v2.grad += -value.grad;
+                if let [c1, c2] = children.as_slice() {
+                    add(c1, grad);
+                    add(c2, -grad);
                 }
             }
             Some(Op::MUL) => {
-                if let (Some(c1), Some(c2)) = &value.children {
-                    let v1 = c1.0.lock().unwrap();
-                    let v1_data = v1.data;
-                    drop(v1);
-
-                    let mut v2 = c2.0.lock().unwrap();
-                    let v2_data = v2.data;
-                    v2.grad += v1_data * value.grad;
-                    drop(v2);
-
-                    let mut v1 = c1.0.lock().unwrap();
-                    v1.grad += v2_data * value.grad;
+                if let [c1, c2] = children.as_slice() {
+                    add(c2, c1.data() * grad);
+                    add(c1, c2.data() * grad);
+                }
+            }
+            Some(Op::DIV) => {
+                if let [c1, c2] = children.as_slice() {
+                    let b = c2.data();
+                    add(c1, grad / b);
+                    add(c2, -c1.data() / (b * b) * grad);
+                }
+            }
+            Some(Op::NEG) => {
+                if let [c] = children.as_slice() {
+                    add(c, -grad);
+                }
+            }
+            Some(Op::MAX) | Some(Op::MIN) => {
+                if let [c1, c2] = children.as_slice() {
+                    // Ties go to the first operand and NaN to the operand
+                    // holding it, like the forward pass.
+                    if c1.data() == value.data || c1.data().is_nan() {
+                        add(c1, grad);
+                    } else {
+                        add(c2, grad);
+                    }
                 }
             }
             Some(Op::POWI(n)) => {
-                if let (Some(c), None) = &value.children {
-                    let mut v = c.0.lock().unwrap();
-                    v.grad += (n as f32 * v.data.powi(n - 1)) * value.grad;
+                if let [c] = children.as_slice() {
+                    add(c, (n as f32 * c.data().powi(n - 1)) * grad);
+                }
+            }
+            Some(Op::POWF(n)) => {
+                if let [c] = children.as_slice() {
+                    add(c, (n * c.data().powf(n - 1.0)) * grad);
+                }
+            }
+            Some(Op::POW) => {
+                if let [c1, c2] = children.as_slice() {
+                    let (a, b) = (c1.data(), c2.data());
+                    add(c1, b * a.powf(b - 1.0) * grad);
+                    // a^b * ln(a) only exists for a positive base.
+                    if a > 0.0 {
+                        add(c2, value.data * a.ln() * grad);
+                    }
                 }
             }
             Some(Op::TANH) => {
-                if let (Some(c), None) = &value.children {
-                    let mut v = c.0.lock().unwrap();
-                    v.grad += (1.0 - value.data.powi(2)) * value.grad;
+                if let [c] = children.as_slice() {
+                    add(c, (1.0 - value.data.powi(2)) * grad);
+                }
+            }
+            Some(Op::SIGMOID) => {
+                if let [c] = children.as_slice() {
+                    add(c, value.data * (1.0 - value.data) * grad);
+                }
+            }
+            Some(Op::LEAKYRELU(alpha)) => {
+                if let [c] = children.as_slice() {
+                    add(c, if c.data() > 0.0 { grad } else { alpha * grad });
+                }
+            }
+            Some(Op::GELU) => {
+                if let [c] = children.as_slice() {
+                    let x = c.data();
+                    let t = (GELU_SCALE * (x + GELU_CUBIC * x.powi(3))).tanh();
+                    let dt = (1.0 - t * t) * GELU_SCALE * (1.0 + 3.0 * GELU_CUBIC * x * x);
+                    add(c, (0.5 * (1.0 + t) + 0.5 * x * dt) * grad);
+                }
+            }
+            Some(Op::SOFTPLUS) => {
+                if let [c] = children.as_slice() {
+                    add(c, sigmoid(c.data()) * grad);
+                }
+            }
+            Some(Op::SILU) => {
+                if let [c] = children.as_slice() {
+                    let (x, s) = (c.data(), sigmoid(c.data()));
+                    add(c, (s + x * s * (1.0 - s)) * grad);
+                }
+            }
+            Some(Op::ELU(alpha)) => {
+                if let [c] = children.as_slice() {
+                    let d = if c.data() > 0.0 {
+                        1.0
+                    } else {
+                        value.data + alpha
+                    };
+                    add(c, d * grad);
+                }
+            }
+            Some(Op::SQRT) => {
+                if let [c] = children.as_slice() {
+                    add(c, 0.5 / value.data * grad);
+                }
+            }
+            // Piecewise constant: the derivative is zero wherever it exists.
+            Some(Op::FLOOR | Op::CEIL | Op::ROUND) => (),
+            Some(Op::SIGN(straight_through)) => {
+                if let [c] = children.as_slice() {
+                    if straight_through {
+                        add(c, grad);
+                    }
+                }
+            }
+            Some(Op::QUANT { .. }) => {
+                if let [c] = children.as_slice() {
+                    add(c, grad);
+                }
+            }
+            Some(Op::CLAMP { lo, hi }) => {
+                if let [c] = children.as_slice() {
+                    if (lo..=hi).contains(&c.data()) {
+                        add(c, grad);
+                    }
+                }
+            }
+            Some(Op::LN) => {
+                if let [c] = children.as_slice() {
+                    add(c, grad / c.data());
+                }
+            }
+            Some(Op::LOG2) => {
+                if let [c] = children.as_slice() {
+                    add(c, grad / (c.data() * std::f32::consts::LN_2));
+                }
+            }
+            Some(Op::LOG10) => {
+                if let [c] = children.as_slice() {
+                    add(c, grad / (c.data() * std::f32::consts::LN_10));
+                }
+            }
+            Some(Op::EXP) => {
+                if let [c] = children.as_slice() {
+                    add(c, value.data * grad);
+                }
+            }
+            Some(Op::SELECT) => {
+                if let [cond, c1, c2] = children.as_slice() {
+                    if cond.data() > 0.0 {
+                        add(c1, grad);
+                    } else {
+                        add(c2, grad);
+                    }
+                }
+            }
+            Some(Op::CUSTOM(ref op)) => {
+                let inputs: Vec<f32> = children.iter().map(|c| c.data()).collect();
+
+                for (c, g) in children.iter().zip(op.backward(&inputs, value.data, grad)) {
+                    add(c, g);
                 }
             }
             None => (),
         }
     }
 
+    /// Applies a custom op to `inputs`, recording it in the graph like a built-in op.
+    pub fn apply_custom(op: impl CustomOp + 'static, inputs: &[Scalar<f32>]) -> Self {
+        let start = profile_start();
+        let data: Vec<f32> = inputs.iter().map(|s| s.data()).collect();
+        let mut output = Value::new(op.forward(&data), "");
+
+        output.children = inputs.iter().map(|s| s.clone().into()).collect();
+        Scalar::from_op(output, Op::CUSTOM(Arc::new(op)), start)
+    }
+
+    /// Splices a black-box function into the graph. Backward approximates
+    /// its derivative by the central difference `(f(x + eps) - f(x - eps)) / 2eps`,
+    /// costing two extra calls to `f`.
+    pub fn apply_numeric(&self, f: impl Fn(f32) -> f32 + Send + Sync + 'static, eps: f32) -> Self {
+        Scalar::apply_custom(
+            Numeric {
+                f: Box::new(f),
+                eps,
+            },
+            std::slice::from_ref(self),
+        )
+    }
+
     pub fn backward(&self) {
-        let scalars = self.traverse();
+        self.backward_with(BackwardOptions::default());
+    }
+
+    pub fn backward_with(&self, opts: BackwardOptions) {
+        let mut scalars = self.topological();
 
         for s in &scalars {
-            let mut v = s.0.lock().unwrap();
-            v.grad = 0.0;
+            s.set_grad(0.0);
         }
 
-        let mut value = self.0.lock().unwrap();
-        value.grad = 1.0;
-        drop(value);
+        self.set_grad(1.0);
 
-        for s in scalars {
+        while let Some(s) = scalars.pop() {
             s.cal_grad();
+
+            if !opts.retain_graph {
+                let mut v = s.0.lock().unwrap();
+                v.children.clear();
+            }
         }
     }
-}
 
-impl<T: Float + NumAssignOps> Scalar<T> {
-    pub fn data(&self) -> T {
-        let v = self.0.lock().unwrap();
-
-        v.data
+    /// Same result as `backward`, computed with up to `threads` worker threads.
+    /// Nodes are grouped by their longest distance from the output; nodes in a
+    /// group never depend on each other, so each group is split across the
+    /// workers, which accumulate into shared children through their gradient
+    /// cells (lock-free with the `atomic-grad` feature).
+    pub fn backward_parallel(&self, threads: usize) {
+        self.backward_parallel_with(ParallelOptions {
+            threads,
+            deterministic: false,
+        });
     }
 
-    pub fn set_data(&self, data: T) {
-        let mut v = self.0.lock().unwrap();
+    pub fn backward_parallel_with(&self, opts: ParallelOptions) {
+        const MIN_CHUNK: usize = 64;
 
-        v.data = data;
-    }
+        // wasm32 can't spawn threads; run on the calling one.
+        let threads = if cfg!(target_arch = "wasm32") {
+            1
+        } else {
+            opts.threads.max(1)
+        };
 
-    pub fn grad(&self) -> T {
-        let v = self.0.lock().unwrap();
+        let scalars = self.topological();
+        let index: HashMap<_, _> = scalars
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (Arc::as_ptr(&s.0), i))
+            .collect();
+        let mut depths = vec![0; scalars.len()];
 
-        v.grad
-    }
+        for (i, s) in scalars.iter().enumerate().rev() {
+            let mut value = s.0.lock().unwrap();
 
-    pub fn traverse(&self) -> Vec<Self> {
-        let mut nodes = vec![self.clone()];
-        let mut pointer = 0;
+            for c in value.children() {
+                let j = index[&Arc::as_ptr(&c.0)];
+                depths[j] = depths[j].max(depths[i] + 1);
+            }
 
-        while nodes.len() > pointer {
-            let node = nodes[pointer].0.clone();
-            let node = node.lock().unwrap();
+            drop(value);
+        }
 
-            match &node.children {
-                (Some(c1), Some(c2)) => {
-                    nodes.push(c1.clone());
-                    nodes.push(c2.clone());
-                }
-                (Some(c), None) | (None, Some(c)) => {
-                    nodes.push(c.clone());
-                }
-                (None, None) => (),
-            }
+        let mut levels: Vec<Vec<Scalar<f32>>> =
+            vec![vec![]; depths.iter().max().map_or(0, |d| d + 1)];
 
-            pointer += 1;
+        for (s, d) in scalars.into_iter().zip(depths) {
+            s.set_grad(0.0);
+            levels[d].push(s);
         }
 
-        nodes
-    }
+        self.set_grad(1.0);
 
-    fn trace(&self) -> (Vec<Self>, Vec<(usize, usize)>) {
-        let mut nodes = vec![self.clone()];
-        let mut edges = vec![];
-        let mut pointer = 0;
+        for level in &levels {
+            let chunk = level.len().div_ceil(threads).max(MIN_CHUNK);
 
-        while nodes.len() > pointer {
-            let node = nodes[pointer].0.clone();
-            let node = node.lock().unwrap();
-
-            match &node.children {
-                (Some(c1), Some(c2)) => {
-                    if Arc::ptr_eq(&c1.0, &c2.0) {
-                        match nodes
-                            .iter()
-                            .enumerate()
-                            .find(|(_, s)| Arc::ptr_eq(&c1.0, &s.0))
-                        {
-                            Some((i, _)) => {
-                                edges.push((i, pointer));
-                                edges.push((i, pointer));
-                            }
-                            None => {
-                                nodes.push(c1.clone());
+            if chunk >= level.len() {
+                level.iter().for_each(|s| s.cal_grad());
+                continue;
+            }
 
-                                edges.push((nodes.len() - 1, pointer));
-                                edges.push((nodes.len() - 1, pointer));
-                            }
-                        }
-                    } else {
-                        match nodes
-                            .iter()
-                            .enumerate()
-                            .find(|(_, s)| Arc::ptr_eq(&c1.0, &s.0))
-                        {
-                            Some((i, _)) => {
-                                edges.push((i, pointer));
-                            }
-                            None => {
-                                nodes.push(c1.clone());
-                                edges.push((nodes.len() - 1, pointer));
-                            }
-                        }
-
-                        match nodes
-                            .iter()
-                            .enumerate()
-                            .find(|(_, s)| Arc::ptr_eq(&c2.0, &s.0))
-                        {
-                            Some((i, _)) => {
-                                edges.push((i, pointer));
-                            }
-                            None => {
-                                nodes.push(c2.clone());
-                                edges.push((nodes.len() - 1, pointer));
-                            }
-                        }
-                    }
-                }
-                (Some(c), None) | (None, Some(c)) => {
-                    match nodes
-                        .iter()
-                        .enumerate()
-                        .find(|(_, s)| Arc::ptr_eq(&c.0, &s.0))
-                    {
-                        Some((i, _)) => {
-                            edges.push((i, pointer));
-                        }
-                        None => {
-                            nodes.push(c.clone());
-                            edges.push((nodes.len() - 1, pointer));
-                        }
+            if !opts.deterministic {
+                thread::scope(|scope| {
+                    for part in level.chunks(chunk) {
+                        scope.spawn(move || part.iter().for_each(|s| s.cal_grad()));
                     }
-                }
-                (None, None) => (),
+                });
+                continue;
             }
 
-            pointer += 1;
-        }
+            let contributions: Vec<Vec<(Scalar<f32>, f32)>> = thread::scope(|scope| {
+                let workers: Vec<_> = level
+                    .chunks(chunk)
+                    .map(|part| {
+                        scope.spawn(move || {
+                            let mut out = vec![];
+                            for s in part {
+                                s.cal_grad_with(|c, g| out.push((c.clone(), g)));
+                            }
+                            out
+                        })
+                    })
+                    .collect();
+
+                workers.into_iter().map(|w| w.join().unwrap()).collect()
+            });
 
-        (nodes, edges)
+            for (c, g) in contributions.into_iter().flatten() {
+                c.add_grad(g);
+            }
+        }
     }
-}
 
-impl<T: Float + NumAssignOps + PartialEq + Display> Scalar<T> {
-    pub fn draw(&self) -> String {
-        let (nodes, edges) = self.trace();
-        let mut vg = VisualGraph::new(Orientation::LeftToRight);
+    /// Runs `backward` and reports on the pass. Fails without touching any
+    /// gradient when the output itself is not finite.
+    pub fn try_backward(&self) -> Result<BackwardReport, EngineError> {
+        if !self.data().is_finite() {
+            return Err(EngineError::NonFiniteErr);
+        }
 
-        let node_handles: Vec<(Option<NodeHandle>, NodeHandle)> = nodes
-            .iter()
-            .map(|node| {
-                let node = node.0.lock().unwrap();
+        let start = Instant::now();
+        let scalars = self.topological();
 
-                let shape = ShapeKind::new_box(&format!(
-                    "{} | data {:.4} | grad {:.4}",
-                    node.label, node.data, node.grad
-                ));
+        for s in &scalars {
+            s.set_grad(0.0);
+        }
 
-                let element = Element::create(
-                    shape,
-                    StyleAttr::simple(),
-                    Orientation::LeftToRight,
-                    Point::new(250.0, 25.0),
-                );
+        self.set_grad(1.0);
 
-                if let Some(op) = &node.op {
-                    let shape = ShapeKind::new_circle(match op {
-                        Op::ADD => "+",
-                        Op::SUB => "-",
-                        Op::MUL => "*",
-                        // Op::DIV => "/",
-                        Op::POWI(_) => "POWI",
-                        Op::TANH => "tanh",
-                    });
+        let mut ops_executed = 0;
 
-                    let op_element = Element::create(
-                        shape,
-                        StyleAttr::simple(),
-                        Orientation::LeftToRight,
-                        Point::new(40.0, 40.0),
-                    );
+        for s in scalars.iter().rev() {
+            if s.0.lock().unwrap().op.is_some() {
+                ops_executed += 1;
+            }
 
-                    let handle = vg.add_node(element);
-                    let op_handle = vg.add_node(op_element);
+            s.cal_grad();
+        }
 
-                    vg.add_edge(Arrow::simple(""), op_handle.clone(), handle);
+        let grads: Vec<f32> = scalars.iter().map(|s| s.grad()).collect();
 
-                    (Some(op_handle), handle)
-                } else {
-                    (None, vg.add_node(element))
-                }
-            })
-            .collect();
+        Ok(BackwardReport {
+            nodes_visited: scalars.len(),
+            ops_executed,
+            elapsed: start.elapsed(),
+            nan_count: grads.iter().filter(|g| g.is_nan()).count(),
+            max_abs_grad: grads
+                .iter()
+                .filter(|g| !g.is_nan())
+                .fold(0.0, |acc, g| acc.max(g.abs())),
+        })
+    }
 
-        for (from, to) in edges {
-            let (_, from_handle) = node_handles[from];
-            let to_handle = match node_handles[to] {
-                (Some(handle), _) => handle,
-                (None, handle) => handle,
-            };
+    /// Like `backward`, but only propagates along paths that reach one of
+    /// `leaves`, skipping the rest of the graph. Gradients of nodes off those
+    /// paths are left unspecified.
+    pub fn backward_for(&self, leaves: &[Scalar<f32>]) {
+        let path = self.paths_to(leaves);
+
+        for s in &path {
+            let children = s.0.lock().unwrap().children();
+
+            s.set_grad(0.0);
+            for c in children {
+                c.set_grad(0.0);
+            }
+        }
+
+        self.set_grad(1.0);
+
+        for s in path.iter().rev() {
+            s.cal_grad();
+        }
+    }
+}
+
+impl<T: Float + NumAssignOps> Scalar<T> {
+    pub fn data(&self) -> T {
+        let v = self.0.lock().unwrap();
+
+        v.data
+    }
+
+    pub fn set_data(&self, data: T) {
+        let mut v = self.0.lock().unwrap();
+
+        v.data = data;
+    }
+
+    pub fn grad(&self) -> T {
+        self.0.grad.get()
+    }
+
+    pub fn set_grad(&self, grad: T) {
+        self.0.grad.set(grad);
+    }
+
+    fn add_grad(&self, grad: T) {
+        self.0.grad.add(grad);
+    }
+
+    fn from_value(value: Value<T>) -> Self {
+        Scalar(Arc::new(Node {
+            value: Mutex::new(value),
+            grad: Grad::new(),
+        }))
+    }
+
+    /// Finishes building an op node, recording it if profiling started at `start`.
+    fn from_op(mut value: Value<T>, op: Op, start: Option<Instant>) -> Self {
+        if let Some(start) = start {
+            profile_record(&op, |p| {
+                p.built += 1;
+                p.build_time += start.elapsed();
+            });
+        }
+
+        value.op = Some(op);
+
+        Scalar::from_value(value)
+    }
+
+    /// Estimated heap bytes of this node alone: the `Arc` allocation holding
+    /// the mutex-guarded value plus the buffers of its children and label,
+    /// and any metadata with its keys and boxed values.
+    fn node_footprint(&self) -> usize {
+        let v = self.0.lock().unwrap();
+        let meta = v.meta.as_ref().map_or(0, |m| {
+            size_of::<Metadata>()
+                + m.0.capacity() * size_of::<(String, Box<dyn Any + Send>)>()
+                + m.0
+                    .iter()
+                    .map(|(k, v)| k.capacity() + size_of_val(&**v))
+                    .sum::<usize>()
+        });
+
+        2 * size_of::<usize>()
+            + size_of::<Node<T>>()
+            + v.children.capacity() * size_of::<Child<T>>()
+            + v.label.capacity()
+            + meta
+    }
+
+    /// Estimated heap bytes held by every distinct node of the graph rooted here.
+    pub fn memory_footprint(&self) -> usize {
+        let (nodes, _) = self.trace();
+
+        nodes.iter().map(|n| n.node_footprint()).sum()
+    }
+
+    fn cal_data(&self) {
+        let mut value = self.0.lock().unwrap();
+
+        let children = value.children();
+        let data = match (&value.op, children.as_slice()) {
+            (Some(Op::ADD), [c1, c2]) => c1.data() + c2.data(),
+            (Some(Op::SUB), [c1, c2]) => c1.data() - c2.data(),
+            (Some(Op::MUL), [c1, c2]) => c1.data() * c2.data(),
+            (Some(Op::DIV), [c1, c2]) => c1.data() / c2.data(),
+            (Some(Op::NEG), [c]) => -c.data(),
+            (Some(Op::MAX), [c1, c2]) => max_of(c1.data(), c2.data()),
+            (Some(Op::MIN), [c1, c2]) => min_of(c1.data(), c2.data()),
+            (Some(Op::POWI(n)), [c]) => c.data().powi(*n),
+            (Some(Op::POWF(n)), [c]) => c.data().powf(T::from(*n).unwrap()),
+            (Some(Op::POW), [c1, c2]) => c1.data().powf(c2.data()),
+            (Some(Op::TANH), [c]) => c.data().tanh(),
+            (Some(Op::SIGMOID), [c]) => sigmoid(c.data()),
+            (Some(Op::LEAKYRELU(alpha)), [c]) => leaky_relu(c.data(), *alpha),
+            (Some(Op::GELU), [c]) => gelu(c.data()),
+            (Some(Op::SOFTPLUS), [c]) => softplus(c.data()),
+            (Some(Op::SILU), [c]) => c.data() * sigmoid(c.data()),
+            (Some(Op::ELU(alpha)), [c]) => elu(c.data(), *alpha),
+            (Some(Op::SQRT), [c]) => c.data().sqrt(),
+            (Some(Op::FLOOR), [c]) => c.data().floor(),
+            (Some(Op::CEIL), [c]) => c.data().ceil(),
+            (Some(Op::ROUND), [c]) => c.data().round(),
+            (Some(Op::SIGN(_)), [c]) => sign(c.data()),
+            (Some(Op::QUANT { bits, scale }), [c]) => fake_quant(c.data(), *bits, *scale),
+            (Some(Op::CLAMP { lo, hi }), [c]) => clamp(c.data(), *lo, *hi),
+            (Some(Op::LN), [c]) => c.data().ln(),
+            (Some(Op::LOG2), [c]) => c.data().log2(),
+            (Some(Op::LOG10), [c]) => c.data().log10(),
+            (Some(Op::EXP), [c]) => c.data().exp(),
+            (Some(Op::SELECT), [cond, c1, c2]) => {
+                if cond.data() > T::zero() {
+                    c1.data()
+                } else {
+                    c2.data()
+                }
+            }
+            (Some(Op::CUSTOM(op)), children) => {
+                let inputs: Vec<f32> = children
+                    .iter()
+                    .map(|c| c.data().to_f32().unwrap())
+                    .collect();
+
+                T::from(op.forward(&inputs)).unwrap()
+            }
+            _ => return,
+        };
+
+        value.data = data;
+    }
+
+    /// Recomputes the data of every node from its children, so a graph can be
+    /// reused after its leaves were updated with `set_data`.
+    pub fn forward(&self) {
+        for s in self.topological() {
+            s.cal_data();
+        }
+    }
+
+    /// Copies the whole graph, leaves included, into new nodes with the same
+    /// data, gradients, labels and ops. Nodes shared in the original are
+    /// shared in the copy; weak links are copied as strong ones.
+    pub fn clone_graph(&self) -> Self {
+        let mut copies: HashMap<*const Node<T>, Self> = HashMap::new();
+
+        for s in self.topological() {
+            let mut value = s.0.lock().unwrap();
+            let mut copy = Value::new(value.data, &value.label);
+
+            copy.op = value.op.clone();
+            copy.children = value
+                .children()
+                .iter()
+                .map(|c| copies[&Arc::as_ptr(&c.0)].clone().into())
+                .collect();
+            drop(value);
+
+            let copy = Scalar::from_value(copy);
+            copy.set_grad(s.grad());
+            copies.insert(Arc::as_ptr(&s.0), copy);
+        }
+
+        copies.remove(&Arc::as_ptr(&self.0)).unwrap()
+    }
+
+    /// Iterates over the distinct nodes of the graph, children before parents.
+    pub fn iter_topological(&self) -> impl Iterator<Item = Self> {
+        self.topological().into_iter()
+    }
+
+    /// Walks the graph in topological order, reporting each node and then
+    /// the edges from its children.
+    pub fn visit(&self, visitor: &mut impl GraphVisitor<T>) {
+        for node in self.topological() {
+            visitor.visit_node(&node);
+
+            for child in node.children() {
+                visitor.visit_edge(&child, &node);
+            }
+        }
+    }
+
+    pub fn children(&self) -> Vec<Self> {
+        self.0.lock().unwrap().children()
+    }
+
+    pub fn op(&self) -> Option<Op> {
+        self.0.lock().unwrap().op.clone()
+    }
+
+    /// Estimated floating point operations of a forward and a backward pass
+    /// over the graph rooted here, counting each shared node once.
+    pub fn flops(&self) -> Flops {
+        self.topological()
+            .iter()
+            .filter_map(|s| {
+                let value = s.0.lock().unwrap();
+
+                value.op.as_ref().map(|op| op.flops(value.children.len()))
+            })
+            .sum()
+    }
+
+    /// Id assigned when the node was created, unique for the life of the
+    /// process. It never changes, and a node's id is larger than its
+    /// children's, which is the order `draw` and `to_dot` list nodes in. They
+    /// name nodes by that position rather than by id, so separately built
+    /// copies of a graph render identically.
+    pub fn id(&self) -> u64 {
+        self.0.lock().unwrap().id
+    }
+
+    pub fn get_label(&self) -> String {
+        self.0.lock().unwrap().label.clone()
+    }
+
+    /// Attaches `value` to the node under `key`, replacing any previous value.
+    /// Metadata isn't copied by `clone_graph`.
+    pub fn set_meta<V: Any + Send>(&self, key: &str, value: V) {
+        let mut v = self.0.lock().unwrap();
+
+        v.meta
+            .get_or_insert_with(Default::default)
+            .0
+            .insert(key.to_string(), Box::new(value));
+    }
+
+    /// The value stored under `key`, if there is one of type `V`.
+    pub fn get_meta<V: Any + Clone>(&self, key: &str) -> Option<V> {
+        let v = self.0.lock().unwrap();
+
+        v.meta.as_ref()?.0.get(key)?.downcast_ref::<V>().cloned()
+    }
+
+    pub fn meta_keys(&self) -> Vec<String> {
+        let v = self.0.lock().unwrap();
+
+        v.meta
+            .as_ref()
+            .map_or(vec![], |m| m.0.keys().cloned().collect())
+    }
+
+    /// Whether both handles point to the same node.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Nodes lying on a path from any of `leaves` up to this node, in
+    /// topological order.
+    fn paths_to(&self, leaves: &[Self]) -> Vec<Self> {
+        let mut reaching: HashSet<_> = leaves.iter().map(|l| Arc::as_ptr(&l.0)).collect();
+
+        self.topological()
+            .into_iter()
+            .filter(|s| {
+                let ptr = Arc::as_ptr(&s.0);
+                let reaches = reaching.contains(&ptr)
+                    || s.0
+                        .lock()
+                        .unwrap()
+                        .children()
+                        .iter()
+                        .any(|c| reaching.contains(&Arc::as_ptr(&c.0)));
+
+                if reaches {
+                    reaching.insert(ptr);
+                }
+
+                reaches
+            })
+            .collect()
+    }
+
+    /// The part of the graph on paths from `leaf` up to this node, e.g. to
+    /// draw only what a single parameter influences. Empty if `leaf` is not
+    /// in the graph.
+    pub fn subgraph_to(&self, leaf: &Self) -> Graph<T> {
+        let keep: HashSet<_> = self
+            .paths_to(std::slice::from_ref(leaf))
+            .iter()
+            .map(|s| Arc::as_ptr(&s.0))
+            .collect();
+        let (nodes, edges) = self.trace();
+
+        let mut index = vec![None; nodes.len()];
+        let mut kept = vec![];
+
+        for (i, n) in nodes.into_iter().enumerate() {
+            if keep.contains(&Arc::as_ptr(&n.0)) {
+                index[i] = Some(kept.len());
+                kept.push(n);
+            }
+        }
+
+        Graph {
+            nodes: kept,
+            edges: edges
+                .into_iter()
+                .filter_map(|(from, to)| Some((index[from]?, index[to]?)))
+                .collect(),
+        }
+    }
+
+    /// Canonical description of the graph: for each node in topological order,
+    /// its op (or leaf label) and the positions of its children.
+    fn structure(&self) -> Vec<(String, Vec<usize>)> {
+        let scalars = self.topological();
+        let index: HashMap<_, _> = scalars
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (Arc::as_ptr(&s.0), i))
+            .collect();
+
+        scalars
+            .iter()
+            .map(|s| {
+                let mut value = s.0.lock().unwrap();
+                let node = match &value.op {
+                    Some(op) => format!("{op:?}"),
+                    None => format!("leaf {}", value.label),
+                };
+                let children = value
+                    .children()
+                    .iter()
+                    .map(|c| index[&Arc::as_ptr(&c.0)])
+                    .collect();
+
+                (node, children)
+            })
+            .collect()
+    }
+
+    /// Whether both graphs have the same ops, wiring and leaf labels,
+    /// regardless of the data they hold.
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        self.structure() == other.structure()
+    }
+
+    /// Hash of the graph's structure, consistent with `structurally_eq`.
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.structure().hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// Switches every link between two intermediate nodes of this graph to a
+    /// weak reference, while links to leaves stay strong. Afterwards an
+    /// intermediate only lives as long as something outside the graph holds
+    /// it, so keeping e.g. a finished loss around no longer retains the whole
+    /// graph behind it. A dropped intermediate is replaced by a constant
+    /// with its id and the data it had when weakened, so operands keep their
+    /// positions but gradients don't flow past it.
+    pub fn weaken(&self) {
+        let mut frozen: HashMap<*const Node<T>, Arc<Frozen<T>>> = HashMap::new();
+
+        for s in self.topological() {
+            let mut value = s.0.lock().unwrap();
+
+            if value.op.is_none() {
+                continue;
+            }
+
+            for child in value.children.iter_mut() {
+                if let Child::Strong(c) = child {
+                    let c_value = c.0.lock().unwrap();
+
+                    if c_value.op.is_some() {
+                        let shared = frozen.entry(Arc::as_ptr(&c.0)).or_insert_with(|| {
+                            Arc::new(Frozen {
+                                id: c_value.id,
+                                data: c_value.data,
+                                constant: OnceLock::new(),
+                            })
+                        });
+                        drop(c_value);
+                        *child = Child::Weak(Arc::downgrade(&c.0), shared.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Distinct nodes of the graph, every node placed after all of its children.
+    fn topological(&self) -> Vec<Self> {
+        Self::topological_from(std::slice::from_ref(self))
+    }
+
+    /// Distinct nodes of the graphs rooted at `roots`, every node placed after
+    /// all of its children.
+    fn topological_from(roots: &[Self]) -> Vec<Self> {
+        let start = profile_start();
+        let mut nodes = vec![];
+        let mut visited = HashSet::new();
+        let mut stack: Vec<(Self, bool)> = roots.iter().rev().map(|r| (r.clone(), false)).collect();
+
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                nodes.push(node);
+                continue;
+            }
+
+            if !visited.insert(Arc::as_ptr(&node.0)) {
+                continue;
+            }
+
+            let mut value = node.0.lock().unwrap();
+            let children = value.children();
+            drop(value);
+
+            stack.push((node, true));
+
+            for c in children {
+                if !visited.contains(&Arc::as_ptr(&c.0)) {
+                    stack.push((c, false));
+                }
+            }
+        }
+
+        if let Some(start) = start {
+            PROFILE.with(|p| {
+                let mut p = p.borrow_mut();
+                p.traversals += 1;
+                p.traversal_time += start.elapsed();
+            });
+        }
+
+        nodes
+    }
+
+    pub fn traverse(&self) -> Vec<Self> {
+        let mut nodes = vec![self.clone()];
+        let mut pointer = 0;
+
+        while nodes.len() > pointer {
+            let node = nodes[pointer].0.clone();
+            let mut node = node.lock().unwrap();
+
+            nodes.extend(node.children());
+
+            pointer += 1;
+        }
+
+        nodes
+    }
+
+    /// Distinct nodes by descending id, so the output comes first and
+    /// every node before its children, and the `(child, parent)` edges
+    /// grouped by parent in that order, each parent's in operand order. The
+    /// result depends only on the graph, not on how it is discovered. This
+    /// holds after `weaken` too, as a dropped node's stand-in keeps its id.
+    fn trace(&self) -> (Vec<Self>, Vec<(usize, usize)>) {
+        let mut nodes = vec![self.clone()];
+        let mut index = HashMap::from([(Arc::as_ptr(&self.0), 0)]);
+        let mut edges = vec![];
+        let mut pointer = 0;
+
+        while nodes.len() > pointer {
+            let children = nodes[pointer].children();
+
+            for c in children {
+                let i = *index.entry(Arc::as_ptr(&c.0)).or_insert_with(|| {
+                    nodes.push(c.clone());
+                    nodes.len() - 1
+                });
+                edges.push((i, pointer));
+            }
+
+            pointer += 1;
+        }
+
+        let ids: Vec<u64> = nodes.iter().map(|n| n.id()).collect();
+        let mut order: Vec<usize> = (0..nodes.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(ids[i]));
+
+        let mut position = vec![0; nodes.len()];
+        for (to, &from) in order.iter().enumerate() {
+            position[from] = to;
+        }
+
+        let mut edges: Vec<_> = edges
+            .into_iter()
+            .map(|(from, to)| (position[from], position[to]))
+            .collect();
+        // Stable, so each parent's edges keep their operand order.
+        edges.sort_by_key(|&(_, to)| to);
+
+        (order.into_iter().map(|i| nodes[i].clone()).collect(), edges)
+    }
+}
+
+/// Callbacks for `Scalar::visit`. Every node is visited once, after all of
+/// its children.
+pub trait GraphVisitor<T: Float + NumAssignOps> {
+    fn visit_node(&mut self, _node: &Scalar<T>) {}
+
+    /// Called once per input of `parent`, so a node used twice by the same
+    /// parent yields two edges.
+    fn visit_edge(&mut self, _child: &Scalar<T>, _parent: &Scalar<T>) {}
+}
+
+/// Distinct nodes of a graph, output first, and its `(child, parent)` edges
+/// as indices into `nodes`.
+#[derive(Debug, Clone)]
+pub struct Graph<T: Float + NumAssignOps> {
+    pub nodes: Vec<Scalar<T>>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl<T: Float + NumAssignOps + PartialEq + Display> Scalar<T> {
+    pub fn draw(&self) -> String {
+        let (nodes, edges) = self.trace();
+
+        Graph { nodes, edges }.draw()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let (nodes, edges) = self.trace();
+
+        Graph { nodes, edges }.to_dot()
+    }
+
+    /// Renders the graph as several SVG pages of at most `max_nodes_per_page`
+    /// nodes each, cutting it in topological order so each page only feeds
+    /// later ones. Nodes are named by their position in that order across all
+    /// pages, and an edge crossing pages ends in a marker box naming the page
+    /// and node (`page 2: n17`) on both sides.
+    pub fn draw_paginated(&self, max_nodes_per_page: usize) -> Vec<String> {
+        let scalars = self.topological();
+        let index: HashMap<_, _> = scalars
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (Arc::as_ptr(&s.0), i))
+            .collect();
+        let per_page = max_nodes_per_page.max(1);
+        let page = |i: usize| i / per_page;
+
+        let mut pages: Vec<(Graph<T>, Vec<GraphRef>)> = scalars
+            .chunks(per_page)
+            .map(|chunk| {
+                (
+                    Graph {
+                        nodes: chunk.to_vec(),
+                        edges: vec![],
+                    },
+                    vec![],
+                )
+            })
+            .collect();
+
+        for (to, s) in scalars.iter().enumerate() {
+            for c in s.children() {
+                let from = index[&Arc::as_ptr(&c.0)];
+                let (from_page, to_page) = (page(from), page(to));
+
+                if from_page == to_page {
+                    pages[to_page]
+                        .0
+                        .edges
+                        .push((from % per_page, to % per_page));
+                } else {
+                    pages[from_page].1.push(GraphRef {
+                        node: from % per_page,
+                        label: format!("page {}: n{to}", to_page + 1),
+                        outgoing: true,
+                    });
+                    pages[to_page].1.push(GraphRef {
+                        node: to % per_page,
+                        label: format!("page {}: n{from}", from_page + 1),
+                        outgoing: false,
+                    });
+                }
+            }
+        }
+
+        pages
+            .iter()
+            .enumerate()
+            .map(|(i, (graph, refs))| graph.render(i * per_page, refs))
+            .collect()
+    }
+}
+
+/// A marker for an edge leaving or entering a page of `draw_paginated`.
+struct GraphRef {
+    node: usize,
+    label: String,
+    outgoing: bool,
+}
+
+impl<T: Float + NumAssignOps + PartialEq + Display> Graph<T> {
+    /// Renders the graph as SVG. Node elements get the ids `n<i>` and
+    /// `n<i>-op` after their index in `nodes`, not the nodes' `Scalar::id`,
+    /// so drawing the same expression twice gives the same output. Names are
+    /// positional: adding a node renames the nodes listed after it.
+    pub fn draw(&self) -> String {
+        self.render(0, &[])
+    }
+
+    /// Like `draw`, numbering nodes from `first` and adding `refs` markers.
+    fn render(&self, first: usize, refs: &[GraphRef]) -> String {
+        let (nodes, edges) = (&self.nodes, &self.edges);
+        let mut vg = VisualGraph::new(Orientation::LeftToRight);
+
+        let node_handles: Vec<(Option<NodeHandle>, NodeHandle)> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let i = first + i;
+                let grad = node.grad();
+                let node = node.0.lock().unwrap();
+
+                let shape = ShapeKind::new_box(&format!(
+                    "{} | data {:.4} | grad {:.4}",
+                    node.label, node.data, grad
+                ));
+
+                let element = Element::create_with_properties(
+                    shape,
+                    StyleAttr::simple(),
+                    Orientation::LeftToRight,
+                    Point::new(250.0, 25.0),
+                    format!("id=\"n{i}\""),
+                );
+
+                if let Some(op) = &node.op {
+                    let shape = ShapeKind::new_circle(op.symbol());
+
+                    let op_element = Element::create_with_properties(
+                        shape,
+                        StyleAttr::simple(),
+                        Orientation::LeftToRight,
+                        Point::new(40.0, 40.0),
+                        format!("id=\"n{i}-op\""),
+                    );
+
+                    let handle = vg.add_node(element);
+                    let op_handle = vg.add_node(op_element);
+
+                    vg.add_edge(Arrow::simple(""), op_handle, handle);
+
+                    (Some(op_handle), handle)
+                } else {
+                    (None, vg.add_node(element))
+                }
+            })
+            .collect();
+
+        for &(from, to) in edges {
+            let (_, from_handle) = node_handles[from];
+            let to_handle = match node_handles[to] {
+                (Some(handle), _) => handle,
+                (None, handle) => handle,
+            };
             vg.add_edge(Arrow::simple(""), from_handle, to_handle);
         }
 
-        let mut svg = SVGWriter::new();
+        for r in refs {
+            let marker = vg.add_node(Element::create(
+                ShapeKind::new_box(&r.label),
+                StyleAttr::simple(),
+                Orientation::LeftToRight,
+                Point::new(120.0, 25.0),
+            ));
+
+            match node_handles[r.node] {
+                (_, handle) if r.outgoing => vg.add_edge(Arrow::simple(""), handle, marker),
+                (Some(handle), _) | (None, handle) => {
+                    vg.add_edge(Arrow::simple(""), marker, handle)
+                }
+            }
+        }
+
+        let mut svg = SVGWriter::new();
+
+        vg.do_it(false, false, false, &mut svg);
+        svg.finalize()
+    }
+
+    /// The graph in Graphviz DOT format. Nodes are named `n<i>` after their
+    /// index in `nodes`, not their `Scalar::id`, with ops drawn as separate
+    /// `n<i>_op` nodes as in `draw`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n    rankdir=LR;\n");
+        let mut has_op = vec![false; self.nodes.len()];
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            let grad = node.grad();
+            let value = node.0.lock().unwrap();
+
+            dot.push_str(&format!(
+                "    n{id} [shape=record, label=\"{} | data {:.4} | grad {:.4}\"];\n",
+                record_escape(&value.label),
+                value.data,
+                grad
+            ));
+
+            if let Some(op) = &value.op {
+                has_op[id] = true;
+                dot.push_str(&format!(
+                    "    n{id}_op [label=\"{}\"];\n    n{id}_op -> n{id};\n",
+                    op.symbol()
+                ));
+            }
+        }
+
+        for &(from, to) in &self.edges {
+            let target = if has_op[to] {
+                format!("n{to}_op")
+            } else {
+                format!("n{to}")
+            };
+
+            dot.push_str(&format!("    n{from} -> {target};\n"));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// `s` with the characters that are special inside a quoted DOT record
+/// label escaped by a backslash, so it stays a single field.
+fn record_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if matches!(c, '\\' | '"' | '|' | '{' | '}' | '<' | '>') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+impl Op {
+    /// Estimated floating point operations to compute a node of this op from
+    /// `inputs` children, and to pass its gradient back, accumulation included.
+    /// Transcendental functions count as one operation, rounding as none.
+    pub(crate) fn flops(&self, inputs: usize) -> Flops {
+        let (forward, backward) = match self {
+            Op::ADD | Op::SUB => (1, 2),
+            Op::MUL => (1, 4),
+            Op::DIV => (1, 6),
+            Op::NEG => (1, 2),
+            Op::MAX | Op::MIN => (1, 1),
+            Op::POWI(n) => {
+                let pow = n.unsigned_abs().max(2) as usize - 1 + usize::from(*n < 0);
+                (pow, pow + 3)
+            }
+            Op::POWF(_) => (1, 4),
+            Op::POW => (1, 8),
+            Op::TANH => (1, 4),
+            Op::SIGMOID => (3, 4),
+            Op::LEAKYRELU(_) => (1, 2),
+            Op::GELU => (8, 16),
+            Op::SOFTPLUS => (4, 5),
+            Op::SILU => (4, 8),
+            Op::ELU(_) => (3, 3),
+            Op::SQRT => (1, 3),
+            Op::SELECT => (0, 1),
+            Op::FLOOR | Op::CEIL | Op::ROUND => (1, 0),
+            Op::SIGN(straight_through) => (1, usize::from(*straight_through)),
+            Op::QUANT { .. } => (2, 1),
+            Op::CLAMP { .. } => (2, 1),
+            Op::LN | Op::EXP => (1, 2),
+            Op::LOG2 | Op::LOG10 => (1, 3),
+            Op::CUSTOM(_) => (inputs, 2 * inputs),
+        };
+
+        Flops { forward, backward }
+    }
+
+    /// Number of operands a node of this op takes, or `None` for custom ops,
+    /// which take any number.
+    pub(crate) fn arity(&self) -> Option<usize> {
+        match self {
+            Op::ADD | Op::SUB | Op::MUL | Op::DIV | Op::MAX | Op::MIN | Op::POW => Some(2),
+            Op::SELECT => Some(3),
+            Op::NEG
+            | Op::POWI(_)
+            | Op::POWF(_)
+            | Op::TANH
+            | Op::SIGMOID
+            | Op::LEAKYRELU(_)
+            | Op::GELU
+            | Op::SOFTPLUS
+            | Op::SILU
+            | Op::ELU(_)
+            | Op::SQRT
+            | Op::FLOOR
+            | Op::CEIL
+            | Op::ROUND
+            | Op::SIGN(_)
+            | Op::QUANT { .. }
+            | Op::CLAMP { .. }
+            | Op::LN
+            | Op::LOG2
+            | Op::LOG10
+            | Op::EXP => Some(1),
+            Op::CUSTOM(_) => None,
+        }
+    }
+
+    /// The variant name without parameters, or a custom op's own name.
+    fn name(&self) -> &str {
+        match self {
+            Op::ADD => "ADD",
+            Op::SUB => "SUB",
+            Op::MUL => "MUL",
+            Op::DIV => "DIV",
+            Op::NEG => "NEG",
+            Op::MAX => "MAX",
+            Op::MIN => "MIN",
+            Op::POWI(_) => "POWI",
+            Op::POWF(_) => "POWF",
+            Op::POW => "POW",
+            Op::TANH => "TANH",
+            Op::SIGMOID => "SIGMOID",
+            Op::LEAKYRELU(_) => "LEAKYRELU",
+            Op::GELU => "GELU",
+            Op::SOFTPLUS => "SOFTPLUS",
+            Op::SILU => "SILU",
+            Op::ELU(_) => "ELU",
+            Op::SQRT => "SQRT",
+            Op::SELECT => "SELECT",
+            Op::FLOOR => "FLOOR",
+            Op::CEIL => "CEIL",
+            Op::ROUND => "ROUND",
+            Op::SIGN(_) => "SIGN",
+            Op::QUANT { .. } => "QUANT",
+            Op::CLAMP { .. } => "CLAMP",
+            Op::LN => "LN",
+            Op::LOG2 => "LOG2",
+            Op::LOG10 => "LOG10",
+            Op::EXP => "EXP",
+            Op::CUSTOM(op) => op.name(),
+        }
+    }
+
+    fn symbol(&self) -> &str {
+        match self {
+            Op::ADD => "+",
+            Op::SUB => "-",
+            Op::MUL => "*",
+            Op::DIV => "/",
+            Op::NEG => "-",
+            Op::MAX => "max",
+            Op::MIN => "min",
+            Op::POWI(_) => "POWI",
+            Op::POWF(_) => "POWF",
+            Op::POW => "^",
+            Op::TANH => "tanh",
+            Op::SIGMOID => "σ",
+            Op::LEAKYRELU(_) => "lrelu",
+            Op::GELU => "gelu",
+            Op::SOFTPLUS => "softplus",
+            Op::SILU => "silu",
+            Op::ELU(_) => "elu",
+            Op::SQRT => "√",
+            Op::SELECT => "select",
+            Op::FLOOR => "floor",
+            Op::CEIL => "ceil",
+            Op::ROUND => "round",
+            Op::SIGN(_) => "sign",
+            Op::QUANT { .. } => "quant",
+            Op::CLAMP { .. } => "clamp",
+            Op::LN => "ln",
+            Op::LOG2 => "log2",
+            Op::LOG10 => "log10",
+            Op::EXP => "exp",
+            Op::CUSTOM(op) => op.name(),
+        }
+    }
+}
+
+impl<T: Float + NumAssignOps + PartialEq> PartialEq for Scalar<T> {
+    fn eq(&self, other: &Self) -> bool {
+        let value = self.0.lock().unwrap();
+        let other_value = other.0.lock().unwrap();
+
+        value.data == other_value.data
+    }
+}
+
+impl<T: Float + NumAssignOps + PartialEq> Eq for Scalar<T> {}
+
+impl<T: Float + NumAssignOps> PartialOrd for Scalar<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let value = self.0.lock().unwrap();
+        let other_value = other.0.lock().unwrap();
+
+        value.data.partial_cmp(&other_value.data)
+    }
+}
+
+impl<T: Float + NumAssignOps + PartialEq + Ord> Ord for Scalar<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let value = self.0.lock().unwrap();
+        let other_value = other.0.lock().unwrap();
+
+        value.data.cmp(&other_value.data)
+    }
+}
+
+impl<T: Float + NumAssignOps> From<T> for Scalar<T> {
+    fn from(data: T) -> Self {
+        Scalar::constant(data)
+    }
+}
+
+impl<T: Add<Output = T> + Float + NumAssignOps, R: Into<Scalar<T>>> Add<R> for Scalar<T> {
+    type Output = Self;
+
+    fn add(self, other: R) -> Self {
+        let start = profile_start();
+        let other = other.into();
+
+        let value = self.0.lock().unwrap();
+        let self_data = value.data;
+        drop(value);
+
+        let other_value = other.0.lock().unwrap();
+        let other_data = other_value.data;
+        drop(other_value);
+
+        let mut output = Value::new(self_data + other_data, "");
+
+        output.children = vec![self.clone().into(), other.clone().into()];
+        Scalar::from_op(output, Op::ADD, start)
+    }
+}
+
+impl<T: Add<Output = T> + Float + NumAssignOps, R: Into<Scalar<T>>> AddAssign<R> for Scalar<T> {
+    fn add_assign(&mut self, other: R) {
+        let start = profile_start();
+        let other = other.into();
+
+        let value = self.0.lock().unwrap();
+        let self_data = value.data;
+        drop(value);
+
+        let other_value = other.0.lock().unwrap();
+        let other_data = other_value.data;
+        drop(other_value);
+
+        let mut output = Value::new(self_data + other_data, "");
+
+        output.children = vec![self.clone().into(), other.clone().into()];
+        *self = Scalar::from_op(output, Op::ADD, start);
+    }
+}
+
+impl<T: Sub<Output = T> + Float + NumAssignOps, R: Into<Scalar<T>>> Sub<R> for Scalar<T> {
+    type Output = Self;
+
+    fn sub(self, rhs: R) -> Self::Output {
+        let start = profile_start();
+        let rhs = rhs.into();
+
+        let value = self.0.lock().unwrap();
+        let self_data = value.data;
+        drop(value);
+
+        let rhs_value = rhs.0.lock().unwrap();
+        let rhs_data = rhs_value.data;
+        drop(rhs_value);
+
+        let mut output = Value::new(self_data - rhs_data, "");
+
+        output.children = vec![self.clone().into(), rhs.clone().into()];
+        Scalar::from_op(output, Op::SUB, start)
+    }
+}
+
+impl<T: Mul<Output = T> + Float + NumAssignOps, R: Into<Scalar<T>>> Mul<R> for Scalar<T> {
+    type Output = Self;
+
+    fn mul(self, rhs: R) -> Self::Output {
+        let start = profile_start();
+        let rhs = rhs.into();
+
+        let value = self.0.lock().unwrap();
+        let self_data = value.data;
+        drop(value);
+
+        let rhs_value = rhs.0.lock().unwrap();
+        let rhs_data = rhs_value.data;
+        drop(rhs_value);
+
+        let mut output = Value::new(self_data * rhs_data, "");
+
+        output.children = vec![self.clone().into(), rhs.clone().into()];
+        Scalar::from_op(output, Op::MUL, start)
+    }
+}
+
+/// Negation as a single NEG node, rather than a subtraction from or a
+/// multiplication by a constant.
+impl<T: Float + NumAssignOps> Neg for Scalar<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        self.unary(|d| -d, Op::NEG)
+    }
+}
+
+/// Division; dividing by a zero scalar gives infinity or NaN like `f32`.
+impl<T: Div<Output = T> + Float + NumAssignOps, R: Into<Scalar<T>>> Div<R> for Scalar<T> {
+    type Output = Self;
+
+    fn div(self, rhs: R) -> Self::Output {
+        let start = profile_start();
+        let rhs = rhs.into();
+
+        let value = self.0.lock().unwrap();
+        let self_data = value.data;
+        drop(value);
+
+        let rhs_value = rhs.0.lock().unwrap();
+        let rhs_data = rhs_value.data;
+        drop(rhs_value);
+
+        let mut output = Value::new(self_data / rhs_data, "");
+
+        output.children = vec![self.clone().into(), rhs.clone().into()];
+        Scalar::from_op(output, Op::DIV, start)
+    }
+}
+
+macro_rules! impl_float_lhs_ops {
+    ($($t:ty),*) => {
+        $(
+            impl Add<Scalar<$t>> for $t {
+                type Output = Scalar<$t>;
+
+                fn add(self, rhs: Scalar<$t>) -> Self::Output {
+                    Scalar::from(self) + rhs
+                }
+            }
+
+            impl Sub<Scalar<$t>> for $t {
+                type Output = Scalar<$t>;
+
+                fn sub(self, rhs: Scalar<$t>) -> Self::Output {
+                    Scalar::from(self) - rhs
+                }
+            }
+
+            impl Mul<Scalar<$t>> for $t {
+                type Output = Scalar<$t>;
+
+                fn mul(self, rhs: Scalar<$t>) -> Self::Output {
+                    Scalar::from(self) * rhs
+                }
+            }
+
+            impl Div<Scalar<$t>> for $t {
+                type Output = Scalar<$t>;
+
+                fn div(self, rhs: Scalar<$t>) -> Self::Output {
+                    Scalar::from(self) / rhs
+                }
+            }
+        )*
+    };
+}
+
+impl_float_lhs_ops!(f32, f64);
+
+impl<T: Float + NumAssignOps> Scalar<T> {
+    fn unary(&self, f: impl Fn(T) -> T, op: Op) -> Self {
+        let start = profile_start();
+        let value = self.0.lock().unwrap();
+        let self_data = value.data;
+        drop(value);
+
+        let mut output = Value::new(f(self_data), "");
+
+        output.children = vec![self.clone().into()];
+
+        Scalar::from_op(output, op, start)
+    }
+
+    pub fn powi(&self, n: i32) -> Self {
+        self.unary(|d| d.powi(n), Op::POWI(n))
+    }
+
+    /// The larger of the two, passing the whole gradient to that operand
+    /// (`self` on ties). A NaN operand gives NaN and takes the gradient.
+    pub fn max(&self, other: &Scalar<T>) -> Self {
+        self.pick(other, max_of(self.data(), other.data()), Op::MAX)
+    }
+
+    /// The smaller of the two, passing the whole gradient to that operand
+    /// (`self` on ties). A NaN operand gives NaN and takes the gradient.
+    pub fn min(&self, other: &Scalar<T>) -> Self {
+        self.pick(other, min_of(self.data(), other.data()), Op::MIN)
+    }
+
+    fn pick(&self, other: &Scalar<T>, data: T, op: Op) -> Self {
+        let start = profile_start();
+        let mut output = Value::new(data, "");
+
+        output.children = vec![self.clone().into(), other.clone().into()];
+        Scalar::from_op(output, op, start)
+    }
+
+    /// `self` raised to a fractional power; like `f32::powf`, a negative base
+    /// gives NaN unless `n` is a whole number.
+    pub fn powf(&self, n: f32) -> Self {
+        self.unary(|d| d.powf(T::from(n).unwrap()), Op::POWF(n))
+    }
+
+    /// `self` raised to a differentiable `exponent`. The exponent only
+    /// receives a gradient, `a^b * ln(a)`, where the base is positive.
+    pub fn pow(&self, exponent: &Scalar<T>) -> Self {
+        let start = profile_start();
+        let mut output = Value::new(self.data().powf(exponent.data()), "");
+
+        output.children = vec![self.clone().into(), exponent.clone().into()];
+        Scalar::from_op(output, Op::POW, start)
+    }
+
+    pub fn tanh(&self) -> Self {
+        self.unary(T::tanh, Op::TANH)
+    }
+
+    /// Logistic function `1 / (1 + e^-x)`, as a single node whose gradient
+    /// reuses the output: `σ(x)(1 - σ(x))`.
+    pub fn sigmoid(&self) -> Self {
+        self.unary(sigmoid, Op::SIGMOID)
+    }
+
+    /// `x` for positive inputs, `alpha * x` otherwise, so negative inputs
+    /// still pass a gradient scaled by `alpha`.
+    pub fn leaky_relu(&self, alpha: f32) -> Self {
+        self.unary(|d| leaky_relu(d, alpha), Op::LEAKYRELU(alpha))
+    }
+
+    /// Gaussian error linear unit, in the tanh approximation
+    /// `0.5x(1 + tanh(√(2/π)(x + 0.044715x³)))`.
+    pub fn gelu(&self) -> Self {
+        self.unary(gelu, Op::GELU)
+    }
+
+    /// `ln(1 + e^x)`, computed as `max(x, 0) + ln(1 + e^-|x|)` so large
+    /// inputs don't overflow. Its gradient is `sigmoid(x)`.
+    pub fn softplus(&self) -> Self {
+        self.unary(softplus, Op::SOFTPLUS)
+    }
+
+    /// SiLU, or swish: `x * sigmoid(x)` as a single node, with gradient
+    /// `σ(x)(1 + x(1 - σ(x)))`.
+    pub fn silu(&self) -> Self {
+        self.unary(|d| d * sigmoid(d), Op::SILU)
+    }
+
+    /// `x` for positive inputs, `alpha * (e^x - 1)` otherwise. The gradient
+    /// on the negative branch is `alpha * e^x`, read back from the output.
+    pub fn elu(&self, alpha: f32) -> Self {
+        self.unary(|d| elu(d, alpha), Op::ELU(alpha))
+    }
+
+    /// Square root, with gradient `0.5 / sqrt(x)` read back from the output.
+    /// Drawn as "√".
+    pub fn sqrt(&self) -> Self {
+        self.unary(T::sqrt, Op::SQRT)
+    }
+
+    /// Rounds down. Piecewise constant, so no gradient flows through it.
+    pub fn floor(&self) -> Self {
+        self.unary(T::floor, Op::FLOOR)
+    }
+
+    /// Rounds up. Piecewise constant, so no gradient flows through it.
+    pub fn ceil(&self) -> Self {
+        self.unary(T::ceil, Op::CEIL)
+    }
+
+    /// Rounds half away from zero. Piecewise constant, so no gradient flows through it.
+    pub fn round(&self) -> Self {
+        self.unary(T::round, Op::ROUND)
+    }
+
+    /// -1, 0 or 1. No gradient flows through it; see `sign_ste`.
+    pub fn sign(&self) -> Self {
+        self.unary(sign, Op::SIGN(false))
+    }
+
+    /// Like `sign`, but backward passes the gradient through unchanged
+    /// (straight-through estimator), as used for binary-weight networks.
+    pub fn sign_ste(&self) -> Self {
+        self.unary(sign, Op::SIGN(true))
+    }
+
+    /// Simulated quantization for quantization-aware training: rounds to the
+    /// nearest multiple of `scale` representable as a signed `bits`-bit
+    /// integer, clamping to `[-2^(bits-1), 2^(bits-1) - 1] * scale`. Backward
+    /// is the straight-through estimator: the gradient passes unchanged, also
+    /// where the input was clamped.
+    ///
+    /// # Panics
+    /// If `bits` isn't in `1..=31`, or `scale` isn't positive and finite.
+    pub fn fake_quant(&self, bits: u32, scale: f32) -> Self {
+        assert!((1..=31).contains(&bits), "fake_quant needs 1 to 31 bits");
+        assert!(
+            scale > 0.0 && scale.is_finite(),
+            "fake_quant needs a positive finite scale"
+        );
+
+        self.unary(|d| fake_quant(d, bits, scale), Op::QUANT { bits, scale })
+    }
+
+    /// Clips the value to `[lo, hi]`. The gradient passes unchanged inside
+    /// the range, bounds included, and is zero where the value was clipped.
+    /// NaN stays NaN, with a zero gradient.
+    ///
+    /// # Panics
+    /// If `lo > hi` or either bound is NaN.
+    pub fn clamp(&self, lo: f32, hi: f32) -> Self {
+        assert!(lo <= hi, "clamp needs lo <= hi");
+
+        self.unary(|d| clamp(d, lo, hi), Op::CLAMP { lo, hi })
+    }
+
+    /// Natural logarithm. Like `f32::ln`, a negative input gives NaN and zero
+    /// gives negative infinity; the same holds for the other logarithms below.
+    pub fn ln(&self) -> Self {
+        self.unary(T::ln, Op::LN)
+    }
+
+    /// Natural logarithm that refuses inputs `<= 0` (and NaN) instead of
+    /// producing a NaN or infinite node.
+    pub fn try_ln(&self) -> Result<Self, EngineError> {
+        let data = self.data();
+
+        if data > T::zero() {
+            Ok(self.ln())
+        } else {
+            Err(EngineError::DomainErr {
+                op: "ln",
+                value: data.to_f64().unwrap_or(f64::NAN),
+            })
+        }
+    }
+
+    pub fn log2(&self) -> Self {
+        self.unary(T::log2, Op::LOG2)
+    }
+
+    pub fn log10(&self) -> Self {
+        self.unary(T::log10, Op::LOG10)
+    }
+
+    /// Logarithm in an arbitrary `base`, built as `ln(x) / ln(base)`.
+    pub fn log(&self, base: T) -> Self {
+        self.ln() * base.ln().recip()
+    }
+
+    /// Natural exponential; its gradient reuses the output, `exp(x)`.
+    pub fn exp(&self) -> Self {
+        self.unary(T::exp, Op::EXP)
+    }
+
+    fn constant(data: T) -> Self {
+        Scalar::from_value(Value::new(data, ""))
+    }
+}
+
+fn sigmoid<T: Float>(x: T) -> T {
+    T::one() / (T::one() + (-x).exp())
+}
+
+/// √(2/π) and the cubic coefficient of the tanh approximation of GELU.
+const GELU_SCALE: f32 = 0.797_884_6;
+const GELU_CUBIC: f32 = 0.044715;
+
+fn gelu<T: Float>(x: T) -> T {
+    let half = T::from(0.5).unwrap();
+    let inner = T::from(GELU_SCALE).unwrap() * (x + T::from(GELU_CUBIC).unwrap() * x.powi(3));
+
+    half * x * (T::one() + inner.tanh())
+}
+
+/// NaN from either operand wins, unlike `Float::max`.
+fn max_of<T: Float>(a: T, b: T) -> T {
+    if a.is_nan() || a >= b {
+        a
+    } else {
+        b
+    }
+}
+
+fn min_of<T: Float>(a: T, b: T) -> T {
+    if a.is_nan() || a <= b {
+        a
+    } else {
+        b
+    }
+}
+
+fn softplus<T: Float>(x: T) -> T {
+    x.max(T::zero()) + (-x.abs()).exp().ln_1p()
+}
+
+pub(crate) fn elu<T: Float>(x: T, alpha: f32) -> T {
+    if x > T::zero() {
+        x
+    } else {
+        x.exp_m1() * T::from(alpha).unwrap()
+    }
+}
+
+fn leaky_relu<T: Float>(x: T, alpha: f32) -> T {
+    if x > T::zero() {
+        x
+    } else {
+        x * T::from(alpha).unwrap()
+    }
+}
+
+fn fake_quant<T: Float>(d: T, bits: u32, scale: f32) -> T {
+    let scale = f64::from(scale);
+    let levels = (1i64 << (bits - 1)) as f64;
+    let q = (d.to_f64().unwrap() / scale)
+        .round()
+        .clamp(-levels, levels - 1.0);
+
+    T::from(q * scale).unwrap()
+}
+
+/// Like `f32::clamp`, keeping NaN rather than letting `max` replace it.
+fn clamp<T: Float>(d: T, lo: f32, hi: f32) -> T {
+    if d.is_nan() {
+        d
+    } else {
+        d.max(T::from(lo).unwrap()).min(T::from(hi).unwrap())
+    }
+}
+
+fn sign<T: Float>(d: T) -> T {
+    if d > T::zero() {
+        T::one()
+    } else if d < T::zero() {
+        -T::one()
+    } else {
+        T::zero()
+    }
+}
+
+/// Backpropagates from several outputs at once, seeding each with the given
+/// gradient, in a single reverse sweep over their combined graph. Seeds for a
+/// repeated output are summed.
+pub fn backward_multi(seeds: &[(Scalar<f32>, f32)]) {
+    let roots: Vec<Scalar<f32>> = seeds.iter().map(|(s, _)| s.clone()).collect();
+    let scalars = Scalar::topological_from(&roots);
+
+    for s in &scalars {
+        s.set_grad(0.0);
+    }
+
+    for (s, seed) in seeds {
+        s.set_grad(s.grad() + seed);
+    }
+
+    for s in scalars.iter().rev() {
+        s.cal_grad();
+    }
+}
+
+/// Returns d`output`/d`input` for each of `inputs` without disturbing the
+/// gradients stored in the graph: they are restored after the internal pass.
+pub fn grad(output: &Scalar<f32>, inputs: &[Scalar<f32>]) -> Vec<f32> {
+    let scalars = output.topological();
+    let saved: Vec<f32> = scalars.iter().map(|s| s.grad()).collect();
+
+    output.backward_for(inputs);
+
+    let in_graph: HashSet<_> = scalars.iter().map(|s| Arc::as_ptr(&s.0)).collect();
+    let grads = inputs
+        .iter()
+        .map(|i| {
+            if in_graph.contains(&Arc::as_ptr(&i.0)) {
+                i.grad()
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    for (s, g) in scalars.iter().zip(saved) {
+        s.set_grad(g);
+    }
+
+    grads
+}
+
+/// Row `i` holds d`outputs[i]`/d`inputs[j]` for every input `j`, computed
+/// with `grad`, so the gradients stored in the graph are left untouched.
+pub fn jacobian(outputs: &[Scalar<f32>], inputs: &[Scalar<f32>]) -> Vec<Vec<f32>> {
+    outputs.iter().map(|o| grad(o, inputs)).collect()
+}
+
+type Subgraph = dyn Fn(&[Scalar<f32>]) -> Scalar<f32> + Send + Sync;
+
+struct Checkpoint(Box<Subgraph>);
+
+impl Debug for Checkpoint {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Checkpoint")
+    }
+}
+
+impl Checkpoint {
+    fn build(&self, inputs: &[f32]) -> (Vec<Scalar<f32>>, Scalar<f32>) {
+        let leaves: Vec<Scalar<f32>> = inputs.iter().map(|d| Scalar::new(*d, "")).collect();
+        let output = (self.0)(&leaves);
+
+        (leaves, output)
+    }
+}
+
+impl CustomOp for Checkpoint {
+    fn name(&self) -> &str {
+        "checkpoint"
+    }
+
+    fn forward(&self, inputs: &[f32]) -> f32 {
+        self.build(inputs).1.data()
+    }
+
+    fn backward(&self, inputs: &[f32], _output: f32, out_grad: f32) -> Vec<f32> {
+        let (leaves, output) = self.build(inputs);
+
+        // Captured nodes are shared with the outer graph, so their gradients
+        // are put back after the pass instead of being overwritten.
+        let scalars = output.topological();
+        let saved: Vec<f32> = scalars.iter().map(|s| s.grad()).collect();
+
+        backward_multi(&[(output, out_grad)]);
+        let grads = leaves.iter().map(|l| l.grad()).collect();
+
+        for (s, g) in scalars.iter().zip(saved) {
+            s.set_grad(g);
+        }
+
+        grads
+    }
+}
+
+struct Numeric {
+    f: Box<dyn Fn(f32) -> f32 + Send + Sync>,
+    eps: f32,
+}
+
+impl Debug for Numeric {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Numeric {{ eps: {} }}", self.eps)
+    }
+}
+
+impl CustomOp for Numeric {
+    fn name(&self) -> &str {
+        "numeric"
+    }
+
+    fn forward(&self, inputs: &[f32]) -> f32 {
+        (self.f)(inputs[0])
+    }
+
+    fn backward(&self, inputs: &[f32], _output: f32, out_grad: f32) -> Vec<f32> {
+        let x = inputs[0];
+        let slope = ((self.f)(x + self.eps) - (self.f)(x - self.eps)) / (2.0 * self.eps);
+
+        vec![slope * out_grad]
+    }
+}
+
+/// Wraps the subgraph `f(inputs)` in a single node that keeps none of its
+/// intermediates: they are dropped after the forward pass and rebuilt from
+/// the inputs' current values during backward, trading compute for memory.
+/// `f` receives fresh leaves holding the inputs' data, so anything it captures
+/// instead, like parameters, is treated as a constant and gets no gradient
+/// through the checkpoint; its stored gradient is left as it was.
+pub fn checkpoint(
+    inputs: &[Scalar<f32>],
+    f: impl Fn(&[Scalar<f32>]) -> Scalar<f32> + Send + Sync + 'static,
+) -> Scalar<f32> {
+    Scalar::apply_custom(Checkpoint(Box::new(f)), inputs)
+}
+
+/// Adds up `scalars` as a balanced tree of ADD nodes, so the graph depth grows
+/// logarithmically instead of linearly as with a fold. An empty slice sums to zero.
+pub fn sum<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Scalar<T> {
+    match scalars {
+        [] => Scalar::constant(T::zero()),
+        [s] => s.clone(),
+        _ => {
+            let (left, right) = scalars.split_at(scalars.len() / 2);
+
+            sum(left) + sum(right)
+        }
+    }
+}
+
+/// Dot product of two equally long slices, reduced with `sum`.
+pub fn dot<T: Float + NumAssignOps>(
+    a: &[Scalar<T>],
+    b: &[Scalar<T>],
+) -> Result<Scalar<T>, EngineError> {
+    if a.len() != b.len() {
+        return Err(EngineError::InputLenErr);
+    }
+
+    let products: Vec<Scalar<T>> = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| x.clone() * y.clone())
+        .collect();
+
+    Ok(sum(&products))
+}
+
+/// Picks `if_true` when `cond` is positive and `if_false` otherwise. The
+/// condition is not differentiated; the gradient flows only into the picked
+/// branch, and the choice is re-evaluated by `forward`.
+pub fn select<T: Float + NumAssignOps>(
+    cond: &Scalar<T>,
+    if_true: &Scalar<T>,
+    if_false: &Scalar<T>,
+) -> Scalar<T> {
+    let start = profile_start();
+    let data = if cond.data() > T::zero() {
+        if_true.data()
+    } else {
+        if_false.data()
+    };
+
+    let mut output = Value::new(data, "");
+
+    output.children = vec![
+        cond.clone().into(),
+        if_true.clone().into(),
+        if_false.clone().into(),
+    ];
+    Scalar::from_op(output, Op::SELECT, start)
+}
+
+/// Arithmetic mean of `scalars`. An empty slice yields NaN.
+pub fn mean<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Scalar<T> {
+    let n = T::from(scalars.len()).unwrap();
+
+    sum(scalars) * n.recip()
+}
+
+/// Population variance of `scalars`, with gradients flowing through the mean.
+pub fn variance<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Scalar<T> {
+    let m = mean(scalars);
+    let squares: Vec<Scalar<T>> = scalars
+        .iter()
+        .map(|s| (s.clone() - m.clone()).powi(2))
+        .collect();
+
+    mean(&squares)
+}
+
+/// Population standard deviation of `scalars`. Its gradient is infinite when
+/// all values are equal.
+pub fn std<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Scalar<T> {
+    variance(scalars).sqrt()
+}
+
+/// Re-centers `scalars` to zero mean and unit variance, dividing by
+/// `sqrt(variance + 1e-5)` so constant inputs stay finite. Gradients flow
+/// through the mean and variance, as in layer normalization.
+pub fn normalize<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Vec<Scalar<T>> {
+    let m = mean(scalars);
+    let inv_std = (variance(scalars) + T::from(1e-5).unwrap()).powi(-1).sqrt();
+
+    scalars
+        .iter()
+        .map(|s| (s.clone() - m.clone()) * inv_std.clone())
+        .collect()
+}
+
+/// `Scalar::clamp` applied to each of `scalars`: values inside `[lo, hi]`,
+/// bounds included, pass their gradient and clipped ones none. Only for
+/// `f32`, the precision `Op::CLAMP` keeps its bounds in.
+///
+/// # Panics
+/// If `lo > hi` or either bound is NaN.
+pub fn clip_values(scalars: &[Scalar<f32>], lo: f32, hi: f32) -> Vec<Scalar<f32>> {
+    scalars.iter().map(|s| s.clamp(lo, hi)).collect()
+}
+
+/// Piecewise-linear interpolation of the control points `(xs[i], ys[i])` at
+/// `x`, differentiable in `x` (the segment's slope) and in the two `ys`
+/// bounding it. Outside `xs` the end values are held, passing no gradient to
+/// `x`. Fails unless there are at least two points, as many `ys` as `xs`
+/// and `xs` is strictly increasing.
+pub fn interp1d<T: Float + NumAssignOps>(
+    x: &Scalar<T>,
+    xs: &[T],
+    ys: &[Scalar<T>],
+) -> Result<Scalar<T>, EngineError> {
+    if xs.len() < 2 || xs.len() != ys.len() {
+        return Err(EngineError::InputLenErr);
+    }
+    if let Some(index) = xs
+        .windows(2)
+        .position(|w| w[0].partial_cmp(&w[1]) != Some(Ordering::Less))
+    {
+        return Err(EngineError::UnsortedErr { index: index + 1 });
+    }
+
+    let xd = x.data();
+    let last = xs.len() - 2;
+    let k = xs.partition_point(|p| *p <= xd).saturating_sub(1).min(last);
+
+    let t = if xd <= xs[0] {
+        Scalar::constant(T::zero())
+    } else if xd >= xs[last + 1] {
+        Scalar::constant(T::one())
+    } else {
+        (x.clone() - xs[k]) * (xs[k + 1] - xs[k]).recip()
+    };
+
+    Ok(ys[k].clone() + (ys[k + 1].clone() - ys[k].clone()) * t)
+}
+
+/// Standard normal sample via the Box-Muller transform.
+pub(crate) fn gaussian(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = 1.0 - rng.gen::<f32>();
+    let u2: f32 = rng.gen();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
+
+/// Draws from `N(mu, sigma²)` by the reparameterization trick: the result is
+/// `mu + sigma * eps` with the standard normal draw `eps` held as a constant
+/// leaf, so gradients flow to `mu` (1) and `sigma` (`eps`).
+pub fn sample_normal<T: Float + NumAssignOps>(
+    mu: &Scalar<T>,
+    sigma: &Scalar<T>,
+    rng: &mut impl Rng,
+) -> Scalar<T> {
+    let eps = Scalar::from_value(Value::new(T::from(gaussian(rng)).unwrap(), "eps"));
+
+    mu.clone() + sigma.clone() * eps
+}
+
+/// Index and value of each of `scalars`, each locked once. Values are
+/// compared with `f64::total_cmp`, which ranks NaN above infinity.
+fn ranked<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Vec<(usize, f64)> {
+    scalars
+        .iter()
+        .map(|s| s.data().to_f64().unwrap())
+        .enumerate()
+        .collect()
+}
+
+/// Index of the largest value, the first on ties; `None` for an empty slice.
+/// Not differentiable.
+pub fn argmax<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Option<usize> {
+    ranked(scalars)
+        .into_iter()
+        .reduce(|best, x| {
+            if x.1.total_cmp(&best.1).is_gt() {
+                x
+            } else {
+                best
+            }
+        })
+        .map(|(i, _)| i)
+}
+
+/// Index of the smallest value, the first on ties; `None` for an empty slice.
+/// Not differentiable.
+pub fn argmin<T: Float + NumAssignOps>(scalars: &[Scalar<T>]) -> Option<usize> {
+    ranked(scalars)
+        .into_iter()
+        .reduce(|best, x| {
+            if x.1.total_cmp(&best.1).is_lt() {
+                x
+            } else {
+                best
+            }
+        })
+        .map(|(i, _)| i)
+}
+
+/// Indices of the `k` largest values, largest first, earlier indices first on
+/// ties. Returns every index when `k` exceeds the length. Not differentiable.
+pub fn topk<T: Float + NumAssignOps>(scalars: &[Scalar<T>], k: usize) -> Vec<usize> {
+    let mut ranked = ranked(scalars);
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    ranked.into_iter().take(k).map(|(i, _)| i).collect()
+}
+
+/// Softmax of `logits / temperature`. Temperatures above one soften the
+/// distribution, below one sharpen it. `temperature` must be positive and
+/// finite; anything else gives NaN or infinite probabilities.
+pub fn softmax<T: Float + NumAssignOps>(logits: &[Scalar<T>], temperature: T) -> Vec<Scalar<T>> {
+    let exps: Vec<Scalar<T>> = scaled_logits(logits, temperature)
+        .iter()
+        .map(|z| z.exp())
+        .collect();
+    let total = sum(&exps).powi(-1);
+
+    exps.into_iter().map(|e| e * total.clone()).collect()
+}
+
+/// Logarithm of `softmax(logits, temperature)`, computed as
+/// `z - logsumexp(z)` so it stays finite where the probabilities underflow.
+/// Like `softmax`, needs a positive finite `temperature`.
+pub fn log_softmax<T: Float + NumAssignOps>(
+    logits: &[Scalar<T>],
+    temperature: T,
+) -> Vec<Scalar<T>> {
+    let z = scaled_logits(logits, temperature);
+    let exps: Vec<Scalar<T>> = z.iter().map(|z| z.exp()).collect();
+    let lse = sum(&exps).ln();
+
+    z.into_iter().map(|z| z - lse.clone()).collect()
+}
+
+/// Negative log-likelihood of class `target` under `softmax(logits, temperature)`,
+/// computed as `logsumexp(z) - z[target]` to stay finite for large logits.
+/// A `temperature` that isn't positive and finite is a `DomainErr`.
+pub fn cross_entropy<T: Float + NumAssignOps>(
+    logits: &[Scalar<T>],
+    target: usize,
+    temperature: T,
+) -> Result<Scalar<T>, EngineError> {
+    if target >= logits.len() {
+        return Err(EngineError::InputLenErr);
+    }
+
+    check_temperature(temperature)?;
+
+    let z = scaled_logits(logits, temperature);
+    let exps: Vec<Scalar<T>> = z.iter().map(|z| z.exp()).collect();
+
+    Ok(sum(&exps).ln() - z[target].clone())
+}
+
+/// Errors with `DomainErr` unless `temperature` is positive and finite.
+pub(crate) fn check_temperature<T: Float>(temperature: T) -> Result<(), EngineError> {
+    if temperature > T::zero() && temperature.is_finite() {
+        Ok(())
+    } else {
+        Err(EngineError::DomainErr {
+            op: "temperature",
+            value: temperature.to_f64().unwrap_or(f64::NAN),
+        })
+    }
+}
+
+/// `logits / temperature`, shifted by the largest value so `exp` cannot
+/// overflow. The shift is a constant and leaves softmax unchanged.
+fn scaled_logits<T: Float + NumAssignOps>(logits: &[Scalar<T>], temperature: T) -> Vec<Scalar<T>> {
+    let max = logits
+        .iter()
+        .map(|l| l.data())
+        .fold(T::neg_infinity(), T::max);
+
+    logits
+        .iter()
+        .map(|l| (l.clone() - max) * temperature.recip())
+        .collect()
+}
+
+/// Estimated floating point operation counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flops {
+    pub forward: usize,
+    pub backward: usize,
+}
+
+impl Flops {
+    pub fn total(&self) -> usize {
+        self.forward + self.backward
+    }
+}
+
+impl Add for Flops {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Flops {
+            forward: self.forward + other.forward,
+            backward: self.backward + other.backward,
+        }
+    }
+}
+
+impl Mul<usize> for Flops {
+    type Output = Self;
+
+    fn mul(self, n: usize) -> Self {
+        Flops {
+            forward: self.forward * n,
+            backward: self.backward * n,
+        }
+    }
+}
+
+impl std::iter::Sum for Flops {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Flops::default(), Add::add)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpProfile {
+    /// Nodes of this op created.
+    pub built: usize,
+    pub build_time: Duration,
+    /// Nodes of this op backpropagated through.
+    pub backward: usize,
+    pub backward_time: Duration,
+}
+
+/// What `profile` measured, per op and for graph traversals (the topological
+/// sorts run by `backward`, `forward` and friends).
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub ops: BTreeMap<String, OpProfile>,
+    pub traversals: usize,
+    pub traversal_time: Duration,
+}
+
+impl Display for ProfileReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<12} {:>10} {:>12} {:>10} {:>12}",
+            "op", "built", "build ms", "backward", "backward ms"
+        )?;
+
+        for (name, p) in &self.ops {
+            writeln!(
+                f,
+                "{:<12} {:>10} {:>12.3} {:>10} {:>12.3}",
+                name,
+                p.built,
+                p.build_time.as_secs_f64() * 1000.0,
+                p.backward,
+                p.backward_time.as_secs_f64() * 1000.0
+            )?;
+        }
+
+        write!(
+            f,
+            "traversals: {} in {:.3} ms",
+            self.traversals,
+            self.traversal_time.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+thread_local! {
+    static PROFILING: Cell<bool> = const { Cell::new(false) };
+    static PROFILE: RefCell<ProfileReport> = RefCell::new(ProfileReport::default());
+}
+
+/// Runs `f` with profiling on, reporting the per-op counts and time spent
+/// building and backpropagating nodes. Only work on the calling thread is
+/// measured, so worker threads of `backward_parallel` aren't included.
+pub fn profile<R>(f: impl FnOnce() -> R) -> (R, ProfileReport) {
+    let guard = Profiling {
+        outer: PROFILING.with(|p| p.replace(true)),
+        saved: Some(PROFILE.with(|p| p.take())),
+    };
+
+    let result = f();
+
+    (result, guard.finish())
+}
+
+/// Puts back the profiling state `profile` found, even if `f` panics.
+struct Profiling {
+    outer: bool,
+    saved: Option<ProfileReport>,
+}
+
+impl Profiling {
+    fn finish(mut self) -> ProfileReport {
+        let saved = self.saved.take().unwrap_or_default();
+
+        PROFILE.with(|p| p.replace(saved))
+    }
+}
+
+impl Drop for Profiling {
+    fn drop(&mut self) {
+        PROFILING.with(|p| p.set(self.outer));
+
+        if let Some(saved) = self.saved.take() {
+            PROFILE.with(|p| p.replace(saved));
+        }
+    }
+}
+
+fn profile_start() -> Option<Instant> {
+    PROFILING.with(|p| p.get()).then(Instant::now)
+}
+
+fn profile_record(op: &Op, update: impl FnOnce(&mut OpProfile)) {
+    PROFILE.with(|p| update(p.borrow_mut().ops.entry(op.name().to_string()).or_default()));
+}
+
+#[derive(Error, Debug)]
+pub enum EngineError {
+    #[error("input data length error")]
+    InputLenErr,
+    #[error("output value is not finite")]
+    NonFiniteErr,
+    #[error("{value} is outside the domain of {op}")]
+    DomainErr { op: &'static str, value: f64 },
+    /// Points that must be strictly increasing aren't, first at `index`.
+    #[error("point {index} is not above the one before it")]
+    UnsortedErr { index: usize },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let a = Scalar::new(1.0, "a");
+        let b = Scalar::new(2.0, "b");
+
+        assert!(a + b == Scalar::new(3.0, ""));
+
+        let a = Scalar::new(1.0, "a");
+        assert!(a.clone() + a.clone() == Scalar::new(2.0, ""));
+        assert!(a.clone() - a.clone() == Scalar::new(0.0, ""));
+        assert!(a.clone() * a.clone() == Scalar::new(1.0, ""));
+        assert!(a.clone() / a.clone() == Scalar::new(1.0, ""));
+        assert!({
+            let t = a.clone();
+            t.pow(&a) == Scalar::new(1.0, "")
+        });
+
+        assert!(Scalar::new(1.0, "") - Scalar::new(2.0, "") == Scalar::new(-1.0, ""));
+        assert!(Scalar::new(2.0, "") * Scalar::new(3.0, "") == Scalar::new(6.0, ""));
+        assert!(Scalar::new(5.0, "") / Scalar::new(2.0, "") == Scalar::new(2.5, ""));
+        assert!(Scalar::new(2.0, "").pow(&Scalar::new(3.0, "")) == Scalar::new(8.0, ""));
+
+        let a = Scalar::new(1.0, "a");
+        let b = Scalar::new(2.0, "b");
+        let c = Scalar::new(4.0, "c");
+
+        let d = a + b;
+        let e = d * c;
+
+        let (nodes, edges) = e.trace();
+
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|n| {
+                    let v = n.0.lock().unwrap();
+                    v.data
+                })
+                .collect::<Vec<f32>>(),
+            vec![12.0, 3.0, 4.0, 2.0, 1.0]
+        );
+        assert_eq!(edges, vec![(1, 0), (2, 0), (4, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn deterministic_draw() {
+        let build = || {
+            let (a, b) = (Scalar::new(1.0, "a"), Scalar::new(2.0, "b"));
+            let c = Scalar::new(3.0, "c");
+            // Discovered c, b, a from the output, but created a, b, c.
+            (c * b.clone() + a.clone() * b) - a.tanh()
+        };
+        let (y, z) = (build(), build());
+        assert_eq!(y.draw(), y.draw());
+        assert_eq!(y.to_dot(), y.to_dot());
+
+        // Names don't depend on the global ids of the nodes.
+        assert_ne!(y.id(), z.id());
+        assert_eq!(y.draw(), z.draw());
+        assert_eq!(y.to_dot(), z.to_dot());
+        assert_eq!(y.draw_paginated(3), z.draw_paginated(3));
+
+        let ids: Vec<u64> = y.trace().0.iter().map(|n| n.id()).collect();
+        assert!(ids.windows(2).all(|w| w[0] > w[1]));
+
+        // Node declarations follow the same order in DOT.
+        let declared: Vec<usize> = y
+            .to_dot()
+            .lines()
+            .filter(|l| l.contains("shape=record"))
+            .map(|l| l.trim()[1..].split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(declared, (0..ids.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn trace_order_after_weaken() {
+        let x = Scalar::new(0.5, "x") * 2.0;
+        let y = x.tanh() + 1.0;
+        y.weaken();
+        drop(x);
+
+        let (nodes, edges) = y.trace();
+        assert!(nodes[0].ptr_eq(&y));
+        assert!(edges.iter().all(|&(child, parent)| child > parent));
+        assert!(y.to_dot().contains("n0_op -> n0;"));
+    }
+
+    #[test]
+    fn dot_record_escaping() {
+        let dot = Scalar::new(1.0, r#"a|{b}<c> "d" e\"#).to_dot();
+
+        assert!(dot.contains(r#"label="a\|\{b\}\<c\> \"d\" e\\ | data"#));
+    }
+
+    #[test]
+    fn memory_footprint() {
+        let a = Scalar::new(1.0, "a");
+        let leaf = a.memory_footprint();
+
+        assert!(leaf >= size_of::<Value<f32>>());
+
+        let b = a.clone() * a.clone() + a.clone();
+        // Two op nodes with an empty label and two children each.
+        let op = leaf - 1 + 2 * size_of::<Child<f32>>();
+
+        assert_eq!(b.memory_footprint(), leaf + 2 * op);
+
+        b.set_meta("step", 3u64);
+        let meta = b.memory_footprint() - leaf - 2 * op;
+        assert!(meta >= size_of::<Metadata>() + "step".len() + size_of::<u64>());
+    }
+
+    #[test]
+    fn shared_nodes() {
+        let a = Scalar::new(2.0, "a");
+        let b = Scalar::new(3.0, "b");
+        let c = Scalar::new(4.0, "c");
+
+        let x = a.clone() * b.clone();
+        let y = x.clone() + x.clone() * c.clone();
+
+        y.backward();
+
+        assert_eq!(y.data(), 30.0);
+        assert_eq!(a.grad(), 15.0);
+        assert_eq!(b.grad(), 10.0);
+        assert_eq!(c.grad(), 6.0);
+
+        a.set_data(1.0);
+        y.forward();
+        y.backward();
+
+        assert_eq!(x.data(), 3.0);
+        assert_eq!(y.data(), 15.0);
+        assert_eq!(a.grad(), 15.0);
+        assert_eq!(c.grad(), 3.0);
+    }
+
+    #[test]
+    fn balanced_sum() {
+        let xs: Vec<Scalar<f32>> = (1..=8).map(|i| Scalar::new(i as f32, "")).collect();
+        let total = sum(&xs);
+
+        assert_eq!(total.data(), 36.0);
+        assert_eq!(total.trace().0.len(), 15);
+        assert_eq!(total.topological().len(), 15);
+
+        total.backward();
+
+        assert!(xs.iter().all(|x| x.grad() == 1.0));
+        assert_eq!(sum::<f32>(&[]).data(), 0.0);
+    }
+
+    #[test]
+    fn statistics() {
+        let xs: Vec<Scalar<f32>> = [1.0, 2.0, 3.0, 6.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+
+        assert_eq!(mean(&xs).data(), 3.0);
+        assert_eq!(variance(&xs).data(), 3.5);
+
+        let s = std(&xs);
+        s.backward();
+
+        assert_eq!(s.data(), 3.5f32.sqrt());
+        // d std / d x_i = (x_i - mean) / (n * std)
+        for x in &xs {
+            let expected = (x.data() - 3.0) / (4.0 * 3.5f32.sqrt());
+            assert!((x.grad() - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn division() {
+        let a = Scalar::new(3.0, "a");
+        let b = Scalar::new(-2.0, "b");
+        let c = a.clone() / b.clone();
+
+        c.backward();
+        assert_eq!(c.data(), -1.5);
+        assert_eq!(a.grad(), -0.5);
+        assert_eq!(b.grad(), -3.0 / 4.0);
+
+        let d = 1.0 / b.clone() + a.clone() / 2.0;
+        d.backward();
+        assert_eq!(d.data(), -0.5 + 1.5);
+        assert_eq!(a.grad(), 0.5);
+        assert_eq!(b.grad(), -0.25);
+
+        b.set_data(4.0);
+        c.forward();
+        assert_eq!(c.data(), 0.75);
+        assert!(c.draw().contains(">/<"));
+    }
+
+    #[test]
+    fn fractional_power() {
+        let x = Scalar::new(4.0, "x");
+
+        let y = x.powf(0.5);
+        y.backward();
+        assert_eq!(y.data(), 2.0);
+        assert_eq!(x.grad(), 0.25);
+
+        let y = x.powf(-1.5);
+        y.backward();
+        assert_eq!(y.data(), 0.125);
+        assert!((x.grad() + 1.5 * 4f32.powf(-2.5)).abs() < 1e-7);
+    }
+
+    #[test]
+    fn scalar_exponent() {
+        let a = Scalar::new(2.0, "a");
+        let b = Scalar::new(3.0, "b");
+
+        let c = a.pow(&b);
+        c.backward();
+        assert_eq!(c.data(), 8.0);
+        assert_eq!(a.grad(), 12.0);
+        assert!((b.grad() - 8.0 * 2f32.ln()).abs() < 1e-5);
+
+        // A negative base still differentiates in the base only.
+        let a = Scalar::new(-2.0, "a");
+        let b = Scalar::new(2.0, "b");
+        let c = a.pow(&b);
+        c.backward();
+        assert_eq!(c.data(), 4.0);
+        assert_eq!(a.grad(), -4.0);
+        assert_eq!(b.grad(), 0.0);
+    }
+
+    #[test]
+    fn negation() {
+        let x = Scalar::new(3.0, "x");
+
+        let y = -x.clone() * 2.0;
+        y.backward();
+        assert_eq!(y.data(), -6.0);
+        assert_eq!(x.grad(), -2.0);
+        assert_eq!(y.iter_topological().count(), 4);
+        assert!(matches!(y.children()[0].op(), Some(Op::NEG)));
+    }
+
+    #[test]
+    fn max_min() {
+        let (a, b) = (Scalar::new(2.0, "a"), Scalar::new(-1.0, "b"));
+
+        let y = a.max(&b) * 3.0 + a.min(&b);
+        y.backward();
+        assert_eq!(y.data(), 5.0);
+        assert_eq!((a.grad(), b.grad()), (3.0, 1.0));
+
+        // Hinge loss max(0, 1 - m) on margin m.
+        let m = Scalar::new(0.25, "m");
+        let hinge = Scalar::new(0.0, "").max(&(1.0 - m.clone()));
+        hinge.backward();
+        assert_eq!((hinge.data(), m.grad()), (0.75, -1.0));
+
+        let tie = a.max(&Scalar::new(2.0, ""));
+        tie.backward();
+        assert_eq!(a.grad(), 1.0);
+
+        // NaN propagates whichever side it is on, like clamp.
+        let nan = Scalar::new(f32::NAN, "nan");
+        for y in [nan.max(&a), a.max(&nan), nan.min(&a), a.min(&nan)] {
+            y.backward();
+            assert!(y.data().is_nan());
+            assert_eq!((nan.grad(), a.grad()), (1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn normalization() {
+        let xs: Vec<Scalar<f32>> = [1.0, 2.0, 3.0, 6.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+
+        let ys = normalize(&xs);
+        assert!(mean(&ys).data().abs() < 1e-6);
+        assert!((variance(&ys).data() - 1.0).abs() < 1e-4);
+
+        // The outputs sum to zero whatever the inputs, so the gradient of
+        // their sum vanishes only if it flows through the mean.
+        sum(&ys).backward();
+        assert!(xs.iter().all(|x| x.grad().abs() < 1e-5));
+
+        let same = vec![Scalar::new(2.0, ""); 3];
+        assert!(normalize(&same).iter().all(|y| y.data() == 0.0));
+
+        let clipped = clip_values(&xs, 1.5, 3.0);
+        assert_eq!(
+            clipped.iter().map(|c| c.data()).collect::<Vec<_>>(),
+            vec![1.5, 2.0, 3.0, 3.0]
+        );
+        assert!(clipped
+            .iter()
+            .zip(&xs)
+            .all(|(c, x)| c.children()[0].ptr_eq(x)));
+        sum(&clipped).backward();
+        // 3.0 sits exactly on the upper bound and, as with `clamp`, passes.
+        assert_eq!(
+            xs.iter().map(|x| x.grad()).collect::<Vec<_>>(),
+            vec![0.0, 1.0, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn dot_product() {
+        let a: Vec<Scalar<f32>> = [1.0, 2.0, 3.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+        let b: Vec<Scalar<f32>> = [4.0, 5.0, 6.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+
+        let d = dot(&a, &b).unwrap();
+        d.backward();
 
-        vg.do_it(false, false, false, &mut svg);
-        svg.finalize()
+        assert_eq!(d.data(), 32.0);
+        assert_eq!(a[2].grad(), 6.0);
+        assert_eq!(b[0].grad(), 1.0);
+        assert!(dot(&a, &b[1..]).is_err());
     }
-}
 
-impl<T: Float + NumAssignOps + PartialEq> PartialEq for Scalar<T> {
-    fn eq(&self, other: &Self) -> bool {
-        let value = self.0.lock().unwrap();
-        let other_value = other.0.lock().unwrap();
+    #[test]
+    fn select_branch() {
+        let x = Scalar::new(-2.0, "x");
+        let zero = Scalar::new(0.0, "");
+        // relu(x) * 3 built from select
+        let y = select(&x, &x, &zero) * Scalar::new(3.0, "");
+
+        y.backward();
+        assert_eq!(y.data(), 0.0);
+        assert_eq!(x.grad(), 0.0);
+
+        x.set_data(2.0);
+        y.forward();
+        y.backward();
+        assert_eq!(y.data(), 6.0);
+        assert_eq!(x.grad(), 3.0);
+    }
 
-        value.data == other_value.data
+    #[test]
+    fn rounding() {
+        let x = Scalar::new(1.5, "x");
+        let y = x.floor() + x.ceil() + x.round() + x.clone();
+
+        y.backward();
+
+        assert_eq!(y.data(), 1.0 + 2.0 + 2.0 + 1.5);
+        assert_eq!(x.grad(), 1.0);
     }
-}
 
-impl<T: Float + NumAssignOps + PartialEq> Eq for Scalar<T> {}
+    #[test]
+    fn sign_gradients() {
+        let x = Scalar::new(-0.3, "x");
 
-impl<T: Float + NumAssignOps> PartialOrd for Scalar<T> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let value = self.0.lock().unwrap();
-        let other_value = other.0.lock().unwrap();
+        let y = x.sign() * Scalar::new(2.0, "");
+        y.backward();
+        assert_eq!(y.data(), -2.0);
+        assert_eq!(x.grad(), 0.0);
 
-        value.data.partial_cmp(&other_value.data)
+        let y = x.sign_ste() * Scalar::new(2.0, "");
+        y.backward();
+        assert_eq!(y.data(), -2.0);
+        assert_eq!(x.grad(), 2.0);
+
+        assert_eq!(Scalar::new(0.0, "").sign().data(), 0.0);
     }
-}
 
-impl<T: Float + NumAssignOps + PartialEq + Ord> Ord for Scalar<T> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let value = self.0.lock().unwrap();
-        let other_value = other.0.lock().unwrap();
+    #[test]
+    fn clamping() {
+        let xs = [-2.0, -1.0, 0.5, 1.0, 3.0];
+        let (lo, hi) = (-1.0, 1.0);
+
+        for (x, (data, grad)) in
+            xs.iter()
+                .zip([(-1.0, 0.0), (-1.0, 1.0), (0.5, 1.0), (1.0, 1.0), (1.0, 0.0)])
+        {
+            let x = Scalar::new(*x, "x");
+            let y = x.clamp(lo, hi) * 2.0;
+            y.backward();
+
+            assert_eq!((y.data(), x.grad()), (2.0 * data, 2.0 * grad));
+        }
 
-        value.data.cmp(&other_value.data)
+        let nan = Scalar::new(f32::NAN, "nan");
+        let y = nan.clamp(lo, hi);
+        assert!(y.data().is_nan());
+        y.forward();
+        assert!(y.data().is_nan());
+        y.backward();
+        assert_eq!(nan.grad(), 0.0);
+
+        assert!(Scalar::new(0.0, "")
+            .clamp(0.0, 1.0)
+            .draw()
+            .contains("clamp"));
     }
-}
 
-impl<T: Add<Output = T> + Float + NumAssignOps> Add for Scalar<T> {
-    type Output = Self;
+    #[test]
+    fn fake_quantization() {
+        let x = Scalar::new(0.37, "x");
+        let y = x.fake_quant(4, 0.25) * Scalar::new(3.0, "");
+
+        assert_eq!(y.data(), 0.25 * 3.0);
+        // Straight-through: the step function has zero derivative almost
+        // everywhere, so this deliberately disagrees with finite differences.
+        y.backward();
+        assert_eq!(x.grad(), 3.0);
+
+        // A 4-bit grid spans [-8, 7] steps; the gradient passes even when clamped.
+        let x = Scalar::new(5.0, "x");
+        let q = x.fake_quant(4, 0.5);
+        q.backward();
+        assert_eq!(q.data(), 3.5);
+        assert_eq!(x.grad(), 1.0);
+        assert_eq!(Scalar::new(-9.0, "").fake_quant(4, 1.0).data(), -8.0);
+
+        for scale in [0.0, -0.5, f32::NAN, f32::INFINITY] {
+            assert!(std::panic::catch_unwind(|| x.fake_quant(4, scale)).is_err());
+        }
+    }
 
-    fn add(self, other: Self) -> Self {
-        let value = self.0.lock().unwrap();
-        let self_data = value.data;
-        drop(value);
+    #[test]
+    fn logarithms() {
+        let x = Scalar::new(8.0, "x");
 
-        let other_value = other.0.lock().unwrap();
-        let other_data = other_value.data;
-        drop(other_value);
+        let y = x.log2();
+        y.backward();
+        assert_eq!(y.data(), 3.0);
+        assert!((x.grad() - 1.0 / (8.0 * 2f32.ln())).abs() < 1e-6);
 
-        let mut output = Value::new(self_data + other_data, "");
+        let y = x.log(4.0);
+        y.backward();
+        assert!((y.data() - 1.5).abs() < 1e-6);
+        assert!((x.grad() - 1.0 / (8.0 * 4f32.ln())).abs() < 1e-6);
 
-        output.children = (Some(self.clone()), Some(other.clone()));
-        output.op = Some(Op::ADD);
+        let y = x.ln() + x.log10();
+        y.backward();
+        assert!((x.grad() - (1.0 / 8.0 + 1.0 / (8.0 * 10f32.ln()))).abs() < 1e-6);
 
-        Scalar(Arc::new(Mutex::new(output)))
+        assert!(Scalar::new(-1.0, "").ln().data().is_nan());
+        assert_eq!(x.try_ln().unwrap().data(), 8f32.ln());
+        assert_eq!(
+            Scalar::new(0.0f32, "").try_ln().unwrap_err().to_string(),
+            "0 is outside the domain of ln"
+        );
+        assert!(Scalar::new(f32::NAN, "").try_ln().is_err());
+        assert_eq!(Scalar::new(0.0, "").log2().data(), f32::NEG_INFINITY);
     }
-}
 
-impl<T: Add<Output = T> + Float + NumAssignOps> AddAssign for Scalar<T> {
-    fn add_assign(&mut self, other: Self) {
-        let value = self.0.lock().unwrap();
-        let self_data = value.data;
-        drop(value);
+    #[test]
+    fn sigmoid_op() {
+        let x = Scalar::new(0.5, "x");
 
-        let other_value = other.0.lock().unwrap();
-        let other_data = other_value.data;
-        drop(other_value);
+        let y = x.sigmoid();
+        y.backward();
+        assert_eq!(y.children().len(), 1);
+        assert!((y.data() - 1.0 / (1.0 + (-0.5f32).exp())).abs() < 1e-7);
+        assert!((x.grad() - y.data() * (1.0 - y.data())).abs() < 1e-7);
 
-        let mut output = Value::new(self_data + other_data, "");
+        assert_eq!(Scalar::new(-200.0f32, "").sigmoid().data(), 0.0);
+        assert_eq!(Scalar::new(200.0f32, "").sigmoid().data(), 1.0);
+    }
+
+    #[test]
+    fn leaky_relu_op() {
+        let x = Scalar::new(-2.0, "x");
+
+        let y = x.leaky_relu(0.1);
+        y.backward();
+        assert!((y.data() + 0.2).abs() < 1e-7);
+        assert_eq!(x.grad(), 0.1);
+
+        let x = Scalar::new(3.0, "x");
+        let y = x.leaky_relu(0.1) * 2.0;
+        y.backward();
+        assert_eq!(y.data(), 6.0);
+        assert_eq!(x.grad(), 2.0);
+    }
 
-        output.children = (Some(self.clone()), Some(other.clone()));
-        output.op = Some(Op::ADD);
+    #[test]
+    fn gelu_op() {
+        for d in [-2.0f32, -0.3, 0.0, 0.7, 3.0] {
+            let x = Scalar::new(d, "x");
+            let y = x.gelu();
+            y.backward();
+
+            let f = |d: f32| gelu(d as f64) as f32;
+            assert!((y.data() - f(d)).abs() < 1e-6);
+            assert!((x.grad() - (f(d + 1e-3) - f(d - 1e-3)) / 2e-3).abs() < 1e-3);
+        }
 
-        *self = Scalar(Arc::new(Mutex::new(output)));
+        assert_eq!(Scalar::new(0.0f32, "").gelu().data(), 0.0);
+        assert!((Scalar::new(1.0f32, "").gelu().data() - 0.841192).abs() < 1e-5);
     }
-}
 
-impl<T: Sub<Output = T> + Float + NumAssignOps> Sub for Scalar<T> {
-    type Output = Self;
+    #[test]
+    fn softplus_op() {
+        let x = Scalar::new(0.5, "x");
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        let value = self.0.lock().unwrap();
-        let self_data = value.data;
-        drop(value);
+        let y = x.softplus();
+        y.backward();
+        assert!((y.data() - (1.0 + 0.5f32.exp()).ln()).abs() < 1e-6);
+        assert!((x.grad() - x.sigmoid().data()).abs() < 1e-7);
 
-        let rhs_value = rhs.0.lock().unwrap();
-        let rhs_data = rhs_value.data;
-        drop(rhs_value);
+        assert_eq!(Scalar::new(1000.0f32, "").softplus().data(), 1000.0);
+        assert_eq!(Scalar::new(-1000.0f32, "").softplus().data(), 0.0);
+    }
 
-        let mut output = Value::new(self_data - rhs_data, "");
+    #[test]
+    fn silu_op() {
+        let x = Scalar::new(-1.5, "x");
+
+        let y = x.silu();
+        y.backward();
+        assert_eq!(y.iter_topological().count(), 2);
+
+        let unfused = x.clone() * x.sigmoid();
+        assert!((y.data() - unfused.data()).abs() < 1e-7);
+        let fused_grad = x.grad();
+        unfused.backward();
+        assert!((fused_grad - x.grad()).abs() < 1e-6);
+    }
 
-        output.children = (Some(self.clone()), Some(rhs.clone()));
-        output.op = Some(Op::SUB);
+    #[test]
+    fn elu_op() {
+        let x = Scalar::new(-1.0, "x");
+
+        let y = x.elu(0.5);
+        y.backward();
+        assert!((y.data() - 0.5 * ((-1.0f32).exp() - 1.0)).abs() < 1e-7);
+        assert!((x.grad() - 0.5 * (-1.0f32).exp()).abs() < 1e-7);
+
+        let x = Scalar::new(2.0, "x");
+        let y = x.elu(0.5);
+        y.backward();
+        assert_eq!((y.data(), x.grad()), (2.0, 1.0));
+    }
 
-        Scalar(Arc::new(Mutex::new(output)))
+    #[test]
+    fn square_root() {
+        let x = Scalar::new(6.25, "x");
+
+        let y = x.sqrt();
+        y.backward();
+        assert_eq!(y.data(), 2.5);
+        assert_eq!(x.grad(), 0.2);
+        assert!(y.draw().contains(">√<"));
     }
-}
 
-impl<T: Mul<Output = T> + Float + NumAssignOps> Mul for Scalar<T> {
-    type Output = Self;
+    #[test]
+    fn exponential() {
+        let x = Scalar::new(0.5, "x");
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        let value = self.0.lock().unwrap();
-        let self_data = value.data;
-        drop(value);
+        let y = x.exp();
+        y.backward();
+        assert_eq!(x.grad(), 0.5f32.exp());
 
-        let rhs_value = rhs.0.lock().unwrap();
-        let rhs_data = rhs_value.data;
-        drop(rhs_value);
+        // A sigmoid built from exp has gradient s(x) * (1 - s(x)).
+        let s = 1.0 / (1.0 + (-x.clone()).exp());
+        s.backward();
+        assert!((x.grad() - s.data() * (1.0 - s.data())).abs() < 1e-6);
+    }
 
-        let mut output = Value::new(self_data * rhs_data, "");
+    #[test]
+    fn literal_operands() {
+        let x = Scalar::new(3.0, "x");
+        let y = 2.0 * x.clone() + 1.0 - x.clone() * 0.5;
+
+        y.backward();
+        assert_eq!(y.data(), 5.5);
+        assert_eq!(x.grad(), 1.5);
 
-        output.children = (Some(self.clone()), Some(rhs.clone()));
-        output.op = Some(Op::MUL);
+        let z: Scalar<f64> = Scalar::from(2.0) * 4.0;
+        assert_eq!(z.data(), 8.0);
 
-        Scalar(Arc::new(Mutex::new(output)))
+        let mut acc = Scalar::new(1.0, "");
+        acc += 2.0;
+        assert_eq!(acc.data(), 3.0);
     }
-}
 
-// impl<T: Div<Output = T> > Div for Scalar<T> {
-//     type Output = Self;
+    #[test]
+    fn partial_backward() {
+        let x = Scalar::new(2.0, "x");
+        let w = Scalar::new(3.0, "w");
+        let b = Scalar::new(1.0, "b");
+        let u = Scalar::new(5.0, "u");
+        let v = Scalar::new(7.0, "v");
 
-//     fn div(self, rhs: Self) -> Self::Output {
-//         let value = self.0.lock().unwrap();
-//         let self_data = value.data;
-//         drop(value);
+        let y = (x.clone() * w.clone() + b.clone()).tanh() + u.clone() * v.clone();
 
-//         let rhs_value = rhs.0.lock().unwrap();
-//         let rhs_data = rhs_value.data;
-//         drop(rhs_value);
+        y.backward();
+        let expected = (x.grad(), b.grad());
+        v.set_grad(42.0);
 
-//         let mut output = Value::new(self_data / rhs_data, "");
+        y.backward_for(&[x.clone(), b.clone()]);
 
-//         output.children = Some((self.clone(), rhs.clone()));
-//         output.op = Some(Op::DIV);
+        assert_eq!((x.grad(), b.grad()), expected);
+        // u * v doesn't reach x, so its leaves are never visited.
+        assert_eq!(v.grad(), 42.0);
+    }
 
-//         Scalar(Arc::new(Mutex::new(output)))
-//     }
-// }
+    #[test]
+    fn multi_root_backward() {
+        let x = Scalar::new(2.0, "x");
+        let y1 = x.clone() * x.clone();
+        let y2 = x.clone() * 3.0;
+        let shared = y1.clone() + y2.clone();
 
-impl<T: Float + NumAssignOps> Scalar<T> {
-    pub fn powi(&self, n: i32) -> Self {
-        let value = self.0.lock().unwrap();
-        let self_data = value.data;
-        drop(value);
+        backward_multi(&[(y1.clone(), 1.0), (y2.clone(), -2.0), (shared, 0.5)]);
+
+        // 1 * 2x - 2 * 3 + 0.5 * (2x + 3)
+        assert_eq!(x.grad(), 4.0 - 6.0 + 3.5);
+        assert_eq!(y1.grad(), 1.5);
+    }
+
+    #[test]
+    fn functional_grad() {
+        let a = Scalar::new(2.0, "a");
+        let b = Scalar::new(3.0, "b");
+        let unrelated = Scalar::new(1.0, "u");
+        let y = a.clone() * b.clone() + a.clone();
+
+        a.set_grad(-1.0);
+        b.set_grad(-2.0);
 
-        let mut output = Value::new(self_data.powi(n), "");
+        assert_eq!(
+            grad(&y, &[a.clone(), b.clone(), unrelated]),
+            vec![4.0, 2.0, 0.0]
+        );
+        assert_eq!((a.grad(), b.grad(), y.grad()), (-1.0, -2.0, 0.0));
 
-        output.children = (Some(self.clone()), None);
-        output.op = Some(Op::POWI(n));
+        let z = a.clone() * a.clone();
+        assert_eq!(
+            jacobian(&[y, z], &[a.clone(), b.clone()]),
+            vec![vec![4.0, 2.0], vec![4.0, 0.0]]
+        );
+    }
 
-        Scalar(Arc::new(Mutex::new(output)))
+    #[test]
+    fn release_graph() {
+        let x = Scalar::new(2.0, "x");
+        let hidden = x.clone() * 3.0;
+        let weak = Arc::downgrade(&hidden.0);
+        let y = hidden.tanh();
+        drop(hidden);
+
+        y.backward_with(BackwardOptions {
+            retain_graph: false,
+        });
+
+        assert!((x.grad() - 3.0 * (1.0 - 6f32.tanh().powi(2))).abs() < 1e-6);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(y.topological().len(), 1);
     }
 
-    pub fn tanh(&self) -> Self {
-        let value = self.0.lock().unwrap();
-        let self_data = value.data;
-        drop(value);
+    #[test]
+    fn backward_report() {
+        let x = Scalar::new(0.0, "x");
+        let y = x.sqrt() + x.clone() * 3.0;
 
-        let mut output = Value::new(self_data.tanh(), "");
+        let report = y.try_backward().unwrap();
 
-        output.children = (Some(self.clone()), None);
-        output.op = Some(Op::TANH);
+        assert_eq!(report.nodes_visited, 5);
+        assert_eq!(report.ops_executed, 3);
+        // d sqrt(x) / dx is infinite at 0, so x's gradient is inf + 3.
+        assert_eq!(report.nan_count, 0);
+        assert_eq!(report.max_abs_grad, f32::INFINITY);
 
-        Scalar(Arc::new(Mutex::new(output)))
+        let z = (x.clone() - 1.0).ln();
+        assert!(matches!(z.try_backward(), Err(EngineError::NonFiniteErr)));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn parallel_backward() {
+        let xs: Vec<Scalar<f32>> = (0..200).map(|i| Scalar::new(i as f32 * 0.01, "")).collect();
+        let hidden: Vec<Scalar<f32>> = (0..100)
+            .map(|j| {
+                sum(&xs
+                    .iter()
+                    .map(|x| (x.clone() * (j as f32 * 0.001)).tanh())
+                    .collect::<Vec<_>>())
+            })
+            .collect();
+        let y = sum(&hidden).powi(2);
+
+        y.backward();
+        let serial: Vec<f32> = xs.iter().map(|x| x.grad()).collect();
+
+        y.backward_parallel(4);
+        for (x, g) in xs.iter().zip(serial) {
+            assert!((x.grad() - g).abs() <= 1e-3 * g.abs().max(1.0));
+        }
+
+        let deterministic = |threads| {
+            y.backward_parallel_with(ParallelOptions {
+                threads,
+                deterministic: true,
+            });
+            xs.iter().map(|x| x.grad().to_bits()).collect::<Vec<_>>()
+        };
+        let first = deterministic(4);
+        assert_eq!(deterministic(4), first);
+        assert_eq!(deterministic(3), first);
+
+        assert_eq!(ParallelOptions::with_threads(2).threads, 2);
+        assert_eq!(default_threads(Some(" 3")), 3);
+        assert_eq!(default_threads(Some("0")), default_threads(None));
+        assert_eq!(default_threads(Some("many")), default_threads(None));
+    }
 
     #[test]
-    fn it_works() {
-        let a = Scalar::new(1.0, "a");
-        let b = Scalar::new(2.0, "b");
+    fn weak_intermediates() {
+        let x = Scalar::new(2.0, "x");
+        let h = x.clone() * 3.0;
+        let y = (h.clone() + 1.0).tanh();
+
+        y.weaken();
+        h.backward();
+        assert_eq!(x.grad(), 3.0);
+
+        // `h + 1` was only held by `y`, so the path from `y` ends in a
+        // constant holding its last value.
+        let nodes = y.topological();
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes[0].op().is_none() && nodes[0].data() == 7.0);
+
+        // Intermediates kept alive by the caller still take part in backward.
+        let hh = h.clone() * h.clone();
+        let z = hh.clone() + x.clone();
+        z.weaken();
+        z.backward();
+        assert_eq!(x.grad(), 2.0 * 6.0 * 3.0 + 1.0);
+
+        let weak = Arc::downgrade(&h.0);
+        drop((h, hh));
+        assert!(weak.upgrade().is_none());
+        assert_eq!(z.data(), 38.0);
+
+        // The surviving operand keeps its place: `x` still gets its gradient
+        // and recomputing uses the frozen value of `hh`.
+        x.set_data(3.0);
+        z.forward();
+        assert_eq!(z.data(), 39.0);
+        z.backward();
+        assert_eq!(x.grad(), 1.0);
+    }
 
-        assert!(a + b == Scalar::new(3.0, ""));
+    #[test]
+    fn weak_dropped_keeps_identity() {
+        let x = Scalar::new(2.0, "x");
+        let h = (x.clone() * 3.0) + 1.0;
+        let y = h.tanh();
+        let id = h.id();
+        y.weaken();
+        drop(h);
+
+        let first = y.children();
+        assert!(first[0].ptr_eq(&y.children()[0]));
+        assert_eq!((first[0].id(), first[0].data()), (id, 7.0));
+
+        y.backward_parallel(2);
+        assert_eq!(x.grad(), 0.0);
+        assert_eq!(y.draw_paginated(1).len(), 2);
+
+        // Parents of the same dropped node share its stand-in.
+        let h = x.clone() * 3.0;
+        let (t, e) = (h.tanh(), h.exp());
+        let z = t.clone() + e.clone();
+        z.weaken();
+        drop(h);
+
+        assert!(t.children()[0].ptr_eq(&e.children()[0]));
+        assert_eq!(z.topological().len(), 4);
+    }
 
-        let a = Scalar::new(1.0, "a");
-        assert!(a.clone() + a.clone() == Scalar::new(2.0, ""));
-        assert!(a.clone() - a.clone() == Scalar::new(0.0, ""));
-        assert!(a.clone() * a.clone() == Scalar::new(1.0, ""));
-        // assert!(a.clone() / a.clone() == Scalar::new(1.0, ""));
-        // assert!({
-        //     let t = a.clone();
-        //     t.pow(a.clone()) == Scalar::new(1.0, "")
-        // });
+    #[test]
+    fn deep_clone() {
+        let a = Scalar::new(2.0, "a");
+        let b = a.clone() * 3.0;
+        let y = b.clone() * b.clone() + a.clone();
+        y.backward();
+
+        let copy = y.clone_graph();
+        let (nodes, edges) = y.trace();
+        let (copy_nodes, copy_edges) = copy.trace();
+
+        assert_eq!(edges, copy_edges);
+        assert!(nodes.iter().zip(&copy_nodes).all(|(n, c)| {
+            !Arc::ptr_eq(&n.0, &c.0) && n.data() == c.data() && n.grad() == c.grad()
+        }));
+
+        // Mutating the copy's leaf leaves the original untouched.
+        let copy_a = copy_nodes
+            .iter()
+            .find(|n| n.0.lock().unwrap().label == "a")
+            .unwrap();
+        copy_a.set_data(1.0);
+        copy.forward();
 
-        assert!(Scalar::new(1.0, "") - Scalar::new(2.0, "") == Scalar::new(-1.0, ""));
-        assert!(Scalar::new(2.0, "") * Scalar::new(3.0, "") == Scalar::new(6.0, ""));
-        // assert!(Scalar::new(5.0, "") / Scalar::new(2.0, "") == Scalar::new(2.5, ""));
-        // assert!(Scalar::new(2.0, "").pow(Scalar::new(3.0, "")) == Scalar::new(8.0, ""));
+        assert_eq!(copy.data(), 10.0);
+        assert_eq!(y.data(), 38.0);
+    }
+
+    #[test]
+    fn structural_equality() {
+        let build = |a: f32, b: f32| {
+            let a = Scalar::new(a, "a");
+            let b = Scalar::new(b, "b");
+            let c = a.clone() * b;
+
+            (c.clone() + c).tanh()
+        };
 
+        let (x, y) = (build(1.0, 2.0), build(-3.0, 0.5));
+        assert!(x.structurally_eq(&y));
+        assert_eq!(x.structural_hash(), y.structural_hash());
+
+        let a = Scalar::new(1.0, "a");
+        let b = Scalar::new(2.0, "b");
+        // Same ops, but no shared node.
+        let z = (a.clone() * b.clone() + a * b).tanh();
+        assert!(!x.structurally_eq(&z));
+        assert_ne!(x.structural_hash(), z.structural_hash());
+    }
+
+    #[test]
+    fn subgraph() {
         let a = Scalar::new(1.0, "a");
         let b = Scalar::new(2.0, "b");
         let c = Scalar::new(4.0, "c");
 
-        let d = a + b;
-        let e = d * c;
+        let d = a + b.clone();
+        let e = d * c.clone();
 
-        let (nodes, edges) = e.trace();
+        let sub = e.subgraph_to(&c);
+        assert_eq!(
+            sub.nodes.iter().map(|n| n.data()).collect::<Vec<f32>>(),
+            vec![12.0, 4.0]
+        );
+        assert_eq!(sub.edges, vec![(1, 0)]);
+
+        let sub = e.subgraph_to(&b);
+        assert_eq!(sub.nodes.len(), 3);
+        assert_eq!(sub.edges, vec![(1, 0), (2, 1)]);
+        assert!(sub.draw().contains("<svg"));
+
+        assert!(e.subgraph_to(&Scalar::new(0.0, "")).nodes.is_empty());
+    }
+
+    #[test]
+    fn visitor() {
+        struct Collect(Vec<String>, usize);
+
+        impl GraphVisitor<f32> for Collect {
+            fn visit_node(&mut self, node: &Scalar<f32>) {
+                self.0.push(node.get_label());
+            }
+
+            fn visit_edge(&mut self, _child: &Scalar<f32>, _parent: &Scalar<f32>) {
+                self.1 += 1;
+            }
+        }
+
+        let a = Scalar::new(2.0, "a");
+        let b = a.clone() * a.clone();
+        b.label("b");
+        let c = b.clone() + a.clone();
+        c.label("c");
+
+        let mut collect = Collect(vec![], 0);
+        c.visit(&mut collect);
+
+        assert_eq!(collect.0, vec!["a", "b", "c"]);
+        assert_eq!(collect.1, 4);
+        assert!(c.iter_topological().next().unwrap().ptr_eq(&a));
+        assert!(matches!(c.op(), Some(Op::ADD)));
+    }
+
+    #[test]
+    fn metadata() {
+        let x = Scalar::new(1.0, "x");
+
+        x.set_meta("layer", 2usize);
+        x.set_meta("unit", String::from("m/s"));
+
+        assert_eq!(x.get_meta::<usize>("layer"), Some(2));
+        assert_eq!(x.get_meta::<String>("unit").as_deref(), Some("m/s"));
+        assert_eq!(x.get_meta::<f32>("layer"), None);
+        assert_eq!(x.get_meta::<usize>("sample"), None);
+
+        let mut keys = x.meta_keys();
+        keys.sort();
+        assert_eq!(keys, vec!["layer", "unit"]);
+    }
+
+    #[test]
+    fn custom_op() {
+        #[derive(Debug)]
+        struct Hypot;
+
+        impl CustomOp for Hypot {
+            fn name(&self) -> &str {
+                "hypot"
+            }
+
+            fn forward(&self, inputs: &[f32]) -> f32 {
+                inputs[0].hypot(inputs[1])
+            }
+
+            fn backward(&self, inputs: &[f32], output: f32, out_grad: f32) -> Vec<f32> {
+                inputs.iter().map(|x| x / output * out_grad).collect()
+            }
+        }
 
+        let a = Scalar::new(3.0, "a");
+        let b = Scalar::new(4.0, "b");
+        let h = Scalar::apply_custom(Hypot, &[a.clone(), b.clone()]);
+        let y = h.clone() * 2.0;
+        y.backward();
+
+        assert_eq!(h.data(), 5.0);
+        assert_eq!(a.grad(), 1.2);
+        assert_eq!(b.grad(), 1.6);
+
+        b.set_data(0.0);
+        y.forward();
+        assert_eq!(y.data(), 6.0);
+        assert!(y.draw().contains("hypot"));
+    }
+
+    #[test]
+    fn numeric_op() {
+        let x = Scalar::new(0.7, "x");
+        let y = x.apply_numeric(f32::sin, 1e-3) * Scalar::new(2.0, "");
+
+        y.backward();
+        assert_eq!(y.data(), 2.0 * 0.7f32.sin());
+        assert!((x.grad() - 2.0 * 0.7f32.cos()).abs() < 1e-3);
+
+        // Non-differentiable points get the average of the one-sided slopes.
+        let x = Scalar::new(0.0, "x");
+        x.apply_numeric(f32::abs, 1e-2).backward();
+        assert_eq!(x.grad(), 0.0);
+    }
+
+    #[test]
+    fn checkpointing() {
+        let block = |xs: &[Scalar<f32>]| (xs[0].clone() * xs[1].clone()).tanh() + xs[0].powi(2);
+
+        let a = Scalar::new(0.5, "a");
+        let b = Scalar::new(-1.5, "b");
+        let direct = block(&[a.clone(), b.clone()]) * 3.0;
+        direct.backward();
+        let expected = (direct.data(), a.grad(), b.grad());
+
+        let c = checkpoint(&[a.clone(), b.clone()], block) * 3.0;
+        c.backward();
+
+        assert_eq!((c.data(), a.grad(), b.grad()), expected);
+        assert_eq!(c.iter_topological().count(), 5);
+
+        a.set_data(1.0);
+        c.forward();
+        assert_eq!(c.data(), block(&[a.clone(), b.clone()]).data() * 3.0);
+
+        // A captured parameter only gets the gradient from outside the
+        // checkpoint, whichever side of the sum it is on.
+        let w = Scalar::new(2.0, "w");
+        let captured = w.clone();
+        let inner = checkpoint(std::slice::from_ref(&a), move |l| {
+            l[0].clone() * captured.clone()
+        });
+
+        (w.clone() * 5.0 + inner.clone()).backward();
+        assert_eq!((w.grad(), a.grad()), (5.0, 2.0));
+        (inner + w.clone() * 5.0).backward();
+        assert_eq!((w.grad(), a.grad()), (5.0, 2.0));
+    }
+
+    #[test]
+    fn interpolation() {
+        let xs = [0.0, 1.0, 3.0];
+        let ys: Vec<Scalar<f32>> = [1.0, 3.0, 2.0]
+            .iter()
+            .map(|d| Scalar::new(*d, "y"))
+            .collect();
+
+        let x = Scalar::new(2.0, "x");
+        let y = interp1d(&x, &xs, &ys).unwrap();
+        y.backward();
+        assert_eq!(y.data(), 2.5);
+        assert_eq!(x.grad(), -0.5);
         assert_eq!(
-            nodes
-                .iter()
-                .map(|n| {
-                    let v = n.0.lock().unwrap();
-                    v.data
-                })
-                .collect::<Vec<f32>>(),
-            vec![12.0, 3.0, 4.0, 1.0, 2.0]
+            ys.iter().map(|y| y.grad()).collect::<Vec<_>>(),
+            vec![0.0, 0.5, 0.5]
+        );
+
+        // Exactly on a control point, and held beyond the ends.
+        assert_eq!(
+            interp1d(&Scalar::new(1.0, ""), &xs, &ys).unwrap().data(),
+            3.0
+        );
+        let x = Scalar::new(-4.0, "x");
+        let y = interp1d(&x, &xs, &ys).unwrap();
+        y.backward();
+        assert_eq!((y.data(), x.grad(), ys[0].grad()), (1.0, 0.0, 1.0));
+        assert_eq!(
+            interp1d(&Scalar::new(9.0, ""), &xs, &ys).unwrap().data(),
+            2.0
+        );
+
+        assert!(interp1d(&x, &xs[..2], &ys).is_err());
+        assert!(interp1d(&x, &xs[..1], &ys[..1]).is_err());
+        for bad in [[0.0, 3.0, 1.0], [0.0, 1.0, 1.0], [0.0, f32::NAN, 3.0]] {
+            assert!(matches!(
+                interp1d(&x, &bad, &ys),
+                Err(EngineError::UnsortedErr { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn reparameterized_sampling() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (mu, sigma) = (Scalar::new(1.0, "mu"), Scalar::new(0.5, "sigma"));
+
+        let z = sample_normal(&mu, &sigma, &mut rng);
+        z.backward();
+        let eps = (z.data() - 1.0) / 0.5;
+        assert_eq!(mu.grad(), 1.0);
+        assert!((sigma.grad() - eps).abs() < 1e-6);
+        assert!(z.iter_topological().any(|n| n.get_label() == "eps"));
+
+        let stats: crate::metrics::Welford = (0..2000)
+            .map(|_| sample_normal(&mu, &sigma, &mut rng).data())
+            .collect();
+        assert!((stats.mean() - 1.0).abs() < 0.05);
+        assert!((stats.variance() - 0.25).abs() < 0.05);
+    }
+
+    #[test]
+    fn arg_extremes() {
+        let xs: Vec<Scalar<f32>> = [0.5, 2.0, -1.0, 2.0, 0.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+
+        assert_eq!(argmax(&xs), Some(1));
+        assert_eq!(argmin(&xs), Some(2));
+        assert_eq!(topk(&xs, 3), vec![1, 3, 0]);
+        assert_eq!(topk(&xs[..2], 5), vec![1, 0]);
+        assert_eq!(argmax::<f32>(&[]), None);
+    }
+
+    #[test]
+    fn softmax_temperature() {
+        let logits = vec![
+            Scalar::new(1000.0, "a"),
+            Scalar::new(999.0, "b"),
+            Scalar::new(998.0, "c"),
+        ];
+
+        let p = softmax(&logits, 1.0);
+        let total: f32 = p.iter().map(|p| p.data()).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!(p[0].data() > p[1].data() && p[1].data() > p[2].data());
+
+        let soft = softmax(&logits, 10.0);
+        assert!(soft[0].data() < p[0].data());
+
+        let loss = cross_entropy(&logits, 1, 1.0).unwrap();
+        loss.backward();
+        assert!((loss.data() + p[1].data().ln()).abs() < 1e-5);
+        assert!((logits[0].grad() - p[0].data()).abs() < 1e-6);
+        assert!((logits[1].grad() - (p[1].data() - 1.0)).abs() < 1e-6);
+
+        assert!(cross_entropy(&logits, 3, 1.0).is_err());
+        for t in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                cross_entropy(&logits, 1, t),
+                Err(EngineError::DomainErr { .. })
+            ));
+        }
+        assert_eq!(Scalar::new(0.0f32, "").exp().data(), 1.0);
+    }
+
+    #[test]
+    fn node_ids() {
+        let a = Scalar::new(2.0, "a");
+        let b = Scalar::new(3.0, "b");
+        let c = a.clone() * b.clone();
+        c.label("c");
+
+        assert!(a.id() < b.id() && b.id() < c.id());
+
+        // Drawn as c, b, a.
+        let svg = c.draw();
+        assert_eq!(svg, c.draw());
+        assert!(svg.contains("id=\"n2\""));
+        assert!(svg.contains("id=\"n0-op\""));
+
+        let dot = c.to_dot();
+        assert!(dot.contains("n0_op -> n0;"));
+        assert!(dot.contains("n2 -> n0_op;"));
+    }
+
+    #[test]
+    fn paginated_draw() {
+        let x = Scalar::new(1.0, "x");
+        let mut y = x.clone();
+        for _ in 0..6 {
+            y = (y.clone() * 2.0).tanh();
+        }
+
+        let nodes = y.iter_topological().count();
+        let pages = y.draw_paginated(5);
+
+        assert_eq!(pages.len(), nodes.div_ceil(5));
+        assert!(pages.iter().all(|p| p.contains("<svg")));
+        // Numbered in topological order across pages, x first and y last.
+        assert_eq!(pages.iter().filter(|p| p.contains("id=\"n0\"")).count(), 1);
+        assert!(pages[0].contains("page 2: n"));
+        assert!(pages[pages.len() - 1].contains(&format!("id=\"n{}\"", nodes - 1)));
+        assert!(pages[pages.len() - 1].contains("page 1: n"));
+        assert_eq!(y.draw_paginated(nodes).len(), 1);
+    }
+
+    #[test]
+    fn profiling() {
+        let x = Scalar::new(0.5, "x");
+
+        let (y, report) = profile(|| {
+            let y = (x.clone() * x.clone() + 1.0).tanh() * 2.0;
+            y.backward();
+            y
+        });
+
+        assert_eq!(report.ops["MUL"].built, 2);
+        assert_eq!(report.ops["MUL"].backward, 2);
+        assert_eq!(report.ops["ADD"].built, 1);
+        assert_eq!(report.ops["TANH"].backward, 1);
+        assert_eq!(report.traversals, 1);
+        assert!(report.to_string().starts_with("op "));
+
+        // Nothing is recorded outside `profile`.
+        y.backward();
+        let (_, empty) = profile(|| ());
+        assert!(empty.ops.is_empty());
+
+        // Nor after `f` panics.
+        let caught = std::panic::catch_unwind(|| {
+            profile(|| {
+                let _ = Scalar::new(1.0, "") * 2.0;
+                panic!("in profile");
+            })
+        });
+        assert!(caught.is_err());
+        assert!(profile_start().is_none());
+        let (_, empty) = profile(|| ());
+        assert!(empty.ops.is_empty());
+    }
+
+    #[test]
+    fn flop_count() {
+        let x = Scalar::new(0.5, "x");
+        let w = Scalar::new(2.0, "w");
+        let h = x.clone() * w.clone();
+        let y = (h.clone() + h).tanh().powi(-2);
+
+        assert_eq!(
+            y.flops(),
+            Flops {
+                forward: 1 + 1 + 1 + 2,
+                backward: 4 + 2 + 4 + 5,
+            }
         );
-        assert_eq!(edges, vec![(1, 0), (2, 0), (3, 1), (4, 1)]);
+        assert_eq!(x.flops().total(), 0);
     }
 }
@@ -0,0 +1,116 @@
+//! Gradient-based explanations of model predictions.
+
+use super::{
+    engine::{jacobian, Scalar},
+    nn::{NeuronError, MLP},
+};
+use std::fmt::Write;
+
+/// Saliency of each input: row `j` holds d`output_j`/d`x_i` for every input
+/// `i`, evaluated at `x`. Model parameter gradients are left untouched.
+pub fn input_gradients(model: &mut MLP, x: &[f32]) -> Result<Vec<Vec<f32>>, NeuronError> {
+    let inputs: Vec<Scalar<f32>> = x
+        .iter()
+        .enumerate()
+        .map(|(i, d)| Scalar::new(*d, &format!("x{i}")))
+        .collect();
+
+    let outputs = model.output(inputs.clone())?;
+
+    Ok(jacobian(&outputs, &inputs))
+}
+
+/// Horizontal bar chart of `values`, one labelled row each, scaled to the
+/// largest magnitude: positive bars extend right of the center line in blue,
+/// negative ones left in red. Labels are escaped, so any text is safe.
+pub fn bars_svg(values: &[f32], labels: &[&str], width: f32) -> String {
+    const ROW: f32 = 20.0;
+    const LABEL: f32 = 60.0;
+
+    let max = values.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+    let half = (width - LABEL) / 2.0;
+    let center = LABEL + half;
+    let height = ROW * values.len() as f32;
+
+    let mut svg =
+        format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">");
+
+    for (i, v) in values.iter().enumerate() {
+        let y = ROW * i as f32;
+        let len = if max > 0.0 { v.abs() / max * half } else { 0.0 };
+        let (x, color) = if *v >= 0.0 {
+            (center, "steelblue")
+        } else {
+            (center - len, "indianred")
+        };
+        let label = xml_escape(labels.get(i).cloned().unwrap_or_default());
+
+        write!(
+            svg,
+            "<text x=\"0\" y=\"{:.2}\" font-size=\"12\">{label}</text>\
+             <rect x=\"{x:.2}\" y=\"{:.2}\" width=\"{len:.2}\" height=\"{:.2}\" fill=\"{color}\"/>",
+            y + ROW * 0.7,
+            y + 2.0,
+            ROW - 4.0,
+        )
+        .unwrap();
+    }
+
+    write!(
+        svg,
+        "<line x1=\"{center:.2}\" y1=\"0\" x2=\"{center:.2}\" y2=\"{height}\" stroke=\"black\"/></svg>"
+    )
+    .unwrap();
+
+    svg
+}
+
+/// `s` with the characters that are special in XML text and attributes
+/// replaced by entities.
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saliency() {
+        let mut mlp = MLP::seeded(3, &[2], 4);
+        let x = [0.5, -1.0, 2.0];
+        let grads = input_gradients(&mut mlp, &x).unwrap();
+
+        // Finite differences of the plain-float prediction agree.
+        for (i, g) in grads[1].iter().enumerate() {
+            let (mut lo, mut hi) = (x, x);
+            lo[i] -= 1e-2;
+            hi[i] += 1e-2;
+            let fd = (mlp.predict(&hi).unwrap()[1] - mlp.predict(&lo).unwrap()[1]) / 2e-2;
+            assert!((g - fd).abs() < 1e-2);
+        }
+
+        assert!(mlp.parameters().iter().all(|p| p.grad() == 0.0));
+        assert!(input_gradients(&mut mlp, &x[..2]).is_err());
+
+        let svg = bars_svg(&grads[0], &["a", "b", "c"], 200.0);
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert!(svg.contains(">b</text>"));
+
+        let svg = bars_svg(&[1.0], &["x < 0 & \"y\""], 200.0);
+        assert!(svg.contains(">x &lt; 0 &amp; &quot;y&quot;</text>"));
+    }
+}
@@ -0,0 +1,324 @@
+//! C ABI over the engine and `MLP`, declared in `include/micrograd.h`. Build
+//! the shared library with
+//! `cargo rustc --lib --crate-type cdylib --release --features ffi`.
+//!
+//! Scalars and models are handed out as owned pointers, released with
+//! `mg_scalar_free` and `mg_mlp_free`. Ops return new handles and leave
+//! their operands alive.
+
+use super::{engine::Scalar, nn::MLP, optim::SGD, train::Trainer};
+use std::{ptr, slice};
+
+pub type MgScalar = Scalar<f32>;
+pub type MgMlp = MLP;
+
+fn handle<T>(value: T) -> *mut T {
+    Box::into_raw(Box::new(value))
+}
+
+/// `len` items at `data`, or `None` for a null pointer with a non-zero
+/// length. Null with length zero is an empty slice, which
+/// `slice::from_raw_parts` doesn't allow.
+unsafe fn items<'a, T>(data: *const T, len: usize) -> Option<&'a [T]> {
+    match data.is_null() {
+        true if len == 0 => Some(&[]),
+        true => None,
+        false => Some(slice::from_raw_parts(data, len)),
+    }
+}
+
+/// Mutable counterpart of `items`.
+unsafe fn items_mut<'a, T>(data: *mut T, len: usize) -> Option<&'a mut [T]> {
+    match data.is_null() {
+        true if len == 0 => Some(&mut []),
+        true => None,
+        false => Some(slice::from_raw_parts_mut(data, len)),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn mg_scalar_new(data: f32) -> *mut MgScalar {
+    handle(Scalar::new(data, ""))
+}
+
+/// # Safety
+/// `s` must come from this module and not be used afterwards. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_free(s: *mut MgScalar) {
+    if !s.is_null() {
+        drop(Box::from_raw(s));
+    }
+}
+
+/// # Safety
+/// `a` and `b` must be live scalar handles.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_add(a: *const MgScalar, b: *const MgScalar) -> *mut MgScalar {
+    handle((*a).clone() + (*b).clone())
+}
+
+/// # Safety
+/// `a` and `b` must be live scalar handles.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_sub(a: *const MgScalar, b: *const MgScalar) -> *mut MgScalar {
+    handle((*a).clone() - (*b).clone())
+}
+
+/// # Safety
+/// `a` and `b` must be live scalar handles.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_mul(a: *const MgScalar, b: *const MgScalar) -> *mut MgScalar {
+    handle((*a).clone() * (*b).clone())
+}
+
+/// # Safety
+/// `a` must be a live scalar handle.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_powi(a: *const MgScalar, n: i32) -> *mut MgScalar {
+    handle((*a).powi(n))
+}
+
+/// # Safety
+/// `a` must be a live scalar handle.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_tanh(a: *const MgScalar) -> *mut MgScalar {
+    handle((*a).tanh())
+}
+
+/// # Safety
+/// `a` must be a live scalar handle.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_exp(a: *const MgScalar) -> *mut MgScalar {
+    handle((*a).exp())
+}
+
+/// # Safety
+/// `s` must be a live scalar handle.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_backward(s: *const MgScalar) {
+    (*s).backward();
+}
+
+/// # Safety
+/// `s` must be a live scalar handle.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_data(s: *const MgScalar) -> f32 {
+    (*s).data()
+}
+
+/// # Safety
+/// `s` must be a live scalar handle.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_set_data(s: *const MgScalar, data: f32) {
+    (*s).set_data(data);
+}
+
+/// # Safety
+/// `s` must be a live scalar handle.
+#[no_mangle]
+pub unsafe extern "C" fn mg_scalar_grad(s: *const MgScalar) -> f32 {
+    (*s).grad()
+}
+
+/// MLP with `nin` inputs and `n_layers` layers of sizes `nouts`, initialized
+/// like `MLP::seeded`, or null if the shape is invalid or `nouts` is null.
+///
+/// # Safety
+/// `nouts` must be null or point to `n_layers` sizes.
+#[no_mangle]
+pub unsafe extern "C" fn mg_mlp_new(
+    nin: usize,
+    nouts: *const usize,
+    n_layers: usize,
+    seed: u64,
+) -> *mut MgMlp {
+    match items(nouts, n_layers) {
+        Some(nouts) if MLP::check_shape(nin, nouts).is_ok() => {
+            handle(MLP::seeded(nin, nouts, seed))
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `m` must come from `mg_mlp_new` and not be used afterwards. Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn mg_mlp_free(m: *mut MgMlp) {
+    if !m.is_null() {
+        drop(Box::from_raw(m));
+    }
+}
+
+/// Trains with SGD on `n_samples` row-major inputs and targets for `epochs`
+/// epochs, returning the last loss, or NaN if the data doesn't fit the model
+/// or a non-empty buffer is null.
+///
+/// # Safety
+/// `m` must be a live model handle, and `xs` and `ys` must hold `n_samples`
+/// rows of the model's input and output size.
+#[no_mangle]
+pub unsafe extern "C" fn mg_mlp_train(
+    m: *mut MgMlp,
+    xs: *const f32,
+    ys: *const f32,
+    n_samples: usize,
+    lr: f32,
+    epochs: usize,
+) -> f32 {
+    let (nin, nout) = ((*m).input_dim(), (*m).output_dim());
+    let rows = |data: *const f32, width: usize| -> Option<Vec<Vec<f32>>> {
+        let data = items(data, n_samples * width)?;
+        Some(data.chunks(width.max(1)).map(|row| row.to_vec()).collect())
+    };
+    let (Some(xs), Some(ys)) = (rows(xs, nin), rows(ys, nout)) else {
+        return f32::NAN;
+    };
+
+    // Moved out and back in place; `fit` reports failures as errors, so
+    // nothing unwinds in between.
+    let mut trainer = Trainer::new(ptr::read(m), SGD::new(lr));
+    let history = trainer.fit(&xs, &ys, epochs, &mut []);
+    ptr::write(m, trainer.into_model());
+
+    match history {
+        Ok(history) => history.last_loss().unwrap_or(f32::NAN),
+        Err(_) => f32::NAN,
+    }
+}
+
+/// Writes the model's outputs for `input` to `output`, returning how many
+/// were written, or -1 if `input` has the wrong size, `output_len` is too
+/// small or a non-empty buffer is null.
+///
+/// # Safety
+/// `m` must be a live model handle, `input` must hold `input_len` floats and
+/// `output` must have room for `output_len`.
+#[no_mangle]
+pub unsafe extern "C" fn mg_mlp_predict(
+    m: *const MgMlp,
+    input: *const f32,
+    input_len: usize,
+    output: *mut f32,
+    output_len: usize,
+) -> i64 {
+    let Some(input) = items(input, input_len) else {
+        return -1;
+    };
+
+    match (*m).predict(input) {
+        Ok(y) if y.len() <= output_len => match items_mut(output, y.len()) {
+            Some(output) => {
+                output.copy_from_slice(&y);
+                y.len() as i64
+            }
+            None => -1,
+        },
+        _ => -1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c_api() {
+        unsafe {
+            let a = mg_scalar_new(2.0);
+            let b = mg_scalar_new(-3.0);
+            let c = mg_scalar_mul(a, b);
+            let d = mg_scalar_tanh(c);
+
+            mg_scalar_backward(d);
+            assert_eq!(mg_scalar_data(c), -6.0);
+            assert_eq!(mg_scalar_grad(a), -3.0 * (1.0 - (-6.0f32).tanh().powi(2)));
+
+            for s in [a, b, c, d] {
+                mg_scalar_free(s);
+            }
+
+            assert!(mg_mlp_new(2, [4, 0].as_ptr(), 2, 7).is_null());
+
+            let m = mg_mlp_new(2, [4, 1].as_ptr(), 2, 7);
+            let xs = [0.0, 1.0, 1.0, 0.0];
+            let ys = [1.0, -1.0];
+            let first = mg_mlp_train(m, xs.as_ptr(), ys.as_ptr(), 2, 0.05, 1);
+            let last = mg_mlp_train(m, xs.as_ptr(), ys.as_ptr(), 2, 0.05, 50);
+            assert!(last < first);
+
+            let mut out = [0.0; 1];
+            assert_eq!(mg_mlp_predict(m, xs.as_ptr(), 2, out.as_mut_ptr(), 1), 1);
+            assert!(out[0] > 0.0);
+            assert_eq!(mg_mlp_predict(m, xs.as_ptr(), 3, out.as_mut_ptr(), 1), -1);
+
+            // Null buffers are errors, or empty if their length is zero.
+            assert!(mg_mlp_new(2, ptr::null(), 2, 7).is_null());
+            assert!(mg_mlp_new(2, ptr::null(), 0, 7).is_null());
+            assert_eq!(mg_mlp_predict(m, ptr::null(), 2, out.as_mut_ptr(), 1), -1);
+            assert_eq!(mg_mlp_predict(m, xs.as_ptr(), 2, ptr::null_mut(), 0), -1);
+            assert!(mg_mlp_train(m, ptr::null(), ys.as_ptr(), 2, 0.05, 1).is_nan());
+            mg_mlp_free(m);
+        }
+    }
+
+    /// `name` declared with the C type for a Rust type in a signature here.
+    fn c_decl(rust: &str, name: &str) -> String {
+        if let Some(pointee) = rust.strip_prefix("*const ") {
+            return c_decl(pointee, &format!("*{name}")).replacen("", "const ", 1);
+        }
+        if let Some(pointee) = rust.strip_prefix("*mut ") {
+            return c_decl(pointee, &format!("*{name}"));
+        }
+
+        let ty = match rust {
+            "" => "void",
+            "f32" => "float",
+            "i32" => "int32_t",
+            "i64" => "int64_t",
+            "u64" => "uint64_t",
+            "usize" => "size_t",
+            "MgScalar" | "MgMlp" => rust,
+            _ => panic!("no C type for {rust}"),
+        };
+        format!("{ty} {name}")
+    }
+
+    #[test]
+    fn header_matches_exports() {
+        let squash = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+        let header = squash(include_str!("../include/micrograd.h"));
+        let source = include_str!("ffi.rs");
+        let exports: Vec<_> = source[..source.find("#[cfg(test)]").unwrap()]
+            .split("#[no_mangle]")
+            .skip(1)
+            .map(|e| squash(&e[e.find("fn ").unwrap() + 3..e.find('{').unwrap()]))
+            .collect();
+        assert_eq!(exports.len(), 16);
+
+        // Every export is declared with the same C signature.
+        for sig in &exports {
+            let (name, rest) = sig.split_once('(').unwrap();
+            let (params, ret) = rest.rsplit_once(')').unwrap();
+            let params: Vec<_> = params
+                .split(',')
+                .filter_map(|p| p.trim().split_once(": "))
+                .map(|(name, ty)| c_decl(ty, name))
+                .collect();
+            let ret = ret.trim().trim_start_matches("-> ");
+            let decl = format!("{}({});", c_decl(ret, name), params.join(", "));
+
+            assert!(header.contains(&decl), "`{decl}` missing from the header");
+        }
+
+        // And nothing else is.
+        let declared = header
+            .split("mg_")
+            .skip(1)
+            .filter(|rest| {
+                let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_');
+                end.is_some_and(|end| rest[end..].starts_with('('))
+            })
+            .count();
+        assert_eq!(declared, exports.len());
+    }
+}
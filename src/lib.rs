@@ -1,2 +1,25 @@
+pub mod analysis;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod diagnostics;
 pub mod engine;
+pub mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod loss;
+pub mod metrics;
 pub mod nn;
+pub mod optim;
+#[cfg(feature = "plotters")]
+pub mod plot;
+pub mod prelude;
+pub mod rl;
+pub mod sample;
+pub mod testing;
+pub mod text;
+pub mod track;
+pub mod train;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
@@ -0,0 +1,89 @@
+//! Graph-building divergences and information measures over distributions.
+
+use super::engine::{log_softmax, sum, EngineError, Float, NumAssignOps, Scalar};
+
+/// `KL(P || Q) = Σ p (ln p - ln q)` between `softmax(p_logits)` and
+/// `softmax(q_logits)`. Both logarithms come from `log_softmax`, so large
+/// logits and near-zero probabilities stay finite. Fails unless the logits
+/// are equally long and non-empty.
+pub fn kl_div<T: Float + NumAssignOps>(
+    p_logits: &[Scalar<T>],
+    q_logits: &[Scalar<T>],
+) -> Result<Scalar<T>, EngineError> {
+    if p_logits.is_empty() || p_logits.len() != q_logits.len() {
+        return Err(EngineError::InputLenErr);
+    }
+
+    let log_p = log_softmax(p_logits, T::one());
+    let log_q = log_softmax(q_logits, T::one());
+    let terms: Vec<Scalar<T>> = log_p
+        .iter()
+        .zip(log_q)
+        .map(|(lp, lq)| lp.exp() * (lp.clone() - lq))
+        .collect();
+
+    Ok(sum(&terms))
+}
+
+/// Shannon entropy `-Σ p ln p` of `probs`, in nats. Zero probabilities add
+/// nothing, as `p ln p -> 0`, and receive no gradient.
+pub fn entropy<T: Float + NumAssignOps>(probs: &[Scalar<T>]) -> Scalar<T> {
+    let terms: Vec<Scalar<T>> = probs
+        .iter()
+        .filter(|p| p.data() > T::zero())
+        .map(|p| p.clone() * p.ln())
+        .collect();
+
+    -sum(&terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::softmax;
+
+    #[test]
+    fn divergences() {
+        let p: Vec<Scalar<f32>> = [1.0, 2.0, 0.5]
+            .iter()
+            .map(|d| Scalar::new(*d, "p"))
+            .collect();
+        let q: Vec<Scalar<f32>> = [0.0, 1.0, 1.0]
+            .iter()
+            .map(|d| Scalar::new(*d, "q"))
+            .collect();
+
+        let (pp, pq) = (softmax(&p, 1.0), softmax(&q, 1.0));
+        let expected: f32 = pp
+            .iter()
+            .zip(&pq)
+            .map(|(a, b)| a.data() * (a.data() / b.data()).ln())
+            .sum();
+
+        let kl = kl_div(&p, &q).unwrap();
+        kl.backward();
+        assert!((kl.data() - expected).abs() < 1e-6);
+        // d KL / d q_logits = softmax(q) - softmax(p)
+        for i in 0..3 {
+            assert!((q[i].grad() - (pq[i].data() - pp[i].data())).abs() < 1e-6);
+        }
+
+        assert!(kl_div(&p, &p).unwrap().data().abs() < 1e-6);
+        let huge: Vec<Scalar<f32>> = [1000.0, -1000.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+        assert!(kl_div(&huge, &p[..2]).unwrap().data().is_finite());
+        assert!(kl_div(&p, &q[..2]).is_err());
+
+        let probs: Vec<Scalar<f32>> = [0.5, 0.5, 0.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+        let h = entropy(&probs);
+        h.backward();
+        assert!((h.data() - 2f32.ln()).abs() < 1e-6);
+        assert!((probs[0].grad() - (-(0.5f32.ln()) - 1.0)).abs() < 1e-6);
+        assert_eq!(probs[2].grad(), 0.0);
+    }
+}
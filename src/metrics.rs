@@ -0,0 +1,157 @@
+//! Streaming statistics that take values one at a time, without keeping
+//! them around or making a second pass.
+
+/// Running count, mean and variance by Welford's algorithm, which stays
+/// accurate where the naive `Σx² - n·mean²` cancels catastrophically, e.g.
+/// for large values with a small spread. Accumulators over separate chunks
+/// combine with `merge`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Welford {
+    count: u64,
+    mean: f64,
+    /// Sum of squared deviations from the current mean.
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Welford {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, x: f64) {
+        if self.count == 0 {
+            (self.min, self.max) = (x, x);
+        } else {
+            self.min = self.min.min(x);
+            self.max = self.max.max(x);
+        }
+
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Combines with an accumulator over other values, as if all had been
+    /// pushed into one (Chan et al.'s parallel update).
+    pub fn merge(&mut self, other: &Welford) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+
+        self.m2 += other.m2 + delta * delta * (self.count * other.count) as f64 / count as f64;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.count = count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// NaN before any value is pushed.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            f64::NAN
+        } else {
+            self.mean
+        }
+    }
+
+    /// Population variance, NaN before any value is pushed.
+    pub fn variance(&self) -> f64 {
+        self.m2 / self.count as f64
+    }
+
+    /// Unbiased sample variance, NaN with fewer than two values.
+    pub fn sample_variance(&self) -> f64 {
+        if self.count < 2 {
+            f64::NAN
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// Population standard deviation.
+    pub fn std(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Smallest and largest value, `None` before any value is pushed.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        (self.count > 0).then_some((self.min, self.max))
+    }
+}
+
+impl Extend<f64> for Welford {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, iter: I) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+impl Extend<f32> for Welford {
+    fn extend<I: IntoIterator<Item = f32>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(f64::from));
+    }
+}
+
+impl FromIterator<f64> for Welford {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Self {
+        let mut stats = Self::new();
+        stats.extend(iter);
+        stats
+    }
+}
+
+impl FromIterator<f32> for Welford {
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> Self {
+        iter.into_iter().map(f64::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn welford() {
+        let stats: Welford = [2.0f32, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+            .into_iter()
+            .collect();
+        assert_eq!(stats.count(), 8);
+        assert_eq!(stats.mean(), 5.0);
+        assert_eq!(stats.variance(), 4.0);
+        assert_eq!(stats.std(), 2.0);
+        assert!((stats.sample_variance() - 32.0 / 7.0).abs() < 1e-12);
+        assert_eq!(stats.range(), Some((2.0, 9.0)));
+
+        // A tiny spread on a huge offset, where Σx² - n·mean² loses it all.
+        let shifted: Welford = [1e9 + 4.0f64, 1e9 + 7.0, 1e9 + 13.0, 1e9 + 16.0]
+            .into_iter()
+            .collect();
+        assert!((shifted.variance() - 22.5).abs() < 1e-6);
+
+        let mut left: Welford = [2.0f64, 4.0, 4.0].into_iter().collect();
+        let right: Welford = [4.0f64, 5.0, 5.0, 7.0, 9.0].into_iter().collect();
+        left.merge(&right);
+        assert!((left.mean() - stats.mean()).abs() < 1e-12);
+        assert!((left.variance() - stats.variance()).abs() < 1e-12);
+        assert_eq!(left.range(), stats.range());
+
+        let empty = Welford::new();
+        assert!(empty.mean().is_nan() && empty.variance().is_nan());
+        assert_eq!(empty.range(), None);
+    }
+}
@@ -1,15 +1,113 @@
-use super::engine::Scalar;
-use rand::{rngs::ThreadRng, Rng};
+use super::{
+    engine::{dot, elu, Flops, Op, Scalar},
+    optim::Scheduler,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    fmt::{self, Write},
+    mem::size_of,
+    ops::Deref,
+    slice,
+    sync::Arc,
+    vec,
+};
 use thiserror::Error;
 
+/// A model or part of one holding trainable parameters.
+pub trait Module {
+    fn parameters(&self) -> Parameters;
+
+    fn zero_grad(&self) {
+        self.parameters().zero_grad();
+    }
+}
+
+pub type ActivationFn = Arc<dyn Fn(&Scalar<f32>) -> Scalar<f32> + Send + Sync>;
+
+/// Nonlinearity a neuron applies to its weighted sum. `true` and `false`
+/// convert to `Tanh` and `Linear`.
+#[derive(Clone)]
+pub enum Activation {
+    Linear,
+    Tanh,
+    Elu(f32),
+    /// A user expression built from differentiable `Scalar` ops.
+    Custom(ActivationFn),
+}
+
+impl Activation {
+    fn apply(&self, z: Scalar<f32>) -> Scalar<f32> {
+        match self {
+            Activation::Linear => z,
+            Activation::Tanh => z.tanh(),
+            Activation::Elu(alpha) => z.elu(*alpha),
+            Activation::Custom(f) => f(&z),
+        }
+    }
+
+    /// Custom activations are evaluated on a one-node graph.
+    fn apply_f32(&self, z: f32) -> f32 {
+        match self {
+            Activation::Linear => z,
+            Activation::Tanh => z.tanh(),
+            Activation::Elu(alpha) => elu(z, *alpha),
+            Activation::Custom(f) => f(&Scalar::new(z, "")).data(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Activation::Linear => "linear",
+            Activation::Tanh => "tanh",
+            Activation::Elu(_) => "elu",
+            Activation::Custom(_) => "custom",
+        }
+    }
+
+    fn flops(&self) -> Flops {
+        match self {
+            Activation::Linear => Flops::default(),
+            Activation::Tanh => Op::TANH.flops(1),
+            Activation::Elu(alpha) => Op::ELU(*alpha).flops(1),
+            Activation::Custom(f) => f(&Scalar::new(0.0, "")).flops(),
+        }
+    }
+}
+
+impl From<bool> for Activation {
+    fn from(nonlin: bool) -> Self {
+        if nonlin {
+            Activation::Tanh
+        } else {
+            Activation::Linear
+        }
+    }
+}
+
 pub struct Neuron {
     w: Vec<Scalar<f32>>,
     b: Scalar<f32>,
-    nonlin: bool,
+    activation: Activation,
 }
 
 impl Neuron {
-    pub fn new(nin: usize, nonlin: bool, rng: &mut ThreadRng) -> Self {
+    /// A neuron with random weights in `[-1, 1)`, e.g. `Neuron::new(3, true,
+    /// rng)` for tanh or `Neuron::new(3, Activation::Elu(1.0), rng)`.
+    pub fn new(nin: usize, activation: impl Into<Activation>, rng: &mut impl Rng) -> Self {
+        Self::with_activation(nin, activation.into(), rng)
+    }
+
+    /// A neuron applying `f` to its weighted sum instead of tanh.
+    pub fn new_with_activation(
+        nin: usize,
+        f: impl Fn(&Scalar<f32>) -> Scalar<f32> + Send + Sync + 'static,
+        rng: &mut impl Rng,
+    ) -> Self {
+        Self::with_activation(nin, Activation::Custom(Arc::new(f)), rng)
+    }
+
+    fn with_activation(nin: usize, activation: Activation, rng: &mut impl Rng) -> Self {
         let mut w = vec![];
 
         for _ in 0..nin {
@@ -20,61 +118,115 @@ impl Neuron {
         Self {
             w,
             b: Scalar::new(0.0, ""),
-            nonlin,
+            activation,
         }
     }
 
     pub fn output(&mut self, input: Vec<Scalar<f32>>) -> Result<Scalar<f32>, NeuronError> {
-        let mut output = Scalar::new(0.0, "");
-
-        if self.w.len() != input.len() {
-            return Err(NeuronError::InputLenErr);
-        };
+        let output = dot(&input, &self.w)
+            .map_err(|_| NeuronError::input_len(self.w.len(), input.len()))?
+            + self.b.clone();
 
-        for i in 0..self.w.len() {
-            let xi = input[i].clone();
-            let wi = self.w[i].clone();
+        Ok(self.activation.apply(output))
+    }
 
-            output += xi * wi;
+    /// Evaluates the neuron on plain floats without building a graph.
+    pub fn predict(&self, input: &[f32]) -> Result<f32, NeuronError> {
+        if input.len() != self.w.len() {
+            return Err(NeuronError::input_len(self.w.len(), input.len()));
         }
 
-        output += self.b.clone();
+        let output = input
+            .iter()
+            .zip(&self.w)
+            .map(|(x, w)| x * w.data())
+            .sum::<f32>()
+            + self.b.data();
 
-        if self.nonlin {
-            Ok(output.tanh())
-        } else {
-            Ok(output.clone())
-        }
+        Ok(self.activation.apply_f32(output))
     }
 
-    pub fn parameters(&self) -> Vec<Scalar<f32>> {
+    pub fn parameters(&self) -> Parameters {
         let mut w = self.w.clone();
         w.push(self.b.clone());
 
-        w
+        Parameters(w)
+    }
+
+    pub fn zero_grad(&self) {
+        self.parameters().zero_grad();
+    }
+}
+
+/// How a layer's weights were last initialized; its biases always start at zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Init {
+    /// Uniform in `[-1, 1)`, as drawn by `Neuron::new`.
+    Uniform,
+    Orthogonal {
+        gain: f32,
+    },
+    Identity,
+}
+
+impl fmt::Display for Init {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Init::Uniform => write!(f, "uniform(-1, 1) weights, zero biases"),
+            Init::Orthogonal { gain } => write!(f, "orthogonal(gain {gain}) weights, zero biases"),
+            Init::Identity => write!(f, "identity weights, zero biases"),
+        }
     }
 }
 
 pub struct Layer {
     neurons: Vec<Neuron>,
+    init: Init,
 }
 
 impl Layer {
-    pub fn new(nin: usize, nout: usize, nonlin: bool, rng: &mut ThreadRng) -> Self {
+    pub fn new(
+        nin: usize,
+        nout: usize,
+        activation: impl Into<Activation>,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let activation = activation.into();
         let mut neurons = vec![];
 
         for _ in 0..nout {
-            neurons.push(Neuron::new(nin, nonlin, rng));
+            neurons.push(Neuron::with_activation(nin, activation.clone(), rng));
+        }
+
+        Self {
+            neurons,
+            init: Init::Uniform,
         }
+    }
 
-        Self { neurons }
+    /// A layer whose neurons all apply `f` as their nonlinearity, e.g. a
+    /// soft clipping `|z| z.tanh() * 3.0` composed from `Scalar` ops.
+    pub fn new_with_activation(
+        nin: usize,
+        nout: usize,
+        f: impl Fn(&Scalar<f32>) -> Scalar<f32> + Send + Sync + 'static,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let activation = Activation::Custom(Arc::new(f));
+
+        Self {
+            neurons: (0..nout)
+                .map(|_| Neuron::with_activation(nin, activation.clone(), rng))
+                .collect(),
+            init: Init::Uniform,
+        }
     }
 
     pub fn output(&mut self, input: Vec<Scalar<f32>>) -> Result<Vec<Scalar<f32>>, NeuronError> {
         let mut output = vec![];
 
-        for neuron in &mut self.neurons {
-            let o = neuron.output(input.clone())?;
+        for (i, neuron) in self.neurons.iter_mut().enumerate() {
+            let o = neuron.output(input.clone()).map_err(|e| e.at_neuron(i))?;
 
             output.push(o);
         }
@@ -82,20 +234,115 @@ impl Layer {
         Ok(output)
     }
 
-    pub fn parameters(&self) -> Vec<Scalar<f32>> {
+    pub fn predict(&self, input: &[f32]) -> Result<Vec<f32>, NeuronError> {
+        self.neurons
+            .iter()
+            .enumerate()
+            .map(|(i, n)| n.predict(input).map_err(|e| e.at_neuron(i)))
+            .collect()
+    }
+
+    pub fn parameters(&self) -> Parameters {
         self.neurons.iter().flat_map(|n| n.parameters()).collect()
     }
+
+    /// Re-initializes the weights as a `gain`-scaled orthogonal matrix: the
+    /// rows (one per neuron) are orthonormal when there are no more neurons
+    /// than inputs, the columns otherwise. Biases are zeroed. Keeps repeated
+    /// application, as in recurrent weights, from exploding or vanishing.
+    pub fn init_orthogonal(&mut self, gain: f32, rng: &mut impl Rng) {
+        let (nout, nin) = (self.neurons.len(), self.nin());
+        let (count, len) = (nout.min(nin), nout.max(nin));
+        let mut basis: Vec<Vec<f32>> = vec![];
+
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+        let project = |v: &mut Vec<f32>, basis: &[Vec<f32>]| {
+            for b in basis {
+                let proj: f32 = v.iter().zip(b).map(|(x, y)| x * y).sum();
+                v.iter_mut().zip(b).for_each(|(x, y)| *x -= proj * y);
+            }
+        };
+
+        // Gram-Schmidt on random vectors, projecting twice to cancel the
+        // rounding left by the first pass. If the second pass still removes
+        // more than half of what the first left, the draw was numerically
+        // dependent on the previous ones and is redrawn (Kahan-Parlett).
+        while basis.len() < count {
+            let mut v: Vec<f32> = (0..len).map(|_| rng.gen_range(-1.0..1.0)).collect();
+
+            project(&mut v, &basis);
+            let first = norm(&v);
+            project(&mut v, &basis);
+            let second = norm(&v);
+
+            if second > 0.0 && second >= 0.5 * first {
+                basis.push(v.into_iter().map(|x| x / second).collect());
+            }
+        }
+
+        for (i, neuron) in self.neurons.iter().enumerate() {
+            for (j, w) in neuron.w.iter().enumerate() {
+                let x = if nout <= nin {
+                    basis[i][j]
+                } else {
+                    basis[j][i]
+                };
+                w.set_data(gain * x);
+            }
+
+            neuron.b.set_data(0.0);
+        }
+
+        self.init = Init::Orthogonal { gain };
+    }
+
+    /// Re-initializes the weights to the identity (ones where the neuron index
+    /// equals the input index) and the biases to zero, so a recurrent layer
+    /// starts out carrying its state forward unchanged.
+    pub fn init_identity(&mut self) {
+        for (i, neuron) in self.neurons.iter().enumerate() {
+            for (j, w) in neuron.w.iter().enumerate() {
+                w.set_data(if i == j { 1.0 } else { 0.0 });
+            }
+
+            neuron.b.set_data(0.0);
+        }
+
+        self.init = Init::Identity;
+    }
+
+    /// The scheme that last set the weights.
+    pub fn init(&self) -> Init {
+        self.init
+    }
+
+    fn nin(&self) -> usize {
+        self.neurons.first().map_or(0, |n| n.w.len())
+    }
+
+    pub fn zero_grad(&self) {
+        self.parameters().zero_grad();
+    }
 }
 
 pub struct MLP {
     layers: Vec<Layer>,
+    nin: usize,
+    nout: usize,
+    /// Set when built by `seeded`.
+    seed: Option<u64>,
 }
 
 impl MLP {
-    pub fn new(nin: usize, nouts: &[usize], rng: &mut ThreadRng) -> Self {
+    /// # Panics
+    /// If the shape is rejected by `check_shape`.
+    pub fn new(nin: usize, nouts: &[usize], rng: &mut impl Rng) -> Self {
+        Self::check_shape(nin, nouts).unwrap_or_else(|e| panic!("{e}"));
+
         let mut layers = vec![];
 
-        if nouts.len() > 0 {
+        if !nouts.is_empty() {
             layers.push(Layer::new(nin, nouts[0], 0 != nouts.len() - 1, rng));
 
             if nouts.len() > 1 {
@@ -110,27 +357,1334 @@ impl MLP {
             }
         }
 
-        Self { layers }
+        Self {
+            layers,
+            nin,
+            nout: nouts[nouts.len() - 1],
+            seed: None,
+        }
+    }
+
+    /// Like `new`, but each layer is initialized from its own `rng_stream`
+    /// named `layer<i>`. A layer's initial weights depend only on the seed,
+    /// its index and its shape, so adding, removing or resizing layers after
+    /// it leaves them unchanged. Resizing a layer does change the next one:
+    /// its neurons take a different number of inputs, so every draw after
+    /// the first neuron's weights moves.
+    ///
+    /// # Panics
+    /// If the shape is rejected by `check_shape`.
+    pub fn seeded(nin: usize, nouts: &[usize], seed: u64) -> Self {
+        Self::check_shape(nin, nouts).unwrap_or_else(|e| panic!("{e}"));
+
+        let nins = std::iter::once(nin).chain(nouts.iter().cloned());
+
+        Self {
+            layers: nins
+                .zip(nouts)
+                .enumerate()
+                .map(|(i, (nin, nout))| {
+                    let mut rng = rng_stream(seed, &format!("layer{i}"));
+
+                    Layer::new(nin, *nout, i != nouts.len() - 1, &mut rng)
+                })
+                .collect(),
+            nin,
+            nout: nouts[nouts.len() - 1],
+            seed: Some(seed),
+        }
+    }
+
+    /// Validates an architecture before building it: there must be at least
+    /// one layer, and neither the input nor any layer may be empty.
+    pub fn check_shape(nin: usize, nouts: &[usize]) -> Result<(), NeuronError> {
+        if nin == 0 || nouts.is_empty() || nouts.contains(&0) {
+            return Err(NeuronError::ShapeErr {
+                nin,
+                layers: nouts.to_vec(),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn input_dim(&self) -> usize {
+        self.nin
+    }
+
+    pub fn output_dim(&self) -> usize {
+        self.nout
     }
 
     pub fn output(&mut self, mut input: Vec<Scalar<f32>>) -> Result<Vec<Scalar<f32>>, NeuronError> {
-        for layer in &mut self.layers {
-            input = layer.output(input)?;
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            input = layer.output(input).map_err(|e| e.at_layer(i))?;
         }
 
         Ok(input)
     }
 
-    pub fn parameters(&self) -> Vec<Scalar<f32>> {
+    /// Evaluates the model on plain floats without building a graph.
+    pub fn predict(&self, input: &[f32]) -> Result<Vec<f32>, NeuronError> {
+        let mut output = input.to_vec();
+
+        for (i, layer) in self.layers.iter().enumerate() {
+            output = layer.predict(&output).map_err(|e| e.at_layer(i))?;
+        }
+
+        Ok(output)
+    }
+
+    /// Class probabilities for each input: the softmax of the outputs, or for
+    /// a single output `[1 - sigmoid(y), sigmoid(y)]`.
+    pub fn predict_proba(&self, batch: &[Vec<f32>]) -> Result<Vec<Vec<f32>>, NeuronError> {
+        batch
+            .iter()
+            .map(|x| {
+                let y = self.predict(x)?;
+
+                Ok(match y.as_slice() {
+                    [y] => {
+                        let p = 1.0 / (1.0 + (-y).exp());
+                        vec![1.0 - p, p]
+                    }
+                    _ => {
+                        let max = y.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                        let exps: Vec<f32> = y.iter().map(|y| (y - max).exp()).collect();
+                        let total: f32 = exps.iter().sum();
+
+                        exps.iter().map(|e| e / total).collect()
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Most probable class for each input; a single output is thresholded at zero.
+    pub fn predict_class(&self, batch: &[Vec<f32>]) -> Result<Vec<usize>, NeuronError> {
+        Ok(self
+            .predict_proba(batch)?
+            .iter()
+            .map(|p| {
+                p.iter()
+                    .enumerate()
+                    .fold((0, f32::NEG_INFINITY), |best, (i, p)| {
+                        if *p > best.1 {
+                            (i, *p)
+                        } else {
+                            best
+                        }
+                    })
+                    .0
+            })
+            .collect())
+    }
+
+    pub fn parameters(&self) -> Parameters {
         self.layers
             .iter()
             .flat_map(|layer| layer.parameters())
             .collect()
     }
+
+    pub fn zero_grad(&self) {
+        self.parameters().zero_grad();
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// The layers, e.g. to re-initialize them; their shapes can't change.
+    pub fn layers_mut(&mut self) -> &mut [Layer] {
+        &mut self.layers
+    }
+
+    /// Estimated floating point operations of a forward and backward pass
+    /// over `batch_size` inputs, matching `Scalar::flops` on the output graphs.
+    pub fn flops(&self, batch_size: usize) -> Flops {
+        let neurons: Flops = self
+            .layers
+            .iter()
+            .flat_map(|layer| &layer.neurons)
+            .map(|n| {
+                // `dot` sums nin products with nin - 1 adds, plus the bias add.
+                let nin = n.w.len();
+                let dot = Op::MUL.flops(2) * nin + Op::ADD.flops(2) * nin;
+
+                dot + n.activation.flops()
+            })
+            .sum();
+
+        neurons * batch_size
+    }
+
+    /// Architecture summary of the model, to be completed with metrics (and
+    /// the seed, unless built by `seeded`) and saved next to its weights.
+    pub fn describe(&self) -> ModelInfo {
+        let inits: Vec<Init> = self.layers.iter().map(|l| l.init()).collect();
+        let init = if inits.iter().all(|i| *i == inits[0]) {
+            inits[0].to_string()
+        } else {
+            let layers: Vec<String> = inits
+                .iter()
+                .enumerate()
+                .map(|(i, init)| format!("layer {i}: {init}"))
+                .collect();
+            layers.join("; ")
+        };
+
+        ModelInfo {
+            nin: self.nin,
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| layer.neurons.len())
+                .collect(),
+            activations: self
+                .layers
+                .iter()
+                .map(|layer| {
+                    layer
+                        .neurons
+                        .first()
+                        .map_or("linear", |n| n.activation.name())
+                })
+                .collect(),
+            parameters: self.parameters().len(),
+            init,
+            seed: self.seed,
+            metrics: vec![],
+        }
+    }
+
+    pub fn layer_parameters(&self) -> Vec<Parameters> {
+        self.layers.iter().map(|layer| layer.parameters()).collect()
+    }
+
+    /// Estimated bytes used by the model: its parameter nodes and the
+    /// vectors holding them. Graphs built by `output` are not included.
+    pub fn memory_footprint(&self) -> usize {
+        let params: usize = self
+            .parameters()
+            .iter()
+            .map(|p| p.memory_footprint() + size_of::<Scalar<f32>>())
+            .sum();
+        let neurons: usize = self
+            .layers
+            .iter()
+            .map(|layer| layer.neurons.len() * size_of::<Neuron>())
+            .sum();
+
+        size_of::<Self>() + self.layers.len() * size_of::<Layer>() + neurons + params
+    }
+
+    /// Renders layer `layer_idx` as an SVG heat map with one strip per
+    /// neuron and one cell per incoming weight: red for positive, blue for
+    /// negative, saturated at the layer's largest magnitude. For the first
+    /// layer of an image model each strip is the pattern its neuron detects.
+    /// `None` if there is no such layer.
+    pub fn draw_layer_weights(&self, layer_idx: usize) -> Option<String> {
+        const CELL: usize = 8;
+
+        let layer = self.layers.get(layer_idx)?;
+        let weights: Vec<Vec<f32>> = layer
+            .neurons
+            .iter()
+            .map(|n| n.w.iter().map(|w| w.data()).collect())
+            .collect();
+        let max = weights.iter().flatten().fold(0.0f32, |m, w| m.max(w.abs()));
+        let (width, height) = (layer.nin() * CELL, weights.len() * (CELL + 2));
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">"
+        );
+
+        for (row, strip) in weights.iter().enumerate() {
+            for (col, w) in strip.iter().enumerate() {
+                let t = if max > 0.0 { w.abs() / max } else { 0.0 };
+                let fade = (255.0 * (1.0 - t)) as u8;
+                let (r, g, b) = if *w >= 0.0 {
+                    (255, fade, fade)
+                } else {
+                    (fade, fade, 255)
+                };
+
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{CELL}\" height=\"{CELL}\" \
+                     fill=\"rgb({r},{g},{b})\"><title>neuron {row}, input {col}: {w}</title></rect>",
+                    col * CELL,
+                    row * (CELL + 2),
+                ));
+            }
+        }
+
+        svg.push_str("</svg>");
+
+        Some(svg)
+    }
+}
+
+impl Module for Neuron {
+    fn parameters(&self) -> Parameters {
+        Neuron::parameters(self)
+    }
+}
+
+impl Module for Layer {
+    fn parameters(&self) -> Parameters {
+        Layer::parameters(self)
+    }
+}
+
+impl Module for MLP {
+    fn parameters(&self) -> Parameters {
+        MLP::parameters(self)
+    }
+}
+
+/// A `Layer` whose input and output sizes are part of its type, so feeding
+/// it a wrongly sized array doesn't compile.
+pub struct TypedLayer<const IN: usize, const OUT: usize> {
+    inner: Layer,
+}
+
+impl<const IN: usize, const OUT: usize> TypedLayer<IN, OUT> {
+    pub fn new(nonlin: bool, rng: &mut impl Rng) -> Self {
+        Self {
+            inner: Layer::new(IN, OUT, nonlin, rng),
+        }
+    }
+
+    pub fn output(&mut self, input: &[Scalar<f32>; IN]) -> [Scalar<f32>; OUT] {
+        let output = self
+            .inner
+            .output(input.to_vec())
+            .expect("sizes are checked by the type");
+
+        to_array(output)
+    }
+
+    pub fn predict(&self, input: &[f32; IN]) -> [f32; OUT] {
+        to_array(
+            self.inner
+                .predict(input)
+                .expect("sizes are checked by the type"),
+        )
+    }
+
+    pub fn inner(&self) -> &Layer {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Layer {
+        self.inner
+    }
+}
+
+impl<const IN: usize, const OUT: usize> Module for TypedLayer<IN, OUT> {
+    fn parameters(&self) -> Parameters {
+        self.inner.parameters()
+    }
+}
+
+/// An `MLP` with `IN` inputs and `OUT` outputs fixed by its type. The hidden
+/// layer sizes stay dynamic.
+pub struct TypedMLP<const IN: usize, const OUT: usize> {
+    inner: MLP,
+}
+
+impl<const IN: usize, const OUT: usize> TypedMLP<IN, OUT> {
+    /// # Panics
+    /// If `IN`, `OUT` or a hidden size is zero.
+    pub fn new(hidden: &[usize], rng: &mut impl Rng) -> Self {
+        Self {
+            inner: MLP::new(IN, &[hidden, &[OUT]].concat(), rng),
+        }
+    }
+
+    /// Like `MLP::seeded`.
+    pub fn seeded(hidden: &[usize], seed: u64) -> Self {
+        Self {
+            inner: MLP::seeded(IN, &[hidden, &[OUT]].concat(), seed),
+        }
+    }
+
+    /// Wraps a dynamic model, failing if its dimensions don't match the type.
+    pub fn from_mlp(mlp: MLP) -> Result<Self, NeuronError> {
+        if mlp.input_dim() != IN || mlp.output_dim() != OUT {
+            return Err(NeuronError::DimErr {
+                expected: (IN, OUT),
+                actual: (mlp.input_dim(), mlp.output_dim()),
+            });
+        }
+
+        Ok(Self { inner: mlp })
+    }
+
+    pub fn output(&mut self, input: &[Scalar<f32>; IN]) -> [Scalar<f32>; OUT] {
+        let output = self
+            .inner
+            .output(input.to_vec())
+            .expect("sizes are checked by the type");
+
+        to_array(output)
+    }
+
+    pub fn predict(&self, input: &[f32; IN]) -> [f32; OUT] {
+        to_array(
+            self.inner
+                .predict(input)
+                .expect("sizes are checked by the type"),
+        )
+    }
+
+    pub fn inner(&self) -> &MLP {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> MLP {
+        self.inner
+    }
+}
+
+impl<const IN: usize, const OUT: usize> Module for TypedMLP<IN, OUT> {
+    fn parameters(&self) -> Parameters {
+        self.inner.parameters()
+    }
+}
+
+fn to_array<T, const N: usize>(v: Vec<T>) -> [T; N] {
+    v.try_into()
+        .unwrap_or_else(|_| unreachable!("sizes are checked by the type"))
 }
 
-#[derive(Error, Debug)]
+/// A frozen copy of an `MLP` for repeated inference. The weights are read
+/// once and activations ping-pong between two buffers allocated up front,
+/// so `run` doesn't allocate, unless a layer has a custom activation. Call
+/// `refresh` after the model is trained further.
+pub struct InferenceSession {
+    layers: Vec<FrozenLayer>,
+    buffers: [Vec<f32>; 2],
+}
+
+struct FrozenLayer {
+    nin: usize,
+    nout: usize,
+    /// Row-major, one row per neuron.
+    weights: Vec<f32>,
+    biases: Vec<f32>,
+    activation: Activation,
+}
+
+impl InferenceSession {
+    pub fn new(model: &MLP) -> Self {
+        let mut session = Self {
+            layers: vec![],
+            buffers: [vec![], vec![]],
+        };
+        session.refresh(model);
+        session
+    }
+
+    /// Re-reads the model's weights, reusing the existing allocations when
+    /// the architecture is unchanged.
+    pub fn refresh(&mut self, model: &MLP) {
+        self.layers.resize_with(model.layers.len(), || FrozenLayer {
+            nin: 0,
+            nout: 0,
+            weights: vec![],
+            biases: vec![],
+            activation: Activation::Linear,
+        });
+
+        for (frozen, layer) in self.layers.iter_mut().zip(&model.layers) {
+            frozen.nin = layer.nin();
+            frozen.nout = layer.neurons.len();
+            frozen.activation = layer
+                .neurons
+                .first()
+                .map_or(Activation::Linear, |n| n.activation.clone());
+            frozen.weights.clear();
+            frozen.biases.clear();
+
+            for n in &layer.neurons {
+                frozen.weights.extend(n.w.iter().map(|w| w.data()));
+                frozen.biases.push(n.b.data());
+            }
+        }
+
+        let width = self
+            .layers
+            .iter()
+            .map(|l| l.nin.max(l.nout))
+            .max()
+            .unwrap_or(0);
+
+        for buffer in &mut self.buffers {
+            buffer.resize(width, 0.0);
+        }
+    }
+
+    /// Overwrites the frozen weights and biases with `values`, in
+    /// `MLP::parameters` order, without touching the model they came from.
+    pub(crate) fn set_parameters(&mut self, values: &[f32]) {
+        let mut values = values.iter();
+
+        for layer in &mut self.layers {
+            for (row, b) in layer.weights.chunks_mut(layer.nin).zip(&mut layer.biases) {
+                for (p, v) in row.iter_mut().chain(std::iter::once(b)).zip(&mut values) {
+                    *p = *v;
+                }
+            }
+        }
+    }
+
+    pub fn nin(&self) -> usize {
+        self.layers.first().map_or(0, |l| l.nin)
+    }
+
+    pub fn nout(&self) -> usize {
+        self.layers.last().map_or(0, |l| l.nout)
+    }
+
+    /// Writes the model's outputs for `input` into `output`, which must be
+    /// exactly `nout()` long.
+    pub fn run(&mut self, input: &[f32], output: &mut [f32]) -> Result<(), NeuronError> {
+        if input.len() != self.nin() {
+            return Err(NeuronError::input_len(self.nin(), input.len()));
+        }
+
+        if output.len() != self.nout() {
+            return Err(NeuronError::OutputLenErr {
+                expected: self.nout(),
+                actual: output.len(),
+            });
+        }
+
+        let [front, back] = &mut self.buffers;
+        let (mut src, mut dst) = (front, back);
+        src[..input.len()].copy_from_slice(input);
+
+        for layer in &self.layers {
+            let x = &src[..layer.nin];
+
+            for (i, y) in dst[..layer.nout].iter_mut().enumerate() {
+                let w = &layer.weights[i * layer.nin..(i + 1) * layer.nin];
+                let z = x.iter().zip(w).map(|(x, w)| x * w).sum::<f32>() + layer.biases[i];
+
+                *y = layer.activation.apply_f32(z);
+            }
+
+            std::mem::swap(&mut src, &mut dst);
+        }
+
+        output.copy_from_slice(&src[..output.len()]);
+
+        Ok(())
+    }
+}
+
+/// An independent ChaCha stream for the module at `path`: the same seed and
+/// path always yield the same numbers, and different paths don't overlap.
+pub fn rng_stream(seed: u64, path: &str) -> ChaCha8Rng {
+    // FNV-1a, stable across platforms and compiler versions.
+    let stream = path.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    rng
+}
+
+/// A trainable lookup table from token ids to vectors, equivalent to a
+/// bias-free linear layer applied to one-hot inputs but without the zero
+/// products.
+pub struct Embedding {
+    weights: Vec<Vec<Scalar<f32>>>,
+}
+
+impl Embedding {
+    /// `vocab` rows of `dim` weights drawn uniformly from `[-1, 1]`.
+    pub fn new(vocab: usize, dim: usize, rng: &mut impl Rng) -> Self {
+        Self {
+            weights: (0..vocab)
+                .map(|_| {
+                    (0..dim)
+                        .map(|_| Scalar::new(rng.gen_range(-1.0..1.0), ""))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// The row of `token`, sharing its weights so gradients reach the table.
+    pub fn lookup(&self, token: usize) -> Result<Vec<Scalar<f32>>, NeuronError> {
+        self.weights
+            .get(token)
+            .cloned()
+            .ok_or(NeuronError::TokenErr {
+                token,
+                vocab: self.weights.len(),
+            })
+    }
+
+    pub fn vocab_size(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn dim(&self) -> usize {
+        self.weights.first().map_or(0, |row| row.len())
+    }
+}
+
+impl Module for Embedding {
+    fn parameters(&self) -> Parameters {
+        self.weights.iter().flatten().cloned().collect()
+    }
+}
+
+/// A learnable strictly positive value, such as a variance, temperature or
+/// rate, stored as its unconstrained logarithm so optimizer steps can't make
+/// it negative.
+pub struct PositiveParam {
+    raw: Scalar<f32>,
+}
+
+impl PositiveParam {
+    /// Panics unless `initial` is positive.
+    pub fn new(initial: f32, label: &str) -> Self {
+        assert!(initial > 0.0, "PositiveParam must start positive");
+
+        Self {
+            raw: Scalar::new(initial.ln(), label),
+        }
+    }
+
+    /// `exp(raw)` as a graph node, to be used in the computation.
+    pub fn value(&self) -> Scalar<f32> {
+        self.raw.exp()
+    }
+
+    /// Current value without building a graph.
+    pub fn get(&self) -> f32 {
+        self.raw.data().exp()
+    }
+
+    /// The unconstrained log-space scalar the optimizer updates.
+    pub fn raw(&self) -> &Scalar<f32> {
+        &self.raw
+    }
+}
+
+impl Module for PositiveParam {
+    fn parameters(&self) -> Parameters {
+        Parameters(vec![self.raw.clone()])
+    }
+}
+
+/// Inverted dropout: while training, zeroes each input with probability `p`
+/// and scales the survivors by `1 / (1 - p)`; in evaluation it is the identity.
+pub struct Dropout {
+    p: f32,
+    training: bool,
+    rng: StdRng,
+    schedule: Option<Box<dyn Scheduler + Send>>,
+}
+
+impl Dropout {
+    pub fn new(p: f32, seed: u64) -> Self {
+        Self {
+            p,
+            training: true,
+            rng: StdRng::seed_from_u64(seed),
+            schedule: None,
+        }
+    }
+
+    /// Drives `p` from `schedule`, applied on each `set_step`.
+    pub fn with_schedule(mut self, schedule: impl Scheduler + Send + 'static) -> Self {
+        self.p = schedule.value(0);
+        self.schedule = Some(Box::new(schedule));
+        self
+    }
+
+    pub fn p(&self) -> f32 {
+        self.p
+    }
+
+    pub fn set_p(&mut self, p: f32) {
+        self.p = p;
+    }
+
+    /// Moves to training step `step`, updating `p` if a schedule is set.
+    pub fn set_step(&mut self, step: usize) {
+        if let Some(schedule) = &self.schedule {
+            self.p = schedule.value(step);
+        }
+    }
+
+    pub fn train(&mut self, training: bool) {
+        self.training = training;
+    }
+
+    pub fn output(&mut self, input: Vec<Scalar<f32>>) -> Vec<Scalar<f32>> {
+        if !self.training || self.p <= 0.0 {
+            return input;
+        }
+
+        let scale = 1.0 / (1.0 - self.p);
+
+        input
+            .into_iter()
+            .map(|x| {
+                if self.rng.gen::<f32>() < self.p {
+                    x * 0.0
+                } else {
+                    x * scale
+                }
+            })
+            .collect()
+    }
+}
+
+/// The trainable scalars of a module, in a stable order.
+#[derive(Debug, Clone, Default)]
+pub struct Parameters(Vec<Scalar<f32>>);
+
+impl Parameters {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Scalar<f32>> {
+        self.0.iter()
+    }
+
+    /// L2 norm of all gradients.
+    pub fn grad_norm(&self) -> f32 {
+        self.0.iter().map(|p| p.grad().powi(2)).sum::<f32>().sqrt()
+    }
+
+    pub fn zero_grad(&self) {
+        self.apply(|p| p.set_grad(0.0));
+    }
+
+    pub fn apply(&self, f: impl FnMut(&Scalar<f32>)) {
+        self.0.iter().for_each(f);
+    }
+}
+
+impl Deref for Parameters {
+    type Target = [Scalar<f32>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<Scalar<f32>>> for Parameters {
+    fn from(params: Vec<Scalar<f32>>) -> Self {
+        Parameters(params)
+    }
+}
+
+impl FromIterator<Scalar<f32>> for Parameters {
+    fn from_iter<I: IntoIterator<Item = Scalar<f32>>>(iter: I) -> Self {
+        Parameters(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Parameters {
+    type Item = Scalar<f32>;
+    type IntoIter = vec::IntoIter<Scalar<f32>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Parameters {
+    type Item = &'a Scalar<f32>;
+    type IntoIter = slice::Iter<'a, Scalar<f32>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInfo {
+    pub nin: usize,
+    /// Output size of each layer.
+    pub layers: Vec<usize>,
+    pub activations: Vec<&'static str>,
+    pub parameters: usize,
+    pub init: String,
+    pub seed: Option<u64>,
+    pub metrics: Vec<(String, f32)>,
+}
+
+impl ModelInfo {
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_metric(mut self, name: &str, value: f32) -> Self {
+        self.metrics.push((name.to_string(), value));
+        self
+    }
+
+    /// Non-finite metrics are written as `null`.
+    pub fn to_json(&self) -> String {
+        let list = |items: Vec<String>| items.join(",");
+        let metrics = self
+            .metrics
+            .iter()
+            .map(|(name, value)| format!("{}:{}", json_string(name), json_number(*value)))
+            .collect();
+
+        format!(
+            "{{\"nin\":{},\"layers\":[{}],\"activations\":[{}],\"parameters\":{},\"init\":{},\"seed\":{},\"metrics\":{{{}}}}}",
+            self.nin,
+            list(self.layers.iter().map(|l| l.to_string()).collect()),
+            list(self.activations.iter().map(|a| json_string(a)).collect()),
+            self.parameters,
+            json_string(&self.init),
+            self.seed.map_or("null".to_string(), |s| s.to_string()),
+            list(metrics)
+        )
+    }
+}
+
+/// `s` as a quoted JSON string, escaped per RFC 8259.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+/// `x` as a JSON number, or `null` for NaN and infinities, which JSON lacks.
+pub(crate) fn json_number(x: f32) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum NeuronError {
-    #[error("input data length error")]
-    InputLenErr,
+    /// An input vector of the wrong length. `layer` and `neuron` locate the
+    /// failure inside a model, when it happened in one.
+    #[error("{}expected {expected} inputs, got {actual}", location(.layer, .neuron))]
+    InputLenErr {
+        expected: usize,
+        actual: usize,
+        layer: Option<usize>,
+        neuron: Option<usize>,
+    },
+    #[error("expected an output buffer of {expected}, got {actual}")]
+    OutputLenErr { expected: usize, actual: usize },
+    #[error("sample {sample}: expected {expected} targets, got {actual}")]
+    TargetLenErr {
+        sample: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("sample {sample}: class {class} out of range for {classes} outputs")]
+    ClassErr {
+        sample: usize,
+        class: f32,
+        classes: usize,
+    },
+    #[error("{inputs} input rows but {targets} target rows")]
+    BatchLenErr { inputs: usize, targets: usize },
+    #[error("token {token} out of range for a vocabulary of {vocab}")]
+    TokenErr { token: usize, vocab: usize },
+    #[error("invalid shape: {nin} inputs into layers {layers:?}; sizes must be non-zero")]
+    ShapeErr { nin: usize, layers: Vec<usize> },
+    /// A model whose `(inputs, outputs)` differ from the ones required.
+    #[error("expected a model from {} inputs to {} outputs, got {} to {}", .expected.0, .expected.1, .actual.0, .actual.1)]
+    DimErr {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+}
+
+impl NeuronError {
+    pub(crate) fn input_len(expected: usize, actual: usize) -> Self {
+        NeuronError::InputLenErr {
+            expected,
+            actual,
+            layer: None,
+            neuron: None,
+        }
+    }
+
+    fn at_neuron(mut self, index: usize) -> Self {
+        if let NeuronError::InputLenErr { neuron, .. } = &mut self {
+            neuron.get_or_insert(index);
+        }
+        self
+    }
+
+    fn at_layer(mut self, index: usize) -> Self {
+        if let NeuronError::InputLenErr { layer, .. } = &mut self {
+            layer.get_or_insert(index);
+        }
+        self
+    }
+}
+
+fn location(layer: &Option<usize>, neuron: &Option<usize>) -> String {
+    match (layer, neuron) {
+        (Some(l), Some(n)) => format!("layer {l}, neuron {n}: "),
+        (Some(l), None) => format!("layer {l}: "),
+        (None, Some(n)) => format!("neuron {n}: "),
+        (None, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::sum,
+        optim::{Optimizer, SGD},
+    };
+
+    #[test]
+    fn parameters() {
+        let mut rng = rand::thread_rng();
+        let mlp = MLP::new(3, &[4, 1], &mut rng);
+        let params = mlp.parameters();
+
+        assert_eq!(params.len(), 4 * 4 + 5);
+        assert_eq!(mlp.layer_parameters()[1].len(), 5);
+
+        params.apply(|p| p.set_grad(2.0));
+        assert_eq!(params.grad_norm(), (4.0 * params.len() as f32).sqrt());
+
+        params.zero_grad();
+        assert!(params.iter().all(|p| p.grad() == 0.0));
+
+        params.apply(|p| p.set_grad(1.0));
+        mlp.layers()[0].zero_grad();
+        assert_eq!(mlp.parameters().grad_norm(), 5.0f32.sqrt());
+
+        mlp.zero_grad();
+        assert_eq!(mlp.parameters().grad_norm(), 0.0);
+    }
+
+    #[test]
+    fn scheduled_dropout() {
+        let input: Vec<Scalar<f32>> = (0..100).map(|_| Scalar::new(1.0, "")).collect();
+        let mut dropout = Dropout::new(0.0, 3).with_schedule(|step| 0.1 * step as f32);
+        let unchanged =
+            |output: Vec<Scalar<f32>>| output.iter().zip(&input).all(|(o, i)| o.ptr_eq(i));
+
+        assert!(unchanged(dropout.output(input.clone())));
+
+        dropout.set_step(5);
+        assert_eq!(dropout.p(), 0.5);
+
+        let output = dropout.output(input.clone());
+        let kept = output.iter().filter(|x| x.data() != 0.0).count();
+        assert!(kept > 25 && kept < 75);
+        assert!(output.iter().all(|x| x.data() == 0.0 || x.data() == 2.0));
+
+        dropout.train(false);
+        assert!(unchanged(dropout.output(input.clone())));
+    }
+
+    #[test]
+    fn predictions() {
+        let mut rng = rand::thread_rng();
+        let mut mlp = MLP::new(2, &[4, 3], &mut rng);
+        let batch = vec![vec![0.5, -1.0], vec![2.0, 0.1]];
+
+        let proba = mlp.predict_proba(&batch).unwrap();
+        let classes = mlp.predict_class(&batch).unwrap();
+
+        for ((x, p), c) in batch.iter().zip(&proba).zip(classes) {
+            let y = mlp
+                .output(x.iter().map(|d| Scalar::new(*d, "")).collect())
+                .unwrap();
+            let predicted = mlp.predict(x).unwrap();
+
+            for (a, b) in y.iter().zip(&predicted) {
+                assert!((a.data() - b).abs() < 1e-6);
+            }
+            assert!((p.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+            assert!(p.iter().all(|q| *q <= p[c]));
+        }
+
+        let binary = MLP::new(2, &[1], &mut rng);
+        let y = binary.predict(&batch[0]).unwrap()[0];
+        assert_eq!(
+            binary.predict_class(&batch[..1]).unwrap(),
+            vec![usize::from(y > 0.0)]
+        );
+        assert!(mlp.predict(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn layer_weights_svg() {
+        let mlp = MLP::seeded(4, &[3, 1], 0);
+        mlp.layers[0].neurons[1].w[2].set_data(-5.0);
+
+        let svg = mlp.draw_layer_weights(0).unwrap();
+        assert_eq!(svg.matches("<rect").count(), 12);
+        assert!(svg.contains("fill=\"rgb(0,0,255)\"><title>neuron 1, input 2: -5</title>"));
+        assert_eq!(
+            mlp.draw_layer_weights(1).unwrap().matches("<rect").count(),
+            3
+        );
+        assert!(mlp.draw_layer_weights(2).is_none());
+    }
+
+    #[test]
+    fn describe() {
+        let mut rng = rand::thread_rng();
+        let mlp = MLP::new(3, &[4, 4, 1], &mut rng);
+
+        let info = mlp.describe().with_seed(42).with_metric("loss", 0.25);
+
+        assert_eq!(info.layers, vec![4, 4, 1]);
+        assert_eq!(info.activations, vec!["tanh", "tanh", "linear"]);
+        assert_eq!(info.parameters, 41);
+        assert_eq!(
+            info.to_json(),
+            "{\"nin\":3,\"layers\":[4,4,1],\"activations\":[\"tanh\",\"tanh\",\"linear\"],\
+             \"parameters\":41,\"init\":\"uniform(-1, 1) weights, zero biases\",\"seed\":42,\
+             \"metrics\":{\"loss\":0.25}}"
+        );
+
+        let mut seeded = MLP::seeded(2, &[3, 3], 7);
+        assert_eq!(seeded.describe().seed, Some(7));
+        seeded.layers_mut()[0].init_orthogonal(1.0, &mut rng);
+        seeded.layers_mut()[1].init_identity();
+        assert_eq!(
+            seeded.describe().init,
+            "layer 0: orthogonal(gain 1) weights, zero biases; \
+             layer 1: identity weights, zero biases"
+        );
+
+        let json = seeded
+            .describe()
+            .with_metric("diverged", f32::NAN)
+            .with_metric("grad\u{1b}\"norm\"", f32::INFINITY)
+            .to_json();
+        assert!(json.contains("\"seed\":7"));
+        assert!(json.ends_with("\"metrics\":{\"diverged\":null,\"grad\\u001b\\\"norm\\\"\":null}}"));
+    }
+
+    #[test]
+    fn positive_param() {
+        let rate = PositiveParam::new(0.5, "rate");
+        let mut sgd = SGD::new(0.02);
+
+        for _ in 0..200 {
+            let loss = (rate.value() - 4.0).powi(2);
+            loss.backward();
+            sgd.step(&rate.parameters());
+
+            assert!(rate.get() > 0.0);
+        }
+
+        assert!((rate.get() - 4.0).abs() < 1e-3);
+        assert_eq!(rate.value().data(), rate.get());
+    }
+
+    #[test]
+    fn orthogonal_init() {
+        let mut rng = rand::thread_rng();
+
+        for (nin, nout) in [(5, 3), (3, 5), (4, 4), (32, 32)] {
+            let mut layer = Layer::new(nin, nout, true, &mut rng);
+            layer.init_orthogonal(1.0, &mut rng);
+
+            let w: Vec<Vec<f32>> = layer
+                .neurons
+                .iter()
+                .map(|n| n.w.iter().map(|w| w.data()).collect())
+                .collect();
+
+            // The smaller Gram matrix, W Wᵀ or Wᵀ W, is the identity.
+            let (outer, inner) = (nout.min(nin), nout.max(nin));
+            for a in 0..outer {
+                for b in 0..outer {
+                    let dot: f32 = (0..inner)
+                        .map(|k| {
+                            if nout <= nin {
+                                w[a][k] * w[b][k]
+                            } else {
+                                w[k][a] * w[k][b]
+                            }
+                        })
+                        .sum();
+                    assert!((dot - if a == b { 1.0 } else { 0.0 }).abs() < 1e-5);
+                }
+            }
+        }
+
+        let mut layer = Layer::new(2, 2, false, &mut rng);
+        layer.init_identity();
+        assert_eq!(layer.predict(&[0.5, -2.0]).unwrap(), vec![0.5, -2.0]);
+    }
+
+    #[test]
+    fn shape_errors() {
+        let mut mlp = MLP::seeded(3, &[4, 2], 0);
+
+        let err = mlp.predict(&[1.0, 2.0]).unwrap_err();
+        assert_eq!(
+            err,
+            NeuronError::InputLenErr {
+                expected: 3,
+                actual: 2,
+                layer: Some(0),
+                neuron: Some(0),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "layer 0, neuron 0: expected 3 inputs, got 2"
+        );
+
+        let x = vec![Scalar::new(1.0, "x")];
+        assert!(matches!(
+            mlp.output(x),
+            Err(NeuronError::InputLenErr { layer: Some(0), .. })
+        ));
+
+        let mut session = InferenceSession::new(&mlp);
+        assert_eq!(
+            session
+                .run(&[0.0; 3], &mut [0.0; 1])
+                .unwrap_err()
+                .to_string(),
+            "expected an output buffer of 2, got 1"
+        );
+    }
+
+    #[test]
+    fn construction_shape() {
+        let mlp = MLP::seeded(3, &[4, 2], 0);
+
+        assert_eq!((mlp.input_dim(), mlp.output_dim()), (3, 2));
+        assert!(MLP::check_shape(3, &[4, 2]).is_ok());
+        assert_eq!(
+            MLP::check_shape(3, &[4, 0, 2]),
+            Err(NeuronError::ShapeErr {
+                nin: 3,
+                layers: vec![4, 0, 2]
+            })
+        );
+        assert!(MLP::check_shape(0, &[1]).is_err());
+        assert!(MLP::check_shape(2, &[]).is_err());
+        assert!(std::panic::catch_unwind(|| MLP::seeded(2, &[], 0)).is_err());
+    }
+
+    #[test]
+    fn typed_mlp() {
+        let mut mlp = TypedMLP::<3, 2>::seeded(&[4], 5);
+        let x = [1.0, -0.5, 2.0];
+        let y = mlp.output(&x.map(|d| Scalar::new(d, "x")));
+
+        for (y, p) in y.iter().zip(mlp.predict(&x)) {
+            assert!((y.data() - p).abs() < 1e-6);
+        }
+        assert_eq!(
+            mlp.predict(&x).to_vec(),
+            MLP::seeded(3, &[4, 2], 5).predict(&x).unwrap()
+        );
+        assert_eq!(mlp.parameters().len(), 4 * 4 + 2 * 5);
+
+        let mut rng = rand::thread_rng();
+        let mut layer = TypedLayer::<2, 3>::new(true, &mut rng);
+        assert_eq!(
+            layer
+                .output(&[Scalar::new(1.0, ""), Scalar::new(2.0, "")])
+                .len(),
+            3
+        );
+
+        assert!(TypedMLP::<3, 2>::from_mlp(MLP::seeded(3, &[2], 0)).is_ok());
+        let err = TypedMLP::<3, 1>::from_mlp(MLP::seeded(3, &[2], 0))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            NeuronError::DimErr {
+                expected: (3, 1),
+                actual: (3, 2)
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "expected a model from 3 inputs to 1 outputs, got 3 to 2"
+        );
+    }
+
+    #[test]
+    fn custom_activation() {
+        let mut rng = rand::thread_rng();
+        let clip = |z: &Scalar<f32>| (z.clone() * (1.0 / 3.0)).tanh() * 3.0;
+        let mut layer = Layer::new_with_activation(2, 3, clip, &mut rng);
+
+        let x = vec![Scalar::new(4.0, "x0"), Scalar::new(-5.0, "x1")];
+        let y = layer.output(x.clone()).unwrap();
+        let z = dot(&x, &layer.neurons[0].w).unwrap();
+
+        assert!((y[0].data() - (z.data() / 3.0).tanh() * 3.0).abs() < 1e-6);
+        assert!((layer.predict(&[4.0, -5.0]).unwrap()[0] - y[0].data()).abs() < 1e-6);
+
+        y[0].backward();
+        let dz = 1.0 - (z.data() / 3.0).tanh().powi(2);
+        assert!((x[0].grad() - dz * layer.neurons[0].w[0].data()).abs() < 1e-5);
+
+        let mut neuron = Neuron::new_with_activation(1, |z| z.powi(2), &mut rng);
+        let w = neuron.w[0].data();
+        assert!(
+            (neuron.output(vec![Scalar::new(2.0, "")]).unwrap().data() - (2.0 * w).powi(2)).abs()
+                < 1e-6
+        );
+    }
+
+    #[test]
+    fn elu_activation() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut neuron = Neuron::new(2, Activation::Elu(1.0), &mut rng);
+        let (w0, w1) = (neuron.w[0].data(), neuron.w[1].data());
+
+        let z = -2.0 * w0.abs() - 2.0 * w1.abs();
+        let x = [-2.0 * w0.signum(), -2.0 * w1.signum()];
+        let y = neuron
+            .output(x.iter().map(|d| Scalar::new(*d, "")).collect())
+            .unwrap();
+        assert!((y.data() - z.exp_m1()).abs() < 1e-6);
+        assert!((neuron.predict(&x).unwrap() - y.data()).abs() < 1e-6);
+
+        let layer = Layer::new(2, 3, Activation::Elu(0.5), &mut rng);
+        assert!(layer.neurons.iter().all(|n| n.activation.name() == "elu"));
+        assert_eq!(
+            Layer::new(2, 1, true, &mut rng).neurons[0]
+                .activation
+                .name(),
+            "tanh"
+        );
+    }
+
+    #[test]
+    fn embedding() {
+        let mut rng = rand::thread_rng();
+        let emb = Embedding::new(5, 3, &mut rng);
+
+        assert_eq!((emb.vocab_size(), emb.dim()), (5, 3));
+        assert_eq!(emb.parameters().len(), 15);
+
+        let row = emb.lookup(2).unwrap();
+        sum(&row).backward();
+        let grads: Vec<f32> = emb.parameters().iter().map(|p| p.grad()).collect();
+        assert_eq!(grads.iter().sum::<f32>(), 3.0);
+        assert_eq!(&grads[6..9], &[1.0; 3]);
+
+        assert_eq!(
+            emb.lookup(5).unwrap_err(),
+            NeuronError::TokenErr { token: 5, vocab: 5 }
+        );
+    }
+
+    #[test]
+    fn inference_session() {
+        let mlp = MLP::seeded(3, &[5, 4, 2], 1);
+        let mut session = InferenceSession::new(&mlp);
+        let mut output = [0.0; 2];
+
+        for x in [[0.5, -1.0, 2.0], [0.0, 0.1, -0.3]] {
+            session.run(&x, &mut output).unwrap();
+            assert_eq!(output.to_vec(), mlp.predict(&x).unwrap());
+        }
+
+        assert!(session.run(&[1.0, 2.0], &mut output).is_err());
+        assert!(session.run(&[1.0, 2.0, 3.0], &mut [0.0; 3]).is_err());
+
+        mlp.parameters().apply(|p| p.set_data(p.data() * 2.0));
+        session.refresh(&mlp);
+        session.run(&[0.5, -1.0, 2.0], &mut output).unwrap();
+        assert_eq!(output.to_vec(), mlp.predict(&[0.5, -1.0, 2.0]).unwrap());
+    }
+
+    #[test]
+    fn flops() {
+        let mut rng = rand::thread_rng();
+        let mut mlp = MLP::new(3, &[4, 2], &mut rng);
+
+        let outputs = mlp
+            .output((0..3).map(|_| Scalar::new(1.0, "")).collect())
+            .unwrap();
+        // Joining the two outputs adds one ADD node.
+        let graph = sum(&outputs).flops();
+
+        assert_eq!(graph, mlp.flops(1) + Op::ADD.flops(2));
+        assert_eq!(mlp.flops(8), mlp.flops(1) * 8);
+    }
+
+    #[test]
+    fn seeded_layers() {
+        let weights = |mlp: &MLP, layer: usize| -> Vec<f32> {
+            mlp.layers[layer]
+                .parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect()
+        };
+
+        let small = MLP::seeded(2, &[4, 1], 7);
+        let deeper = MLP::seeded(2, &[4, 3, 1], 7);
+
+        assert_eq!(weights(&small, 0), weights(&deeper, 0));
+        assert_eq!(weights(&small, 0), weights(&MLP::seeded(2, &[4, 1], 7), 0));
+        assert_ne!(weights(&small, 0), weights(&MLP::seeded(2, &[4, 1], 8), 0));
+
+        // Resizing a layer keeps the ones before it, but not the next.
+        let (narrow, wide) = (MLP::seeded(2, &[4, 3, 2], 7), MLP::seeded(2, &[4, 5, 2], 7));
+        let second = |mlp: &MLP| -> Vec<f32> {
+            mlp.layers[2].neurons[1].w[..3]
+                .iter()
+                .map(|w| w.data())
+                .collect()
+        };
+        assert_eq!(weights(&narrow, 0), weights(&wide, 0));
+        assert_ne!(second(&narrow), second(&wide));
+        assert_eq!(
+            deeper.describe().activations,
+            vec!["tanh", "tanh", "linear"]
+        );
+
+        let mut a = rng_stream(1, "layer0");
+        let mut b = rng_stream(1, "layer1");
+        assert_ne!(a.gen::<u64>(), b.gen::<u64>());
+    }
 }
@@ -0,0 +1,1007 @@
+use super::{
+    engine::{gaussian, jacobian, Scalar},
+    nn::{NeuronError, MLP},
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+pub trait Optimizer {
+    /// Updates `params` from their gradients. Derivative-free optimizers,
+    /// which have to re-evaluate the loss, panic here and need
+    /// `step_with_closure` instead.
+    fn step(&mut self, params: &[Scalar<f32>]);
+
+    /// Step for algorithms that re-evaluate the loss, such as line searches.
+    /// Gradients for the current parameters are already set; `closure`
+    /// recomputes the loss at whatever values the parameters hold and runs
+    /// its backward pass. The default takes a plain `step`.
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        _closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        self.step(params);
+    }
+
+    /// Updates the learning rate; optimizers without one ignore it.
+    fn set_lr(&mut self, _lr: f32) {}
+
+    /// Current learning rate, if the optimizer has one.
+    fn lr(&self) -> Option<f32> {
+        None
+    }
+
+    /// Internal state (learning rate, step counters...) to checkpoint, as
+    /// numbers that `load_state` accepts back.
+    fn state(&self) -> Vec<f64> {
+        vec![]
+    }
+
+    /// Restores a state produced by `state`, returning false if it doesn't fit.
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        state.is_empty()
+    }
+}
+
+impl<O: Optimizer + ?Sized> Optimizer for Box<O> {
+    fn step(&mut self, params: &[Scalar<f32>]) {
+        (**self).step(params);
+    }
+
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        (**self).step_with_closure(params, closure);
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        (**self).set_lr(lr);
+    }
+
+    fn lr(&self) -> Option<f32> {
+        (**self).lr()
+    }
+
+    fn state(&self) -> Vec<f64> {
+        (**self).state()
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        (**self).load_state(state)
+    }
+}
+
+pub struct SGD {
+    lr: f32,
+}
+
+impl SGD {
+    pub fn new(lr: f32) -> Self {
+        Self { lr }
+    }
+}
+
+impl Optimizer for SGD {
+    fn step(&mut self, params: &[Scalar<f32>]) {
+        for p in params {
+            p.set_data(p.data() - self.lr * p.grad());
+        }
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        self.lr = lr;
+    }
+
+    fn lr(&self) -> Option<f32> {
+        Some(self.lr)
+    }
+
+    fn state(&self) -> Vec<f64> {
+        vec![self.lr as f64]
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        match state {
+            [lr] => {
+                self.lr = *lr as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Gradient descent picking each step size by backtracking line search:
+/// starting from `lr`, the step shrinks by `shrink` until the re-evaluated
+/// loss drops by at least `c * step * |grad|²` (the Armijo condition). When
+/// `max_evals` trials all fail, the parameters stay where they were. Without
+/// a closure, `step` is plain SGD at `lr`.
+pub struct LineSearch {
+    lr: f32,
+    pub shrink: f32,
+    pub c: f32,
+    pub max_evals: usize,
+    last_step: Option<f32>,
+}
+
+impl LineSearch {
+    pub fn new(lr: f32) -> Self {
+        Self {
+            lr,
+            shrink: 0.5,
+            c: 1e-4,
+            max_evals: 20,
+            last_step: None,
+        }
+    }
+
+    /// Step size accepted by the latest line search, `None` if it failed.
+    pub fn last_step(&self) -> Option<f32> {
+        self.last_step
+    }
+}
+
+impl Optimizer for LineSearch {
+    fn step(&mut self, params: &[Scalar<f32>]) {
+        SGD::new(self.lr).step(params);
+    }
+
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        let start: Vec<f32> = params.iter().map(|p| p.data()).collect();
+        let grads: Vec<f32> = params.iter().map(|p| p.grad()).collect();
+        let slope: f32 = grads.iter().map(|g| g * g).sum();
+        let before = closure().data();
+        let mut step = self.lr;
+
+        self.last_step = None;
+
+        for _ in 0..self.max_evals {
+            for (p, (d, g)) in params.iter().zip(start.iter().zip(&grads)) {
+                p.set_data(d - step * g);
+            }
+
+            if closure().data() <= before - self.c * step * slope {
+                self.last_step = Some(step);
+                return;
+            }
+
+            step *= self.shrink;
+        }
+
+        for (p, d) in params.iter().zip(start) {
+            p.set_data(d);
+        }
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        self.lr = lr;
+    }
+
+    fn lr(&self) -> Option<f32> {
+        Some(self.lr)
+    }
+
+    fn state(&self) -> Vec<f64> {
+        vec![self.lr as f64]
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        match state {
+            [lr] => {
+                self.lr = *lr as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Derivative-free: each step sweeps the parameters in order, moving each by
+/// `+delta` or `-delta` when that lowers the loss. A sweep without any
+/// improvement halves `delta`. Needs `step_with_closure`; `step` panics.
+pub struct CoordinateDescent {
+    delta: f32,
+}
+
+impl CoordinateDescent {
+    pub fn new(delta: f32) -> Self {
+        Self { delta }
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+}
+
+impl Optimizer for CoordinateDescent {
+    fn step(&mut self, _params: &[Scalar<f32>]) {
+        panic!("CoordinateDescent needs step_with_closure");
+    }
+
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        let mut best = closure().data();
+        let mut improved = false;
+
+        for p in params {
+            let start = p.data();
+
+            for candidate in [start + self.delta, start - self.delta] {
+                p.set_data(candidate);
+                let loss = closure().data();
+
+                if loss < best {
+                    best = loss;
+                    improved = true;
+                    break;
+                }
+
+                p.set_data(start);
+            }
+        }
+
+        if !improved {
+            self.delta /= 2.0;
+        }
+    }
+
+    fn state(&self) -> Vec<f64> {
+        vec![self.delta as f64]
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        match state {
+            [delta] => {
+                self.delta = *delta as f32;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Derivative-free: each step draws `samples` Gaussian perturbations of
+/// standard deviation `radius` around the parameters and moves to the best
+/// one if it lowers the loss. Needs `step_with_closure`; `step` panics.
+pub struct RandomSearch {
+    radius: f32,
+    samples: usize,
+    seed: u64,
+    /// Gaussian samples drawn so far, replayed to restore the RNG.
+    draws: u64,
+    rng: StdRng,
+}
+
+impl RandomSearch {
+    pub fn new(radius: f32, samples: usize, seed: u64) -> Self {
+        Self {
+            radius,
+            samples,
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Optimizer for RandomSearch {
+    fn step(&mut self, _params: &[Scalar<f32>]) {
+        panic!("RandomSearch needs step_with_closure");
+    }
+
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        let start: Vec<f32> = params.iter().map(|p| p.data()).collect();
+        let mut best = (closure().data(), start.clone());
+
+        for _ in 0..self.samples {
+            let candidate: Vec<f32> = start
+                .iter()
+                .map(|d| d + self.radius * gaussian(&mut self.rng))
+                .collect();
+            self.draws += params.len() as u64;
+
+            set_all(params, &candidate);
+            let loss = closure().data();
+
+            if loss < best.0 {
+                best = (loss, candidate);
+            }
+        }
+
+        set_all(params, &best.1);
+    }
+
+    fn state(&self) -> Vec<f64> {
+        vec![self.draws as f64]
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        match state {
+            [draws] => {
+                self.draws = *draws as u64;
+                self.rng = replay(self.seed, self.draws);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The (1+1) evolution strategy: each step tries one Gaussian perturbation of
+/// standard deviation `sigma` and keeps it if the loss doesn't get worse.
+/// `sigma` adapts by the 1/5 success rule, growing on success and shrinking
+/// on failure so about a fifth of the trials succeed. Needs
+/// `step_with_closure`; `step` panics.
+pub struct OnePlusOneES {
+    sigma: f32,
+    seed: u64,
+    /// Gaussian samples drawn so far, replayed to restore the RNG.
+    draws: u64,
+    rng: StdRng,
+}
+
+impl OnePlusOneES {
+    pub fn new(sigma: f32, seed: u64) -> Self {
+        Self {
+            sigma,
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn sigma(&self) -> f32 {
+        self.sigma
+    }
+}
+
+impl Optimizer for OnePlusOneES {
+    fn step(&mut self, _params: &[Scalar<f32>]) {
+        panic!("OnePlusOneES needs step_with_closure");
+    }
+
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        let start: Vec<f32> = params.iter().map(|p| p.data()).collect();
+        let before = closure().data();
+        let candidate: Vec<f32> = start
+            .iter()
+            .map(|d| d + self.sigma * gaussian(&mut self.rng))
+            .collect();
+        self.draws += params.len() as u64;
+
+        set_all(params, &candidate);
+
+        if closure().data() <= before {
+            self.sigma *= 1.5;
+        } else {
+            set_all(params, &start);
+            self.sigma *= 1.5f32.powf(-0.25);
+        }
+    }
+
+    fn state(&self) -> Vec<f64> {
+        vec![self.sigma as f64, self.draws as f64]
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        match state {
+            [sigma, draws] => {
+                self.sigma = *sigma as f32;
+                self.draws = *draws as u64;
+                self.rng = replay(self.seed, self.draws);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn set_all(params: &[Scalar<f32>], data: &[f32]) {
+    for (p, d) in params.iter().zip(data) {
+        p.set_data(*d);
+    }
+}
+
+/// RNG seeded with `seed` after `draws` Gaussian samples.
+fn replay(seed: u64, draws: u64) -> StdRng {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for _ in 0..draws {
+        gaussian(&mut rng);
+    }
+
+    rng
+}
+
+/// Least-squares solver: each step linearizes the residuals around the
+/// current parameters and jumps to the minimum of the linear model, solving
+/// `(JᵀJ + damping·I) δ = -Jᵀr` with the residuals' Jacobian `J`. Converges
+/// in a handful of iterations on small regression problems; the damping
+/// keeps the system solvable when `JᵀJ` is singular. Not an `Optimizer`, as
+/// it needs every residual rather than their summed loss.
+pub struct GaussNewton {
+    pub damping: f32,
+}
+
+impl GaussNewton {
+    pub fn new(damping: f32) -> Self {
+        Self { damping }
+    }
+
+    /// Moves `params` by one Gauss-Newton step for `residuals`, scalars
+    /// built from them. Returns false, leaving them unchanged, when the
+    /// linear system can't be solved.
+    pub fn step(&self, params: &[Scalar<f32>], residuals: &[Scalar<f32>]) -> bool {
+        self.solve_step(params, &jacobian(residuals, params), residuals)
+    }
+
+    /// Fits the model's outputs to the targets in squared error for up to
+    /// `iterations` steps, building one Jacobian per sample. Returns the
+    /// summed squared error before each step taken.
+    pub fn fit(
+        &self,
+        model: &mut MLP,
+        xs: &[Vec<f32>],
+        ys: &[Vec<f32>],
+        iterations: usize,
+    ) -> Result<Vec<f32>, NeuronError> {
+        if xs.len() != ys.len() {
+            return Err(NeuronError::BatchLenErr {
+                inputs: xs.len(),
+                targets: ys.len(),
+            });
+        }
+
+        let params = model.parameters();
+        let mut losses = vec![];
+
+        for _ in 0..iterations {
+            let (mut rows, mut residuals) = (vec![], vec![]);
+
+            for (sample, (x, y)) in xs.iter().zip(ys).enumerate() {
+                let ypred = model.output(x.iter().map(|d| Scalar::new(*d, "")).collect())?;
+
+                if ypred.len() != y.len() {
+                    return Err(NeuronError::TargetLenErr {
+                        sample,
+                        expected: ypred.len(),
+                        actual: y.len(),
+                    });
+                }
+
+                rows.extend(jacobian(&ypred, &params));
+                residuals.extend(ypred.into_iter().zip(y).map(|(yp, yt)| yp - *yt));
+            }
+
+            losses.push(residuals.iter().map(|r| r.data().powi(2)).sum());
+
+            if !self.solve_step(&params, &rows, &residuals) {
+                break;
+            }
+        }
+
+        Ok(losses)
+    }
+
+    fn solve_step(
+        &self,
+        params: &[Scalar<f32>],
+        j: &[Vec<f32>],
+        residuals: &[Scalar<f32>],
+    ) -> bool {
+        let n = params.len();
+        let mut a = vec![vec![0.0f64; n]; n];
+        let mut b = vec![0.0f64; n];
+
+        for (row, r) in j.iter().zip(residuals) {
+            for (k, jk) in row.iter().enumerate() {
+                b[k] -= *jk as f64 * r.data() as f64;
+
+                for (l, jl) in row.iter().enumerate() {
+                    a[k][l] += *jk as f64 * *jl as f64;
+                }
+            }
+        }
+
+        for (k, row) in a.iter_mut().enumerate() {
+            row[k] += self.damping as f64;
+        }
+
+        let Some(delta) = solve(a, b) else {
+            return false;
+        };
+
+        for (p, d) in params.iter().zip(delta) {
+            p.set_data(p.data() + d as f32);
+        }
+
+        true
+    }
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting, `None`
+/// when `a` is singular.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot = (col..n).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+
+            for (x, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+
+    for row in (0..n).rev() {
+        let rest: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+
+    Some(x)
+}
+
+/// Wraps an optimizer, adding Gaussian noise to every gradient before its
+/// step. The variance anneals as `eta / (1 + t)^gamma` over steps `t`.
+pub struct GradientNoise<O: Optimizer> {
+    inner: O,
+    eta: f32,
+    gamma: f32,
+    t: u32,
+    seed: u64,
+    /// Gaussian samples drawn so far, replayed to restore the RNG.
+    draws: u64,
+    rng: StdRng,
+}
+
+impl<O: Optimizer> GradientNoise<O> {
+    pub fn new(inner: O, eta: f32, gamma: f32, seed: u64) -> Self {
+        Self {
+            inner,
+            eta,
+            gamma,
+            t: 0,
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Noise variance applied at the next step.
+    pub fn variance(&self) -> f32 {
+        self.eta / (1.0 + self.t as f32).powf(self.gamma)
+    }
+
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+
+    fn add_noise(&mut self, params: &[Scalar<f32>]) {
+        let std = self.variance().sqrt();
+
+        for p in params {
+            p.set_grad(p.grad() + std * gaussian(&mut self.rng));
+        }
+
+        self.draws += params.len() as u64;
+        self.t += 1;
+    }
+}
+
+impl<O: Optimizer> Optimizer for GradientNoise<O> {
+    fn step(&mut self, params: &[Scalar<f32>]) {
+        self.add_noise(params);
+        self.inner.step(params);
+    }
+
+    /// Only the gradients passed in are noisy; those the inner optimizer
+    /// gets from re-evaluating `closure` are exact.
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        self.add_noise(params);
+        self.inner.step_with_closure(params, closure);
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        self.inner.set_lr(lr);
+    }
+
+    fn lr(&self) -> Option<f32> {
+        self.inner.lr()
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = vec![self.t as f64, self.draws as f64];
+        state.extend(self.inner.state());
+        state
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        match state {
+            [t, draws, inner @ ..] if self.inner.load_state(inner) => {
+                self.t = *t as u32;
+                self.draws = *draws as u64;
+                self.rng = replay(self.seed, self.draws);
+
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A hyperparameter value as a function of the training step, used for
+/// learning rates as well as module settings like the dropout probability.
+pub trait Scheduler {
+    fn value(&self, step: usize) -> f32;
+}
+
+impl<F: Fn(usize) -> f32> Scheduler for F {
+    fn value(&self, step: usize) -> f32 {
+        self(step)
+    }
+}
+
+/// Moves linearly from `start` to `end` over `steps`, then stays at `end`.
+pub struct Linear {
+    pub start: f32,
+    pub end: f32,
+    pub steps: usize,
+}
+
+impl Scheduler for Linear {
+    fn value(&self, step: usize) -> f32 {
+        if step >= self.steps {
+            return self.end;
+        }
+
+        self.start + (self.end - self.start) * step as f32 / self.steps as f32
+    }
+}
+
+/// Multiplies `initial` by `gamma` every step.
+pub struct Exponential {
+    pub initial: f32,
+    pub gamma: f32,
+}
+
+impl Scheduler for Exponential {
+    fn value(&self, step: usize) -> f32 {
+        self.initial * self.gamma.powi(step as i32)
+    }
+}
+
+/// Wraps an optimizer, setting its learning rate from `schedule` before each step.
+pub struct Scheduled<O: Optimizer, S: Scheduler> {
+    inner: O,
+    schedule: S,
+    t: usize,
+}
+
+impl<O: Optimizer, S: Scheduler> Scheduled<O, S> {
+    pub fn new(inner: O, schedule: S) -> Self {
+        Self {
+            inner,
+            schedule,
+            t: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+}
+
+impl<O: Optimizer, S: Scheduler> Optimizer for Scheduled<O, S> {
+    fn step(&mut self, params: &[Scalar<f32>]) {
+        self.inner.set_lr(self.schedule.value(self.t));
+        self.t += 1;
+        self.inner.step(params);
+    }
+
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        self.inner.set_lr(self.schedule.value(self.t));
+        self.t += 1;
+        self.inner.step_with_closure(params, closure);
+    }
+
+    fn lr(&self) -> Option<f32> {
+        self.inner.lr()
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = vec![self.t as f64];
+        state.extend(self.inner.state());
+        state
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        match state {
+            [t, inner @ ..] if self.inner.load_state(inner) => {
+                self.t = *t as usize;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A projection applied to a group of parameters after each optimizer step.
+pub trait Constraint {
+    fn project(&self, params: &[Scalar<f32>]);
+}
+
+impl<F: Fn(&[Scalar<f32>])> Constraint for F {
+    fn project(&self, params: &[Scalar<f32>]) {
+        self(params)
+    }
+}
+
+/// Clamps every parameter into `[min, max]`.
+pub struct Clamp {
+    min: f32,
+    max: f32,
+}
+
+impl Clamp {
+    /// Checks the bounds here, so a bad range fails at construction rather
+    /// than in the middle of training.
+    ///
+    /// # Panics
+    /// If `min > max` or either bound is NaN.
+    pub fn new(min: f32, max: f32) -> Self {
+        assert!(min <= max, "Clamp needs min <= max");
+
+        Self { min, max }
+    }
+}
+
+impl Constraint for Clamp {
+    fn project(&self, params: &[Scalar<f32>]) {
+        for p in params {
+            p.set_data(p.data().clamp(self.min, self.max));
+        }
+    }
+}
+
+pub struct NonNegative;
+
+impl Constraint for NonNegative {
+    fn project(&self, params: &[Scalar<f32>]) {
+        for p in params {
+            p.set_data(p.data().max(0.0));
+        }
+    }
+}
+
+/// Rescales the group, as one vector, to unit L2 norm. A zero vector is left as is.
+pub struct UnitNorm;
+
+impl Constraint for UnitNorm {
+    fn project(&self, params: &[Scalar<f32>]) {
+        let norm = params.iter().map(|p| p.data().powi(2)).sum::<f32>().sqrt();
+
+        if norm > 0.0 {
+            for p in params {
+                p.set_data(p.data() / norm);
+            }
+        }
+    }
+}
+
+type ConstrainedGroup = (Vec<Scalar<f32>>, Box<dyn Constraint>);
+
+/// Wraps an optimizer, projecting parameter groups onto their constraints
+/// after every step.
+pub struct Constrained<O: Optimizer> {
+    inner: O,
+    groups: Vec<ConstrainedGroup>,
+}
+
+impl<O: Optimizer> Constrained<O> {
+    pub fn new(inner: O) -> Self {
+        Self {
+            inner,
+            groups: vec![],
+        }
+    }
+
+    /// Adds a group of parameters kept within `constraint`.
+    pub fn with(mut self, params: &[Scalar<f32>], constraint: impl Constraint + 'static) -> Self {
+        self.groups.push((params.to_vec(), Box::new(constraint)));
+        self
+    }
+
+    pub fn into_inner(self) -> O {
+        self.inner
+    }
+
+    fn project(&self) {
+        for (group, constraint) in &self.groups {
+            constraint.project(group);
+        }
+    }
+}
+
+impl<O: Optimizer> Optimizer for Constrained<O> {
+    fn step(&mut self, params: &[Scalar<f32>]) {
+        self.inner.step(params);
+        self.project();
+    }
+
+    fn step_with_closure(
+        &mut self,
+        params: &[Scalar<f32>],
+        closure: &mut dyn FnMut() -> Scalar<f32>,
+    ) {
+        self.inner.step_with_closure(params, closure);
+        self.project();
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        self.inner.set_lr(lr);
+    }
+
+    fn lr(&self) -> Option<f32> {
+        self.inner.lr()
+    }
+
+    fn state(&self) -> Vec<f64> {
+        self.inner.state()
+    }
+
+    fn load_state(&mut self, state: &[f64]) -> bool {
+        self.inner.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_noise() {
+        let run = |seed| {
+            let p = Scalar::new(1.0, "p");
+            let params = vec![p.clone()];
+            let mut opt = GradientNoise::new(SGD::new(0.1), 0.3, 0.55, seed);
+
+            for _ in 0..3 {
+                p.set_grad(0.5);
+                opt.step(&params);
+            }
+
+            (p.data(), opt.variance())
+        };
+
+        let (data, variance) = run(7);
+
+        assert_eq!(run(7).0, data);
+        assert_ne!(run(8).0, data);
+        assert_ne!(data, 1.0 - 3.0 * 0.1 * 0.5);
+        assert!((variance - 0.3 / 4.0f32.powf(0.55)).abs() < 1e-7);
+    }
+
+    #[test]
+    fn gauss_newton() {
+        // A linear model is solved exactly in one step.
+        let xs: Vec<Vec<f32>> = (0..5).map(|i| vec![i as f32 * 0.5]).collect();
+        let ys: Vec<Vec<f32>> = xs.iter().map(|x| vec![2.0 * x[0] + 1.0]).collect();
+        let mut linear = MLP::seeded(1, &[1], 0);
+
+        let losses = GaussNewton::new(0.0).fit(&mut linear, &xs, &ys, 2).unwrap();
+        assert!(losses[0] > 1.0);
+        assert!(losses[1] < 1e-8);
+        assert!((linear.predict(&[3.0]).unwrap()[0] - 7.0).abs() < 1e-4);
+
+        let ys: Vec<Vec<f32>> = xs.iter().map(|x| vec![(x[0] - 1.0).tanh()]).collect();
+        let mut mlp = MLP::seeded(1, &[3, 1], 0);
+        let losses = GaussNewton::new(1e-2).fit(&mut mlp, &xs, &ys, 3).unwrap();
+        assert!(losses[2] < 1e-2 * losses[0]);
+
+        // Duplicated parameters make JᵀJ singular without damping.
+        let p = Scalar::new(1.0, "p");
+        let r = p.clone() + p.clone() - 4.0;
+        assert!(!GaussNewton::new(0.0).step(&[p.clone(), p.clone()], &[r]));
+        assert_eq!(p.data(), 1.0);
+
+        assert!(GaussNewton::new(0.0)
+            .fit(&mut mlp, &xs, &ys[1..], 1)
+            .is_err());
+    }
+
+    #[test]
+    fn schedules() {
+        let linear = Linear {
+            start: 1.0,
+            end: 0.0,
+            steps: 4,
+        };
+        assert_eq!(linear.value(1), 0.75);
+        assert_eq!(linear.value(9), 0.0);
+
+        let p = Scalar::new(0.0, "p");
+        let params = vec![p.clone()];
+        let mut opt = Scheduled::new(
+            SGD::new(0.0),
+            Exponential {
+                initial: 1.0,
+                gamma: 0.5,
+            },
+        );
+
+        for _ in 0..3 {
+            p.set_grad(-1.0);
+            opt.step(&params);
+        }
+
+        assert_eq!(p.data(), 1.75);
+        assert_eq!((|step| step as f32 * 2.0).value(3), 6.0);
+    }
+
+    #[test]
+    fn constraints() {
+        let w: Vec<Scalar<f32>> = [3.0, 4.0].iter().map(|d| Scalar::new(*d, "w")).collect();
+        let b = Scalar::new(0.5, "b");
+        let params = vec![w[0].clone(), w[1].clone(), b.clone()];
+
+        let mut opt = Constrained::new(SGD::new(1.0))
+            .with(&w, UnitNorm)
+            .with(&params[2..], NonNegative);
+
+        for p in &params {
+            p.set_grad(1.0);
+        }
+        opt.step(&params);
+
+        assert!((w[0].data().powi(2) + w[1].data().powi(2) - 1.0).abs() < 1e-6);
+        assert!((w[0].data() - 2.0 / 13.0f32.sqrt()).abs() < 1e-6);
+        assert_eq!(b.data(), 0.0);
+
+        let mut opt = Constrained::new(SGD::new(1.0)).with(&params, Clamp::new(-0.1, 0.1));
+        opt.step(&params);
+        assert!(params.iter().all(|p| p.data().abs() <= 0.1));
+
+        assert!(std::panic::catch_unwind(|| Clamp::new(1.0, 0.0)).is_err());
+        assert!(std::panic::catch_unwind(|| Clamp::new(f32::NAN, 0.0)).is_err());
+    }
+}
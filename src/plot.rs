@@ -0,0 +1,199 @@
+//! Line charts rendered with plotters. The output format follows the file
+//! extension: `.svg` or `.png`.
+
+use super::train::History;
+use plotters::{coord::Shift, prelude::*};
+use std::path::Path;
+use thiserror::Error;
+
+struct Chart<'a> {
+    caption: &'a str,
+    x_desc: &'a str,
+    y_desc: &'a str,
+    series: Vec<(String, Vec<(f32, f32)>)>,
+}
+
+/// Loss and every recorded metric per epoch.
+pub fn plot_history(
+    history: &History,
+    path: impl AsRef<Path>,
+    size: (u32, u32),
+) -> Result<(), PlotError> {
+    let per_epoch = |values: Vec<f32>| -> Vec<(f32, f32)> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| (i as f32, v))
+            .collect()
+    };
+
+    let mut series = vec![("loss".to_string(), per_epoch(history.losses()))];
+
+    for name in &history.metric_names {
+        series.push((name.clone(), per_epoch(history.metric(name).unwrap())));
+    }
+
+    render(
+        path.as_ref(),
+        size,
+        &Chart {
+            caption: "Training history",
+            x_desc: "epoch",
+            y_desc: "",
+            series,
+        },
+    )
+}
+
+/// A ROC curve as returned by `diagnostics::roc_curve`, with the chance diagonal.
+pub fn plot_roc(
+    points: &[(f32, f32)],
+    path: impl AsRef<Path>,
+    size: (u32, u32),
+) -> Result<(), PlotError> {
+    render(
+        path.as_ref(),
+        size,
+        &Chart {
+            caption: "ROC",
+            x_desc: "false positive rate",
+            y_desc: "true positive rate",
+            series: vec![
+                ("ROC".to_string(), points.to_vec()),
+                ("chance".to_string(), vec![(0.0, 0.0), (1.0, 1.0)]),
+            ],
+        },
+    )
+}
+
+/// Loss against learning rate from a learning rate sweep, given as
+/// `(lr, loss)` points; the rate axis is `log10(lr)`.
+pub fn plot_lr_finder(
+    points: &[(f32, f32)],
+    path: impl AsRef<Path>,
+    size: (u32, u32),
+) -> Result<(), PlotError> {
+    render(
+        path.as_ref(),
+        size,
+        &Chart {
+            caption: "Learning rate finder",
+            x_desc: "log10(lr)",
+            y_desc: "loss",
+            series: vec![(
+                "loss".to_string(),
+                points.iter().map(|(lr, l)| (lr.log10(), *l)).collect(),
+            )],
+        },
+    )
+}
+
+fn render(path: &Path, size: (u32, u32), chart: &Chart) -> Result<(), PlotError> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("svg") => draw(SVGBackend::new(path, size).into_drawing_area(), chart),
+        Some("png") => draw(BitMapBackend::new(path, size).into_drawing_area(), chart),
+        _ => Err(PlotError::FormatErr),
+    }
+}
+
+fn draw<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, chart: &Chart) -> Result<(), PlotError> {
+    let err = |e: DrawingAreaErrorKind<DB::ErrorType>| PlotError::BackendErr(e.to_string());
+    let points = || chart.series.iter().flat_map(|(_, s)| s.iter());
+
+    let (mut x0, mut x1, mut y0, mut y1) = points().fold(
+        (
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ),
+        |(x0, x1, y0, y1), (x, y)| (x0.min(*x), x1.max(*x), y0.min(*y), y1.max(*y)),
+    );
+
+    if x0 > x1 {
+        (x0, x1, y0, y1) = (0.0, 1.0, 0.0, 1.0);
+    }
+    if x0 == x1 {
+        x1 = x0 + 1.0;
+    }
+    if y0 == y1 {
+        y1 = y0 + 1.0;
+    }
+
+    root.fill(&WHITE).map_err(err)?;
+
+    let mut ctx = ChartBuilder::on(&root)
+        .caption(chart.caption, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d(x0..x1, y0..y1)
+        .map_err(err)?;
+
+    ctx.configure_mesh()
+        .x_desc(chart.x_desc)
+        .y_desc(chart.y_desc)
+        .draw()
+        .map_err(err)?;
+
+    for (i, (name, series)) in chart.series.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+
+        ctx.draw_series(LineSeries::new(
+            series.iter().cloned(),
+            color.stroke_width(2),
+        ))
+        .map_err(err)?
+        .label(name)
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    ctx.configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(err)?;
+
+    root.present().map_err(err)
+}
+
+#[derive(Error, Debug)]
+pub enum PlotError {
+    #[error("plotting backend error: {0}")]
+    BackendErr(String),
+    #[error("unsupported image format, expected .svg or .png")]
+    FormatErr,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::roc_curve;
+
+    #[test]
+    fn charts() {
+        let dir = std::env::temp_dir();
+        let history = History {
+            metric_names: vec![],
+            epochs: vec![],
+        };
+        let roc = roc_curve(&[0.9, 0.4, 0.6], &[true, false, true]);
+
+        let svg = dir.join(format!("micrograd-roc-{}.svg", std::process::id()));
+        plot_roc(&roc, &svg, (320, 240)).unwrap();
+        assert!(std::fs::read_to_string(&svg).unwrap().contains("<svg"));
+
+        let png = dir.join(format!("micrograd-lr-{}.png", std::process::id()));
+        plot_lr_finder(&[(1e-3, 1.0), (1e-2, 0.5), (1e-1, 2.0)], &png, (320, 240)).unwrap();
+        assert!(std::fs::metadata(&png).unwrap().len() > 0);
+
+        plot_history(&history, &svg, (320, 240)).unwrap();
+        assert!(matches!(
+            plot_history(&history, dir.join("history.jpg"), (320, 240)),
+            Err(PlotError::FormatErr)
+        ));
+
+        std::fs::remove_file(svg).unwrap();
+        std::fs::remove_file(png).unwrap();
+    }
+}
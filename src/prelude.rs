@@ -0,0 +1,27 @@
+//! The commonly used types and functions, importable at once with
+//! `use micrograd::prelude::*;`. `engine::std` is left out so the glob
+//! doesn't shadow the standard library.
+
+pub use crate::{
+    engine::{
+        argmax, argmin, backward_multi, checkpoint, clip_values, cross_entropy, dot, grad,
+        interp1d, jacobian, log_softmax, mean, normalize, sample_normal, select, softmax, sum,
+        topk, variance, BackwardOptions, CustomOp, EngineError, Float, NumAssignOps,
+        ParallelOptions, Scalar, Zero,
+    },
+    loss::{entropy, kl_div},
+    nn::{
+        Activation, Dropout, Embedding, InferenceSession, Layer, Module, Neuron, NeuronError,
+        Parameters, PositiveParam, TypedLayer, TypedMLP, MLP,
+    },
+    optim::{
+        Clamp, Constrained, Constraint, CoordinateDescent, Exponential, GaussNewton, GradientNoise,
+        LineSearch, Linear, NonNegative, OnePlusOneES, Optimizer, RandomSearch, Scheduled,
+        Scheduler, UnitNorm, SGD,
+    },
+    rl::{discounted_returns, reinforce_loss},
+    sample::{multinomial, with_temperature},
+    text::{bigrams, Vocab},
+    track::Run,
+    train::{fit_temperature, nll, Callback, CheckpointError, History, Loss, StaticGraph, Trainer},
+};
@@ -0,0 +1,71 @@
+//! Policy-gradient utilities, where gradients reach the model through the
+//! log-probabilities of the actions it took rather than supervised targets.
+
+use super::engine::{mean, EngineError, Scalar};
+
+/// REINFORCE surrogate loss `-mean(log π(a_t) · G_t)`. The returns (or
+/// advantages, with a baseline subtracted) are constants, so descending this
+/// loss raises the log-probability of actions in proportion to their return.
+/// Fails unless there is one return per log-probability.
+pub fn reinforce_loss(
+    log_probs: &[Scalar<f32>],
+    returns: &[f32],
+) -> Result<Scalar<f32>, EngineError> {
+    if log_probs.len() != returns.len() {
+        return Err(EngineError::InputLenErr);
+    }
+
+    let terms: Vec<Scalar<f32>> = log_probs
+        .iter()
+        .zip(returns)
+        .map(|(lp, g)| lp.clone() * *g)
+        .collect();
+
+    Ok(-mean(&terms))
+}
+
+/// Returns `G_t = r_t + gamma * G_{t+1}` of every step of an episode.
+pub fn discounted_returns(rewards: &[f32], gamma: f32) -> Vec<f32> {
+    let mut returns = vec![0.0; rewards.len()];
+    let mut g = 0.0;
+
+    for (t, r) in rewards.iter().enumerate().rev() {
+        g = r + gamma * g;
+        returns[t] = g;
+    }
+
+    returns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{log_softmax, softmax};
+
+    #[test]
+    fn policy_gradient() {
+        assert_eq!(
+            discounted_returns(&[1.0, 0.0, 2.0], 0.5),
+            vec![1.5, 1.0, 2.0]
+        );
+
+        let logits: Vec<Scalar<f32>> = [0.2, -0.4, 1.0]
+            .iter()
+            .map(|d| Scalar::new(*d, ""))
+            .collect();
+        let log_probs = log_softmax(&logits, 1.0);
+        let probs: Vec<f32> = softmax(&logits, 1.0).iter().map(|p| p.data()).collect();
+
+        // Action 1 taken twice with returns 2 and -1.
+        let loss =
+            reinforce_loss(&[log_probs[1].clone(), log_probs[1].clone()], &[2.0, -1.0]).unwrap();
+        loss.backward();
+
+        for (i, l) in logits.iter().enumerate() {
+            let onehot = if i == 1 { 1.0 } else { 0.0 };
+            assert!((l.grad() + 0.5 * (onehot - probs[i])).abs() < 1e-6);
+        }
+
+        assert!(reinforce_loss(&log_probs, &[1.0]).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! Drawing from the distributions models output, e.g. to generate text from
+//! a trained character model.
+
+use super::engine::{argmax, Scalar};
+use rand::Rng;
+
+/// Draws an index with probability proportional to `probs`, which needn't
+/// be normalized. Negative and NaN weights count as zero. Returns `None` if
+/// nothing has positive weight.
+pub fn multinomial(probs: &[Scalar<f32>], rng: &mut impl Rng) -> Option<usize> {
+    let weights: Vec<f32> = probs.iter().map(|p| p.data()).collect();
+
+    draw(&weights, rng)
+}
+
+/// Samples a class from `softmax(logits / temperature)` without building a
+/// graph. Lower temperatures favor the likeliest classes; zero or below
+/// always picks the argmax.
+pub fn with_temperature(
+    logits: &[Scalar<f32>],
+    temperature: f32,
+    rng: &mut impl Rng,
+) -> Option<usize> {
+    if temperature <= 0.0 {
+        return argmax(logits);
+    }
+
+    let logits: Vec<f32> = logits.iter().map(|l| l.data()).collect();
+    let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let weights: Vec<f32> = logits
+        .iter()
+        .map(|l| ((l - max) / temperature).exp())
+        .collect();
+
+    draw(&weights, rng)
+}
+
+fn draw(weights: &[f32], rng: &mut impl Rng) -> Option<usize> {
+    let weight = |w: f32| if w > 0.0 { w } else { 0.0 };
+    let total: f32 = weights.iter().map(|w| weight(*w)).sum();
+
+    if total <= 0.0 || !total.is_finite() {
+        return None;
+    }
+
+    let mut target = rng.gen::<f32>() * total;
+
+    for (i, w) in weights.iter().enumerate() {
+        target -= weight(*w);
+
+        if target < 0.0 {
+            return Some(i);
+        }
+    }
+
+    // Rounding can leave a sliver past the last bucket.
+    weights.iter().rposition(|w| weight(*w) > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn sampling() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let probs: Vec<Scalar<f32>> = [1.0, 0.0, 3.0, -2.0]
+            .iter()
+            .map(|p| Scalar::new(*p, ""))
+            .collect();
+
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            counts[multinomial(&probs, &mut rng).unwrap()] += 1;
+        }
+
+        assert_eq!((counts[1], counts[3]), (0, 0));
+        assert!((counts[2] as f32 / 4000.0 - 0.75).abs() < 0.03);
+        assert_eq!(multinomial(&probs[1..2], &mut rng), None);
+
+        let logits: Vec<Scalar<f32>> = [0.0, 1.0, 0.5]
+            .iter()
+            .map(|l| Scalar::new(*l, ""))
+            .collect();
+        assert_eq!(with_temperature(&logits, 0.0, &mut rng), Some(1));
+
+        let greedy = (0..200)
+            .filter(|_| with_temperature(&logits, 0.05, &mut rng) == Some(1))
+            .count();
+        let flat = (0..200)
+            .filter(|_| with_temperature(&logits, 100.0, &mut rng) == Some(1))
+            .count();
+        assert!(greedy > 190 && flat < 100);
+    }
+}
@@ -0,0 +1,785 @@
+use super::engine::{sum, Op, Scalar};
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    collections::HashMap,
+    fmt, fs, io, mem,
+    ops::{Add, Div, Mul, Sub},
+    path::Path,
+};
+
+/// Values the assertion macros can compare: plain floats and the data of scalars.
+pub trait ApproxValue {
+    fn value(&self) -> f64;
+}
+
+impl ApproxValue for f32 {
+    fn value(&self) -> f64 {
+        *self as f64
+    }
+}
+
+impl ApproxValue for f64 {
+    fn value(&self) -> f64 {
+        *self
+    }
+}
+
+impl ApproxValue for Scalar<f32> {
+    fn value(&self) -> f64 {
+        self.data() as f64
+    }
+}
+
+impl ApproxValue for Scalar<f64> {
+    fn value(&self) -> f64 {
+        self.data()
+    }
+}
+
+impl<V: ApproxValue> ApproxValue for &V {
+    fn value(&self) -> f64 {
+        (*self).value()
+    }
+}
+
+/// True if `a` and `b` differ by at most `tol`, or both are NaN.
+pub fn close(a: f64, b: f64, tol: f64) -> bool {
+    (a.is_nan() && b.is_nan()) || a == b || (a - b).abs() <= tol
+}
+
+/// Asserts two scalars or floats are within `tol` of each other.
+#[macro_export]
+macro_rules! assert_scalar_close {
+    ($a:expr, $b:expr, $tol:expr) => {{
+        let (a, b) = (
+            $crate::testing::ApproxValue::value(&$a),
+            $crate::testing::ApproxValue::value(&$b),
+        );
+
+        assert!(
+            $crate::testing::close(a, b, $tol as f64),
+            "assertion failed: `{}` ≈ `{}` (left: {}, right: {}, tol: {})",
+            stringify!($a),
+            stringify!($b),
+            a,
+            b,
+            $tol
+        );
+    }};
+}
+
+/// Asserts the gradient of each scalar in `$scalars` is within `tol` of the
+/// matching float in `$expected`.
+#[macro_export]
+macro_rules! assert_grads_close {
+    ($scalars:expr, $expected:expr, $tol:expr) => {{
+        let grads: Vec<f64> = $scalars.iter().map(|s| s.grad() as f64).collect();
+        let expected: Vec<f64> = $expected.iter().map(|e| *e as f64).collect();
+
+        assert_eq!(grads.len(), expected.len(), "gradient count mismatch");
+
+        for (i, (g, e)) in grads.iter().zip(&expected).enumerate() {
+            assert!(
+                $crate::testing::close(*g, *e, $tol as f64),
+                "gradient {} of `{}`: {} is not within {} of {}",
+                i,
+                stringify!($scalars),
+                g,
+                $tol,
+                e
+            );
+        }
+    }};
+}
+
+/// Step of the complex-step derivative; far below f64 rounding of the real
+/// part, which it never touches.
+const COMPLEX_STEP: f64 = 1e-20;
+
+/// Minimal complex number for complex-step differentiation.
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn real(re: f64) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    fn exp(self) -> Self {
+        let r = self.re.exp();
+        Self {
+            re: r * self.im.cos(),
+            im: r * self.im.sin(),
+        }
+    }
+
+    fn ln(self) -> Self {
+        Self {
+            re: self.re.hypot(self.im).ln(),
+            im: self.im.atan2(self.re),
+        }
+    }
+
+    fn powf(self, n: f64) -> Self {
+        (self.ln() * Self::real(n)).exp()
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let mut result = Self::real(1.0);
+        for _ in 0..n.unsigned_abs() {
+            result = result * self;
+        }
+
+        if n < 0 {
+            Self::real(1.0) / result
+        } else {
+            result
+        }
+    }
+
+    fn tanh(self) -> Self {
+        // Beyond this the real part is ±1 and the imaginary one vanishes.
+        if self.re.abs() > 20.0 {
+            return Self::real(self.re.signum());
+        }
+
+        let d = (2.0 * self.re).cosh() + (2.0 * self.im).cos();
+        Self {
+            re: (2.0 * self.re).sinh() / d,
+            im: (2.0 * self.im).sin() / d,
+        }
+    }
+
+    fn sigmoid(self) -> Self {
+        Self::real(1.0) / (Self::real(1.0) + (Self::real(0.0) - self).exp())
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            re: self.re + rhs.re,
+            im: self.im + rhs.im,
+        }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            re: self.re - rhs.re,
+            im: self.im - rhs.im,
+        }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            re: self.re * rhs.re - self.im * rhs.im,
+            im: self.re * rhs.im + self.im * rhs.re,
+        }
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let d = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self {
+            re: (self.re * rhs.re + self.im * rhs.im) / d,
+            im: (self.im * rhs.re - self.re * rhs.im) / d,
+        }
+    }
+}
+
+/// The node's op applied to complex `args`, `None` for ops that have no
+/// analytic extension (rounding, selection, custom ops).
+fn complex_op(op: &Op, args: &[Complex]) -> Option<Complex> {
+    let c = Complex::real;
+
+    Some(match (op, args) {
+        (Op::ADD, [a, b]) => *a + *b,
+        (Op::SUB, [a, b]) => *a - *b,
+        (Op::MUL, [a, b]) => *a * *b,
+        (Op::DIV, [a, b]) => *a / *b,
+        (Op::NEG, [a]) => c(0.0) - *a,
+        (Op::MAX, [a, b]) => {
+            if a.re >= b.re {
+                *a
+            } else {
+                *b
+            }
+        }
+        (Op::MIN, [a, b]) => {
+            if a.re <= b.re {
+                *a
+            } else {
+                *b
+            }
+        }
+        (Op::POWI(n), [a]) => a.powi(*n),
+        (Op::POWF(n), [a]) => a.powf(*n as f64),
+        (Op::POW, [a, b]) => (a.ln() * *b).exp(),
+        (Op::TANH, [a]) => a.tanh(),
+        (Op::SIGMOID, [a]) => a.sigmoid(),
+        (Op::LEAKYRELU(alpha), [a]) if a.re <= 0.0 => c(*alpha as f64) * *a,
+        (Op::LEAKYRELU(_), [a]) => *a,
+        (Op::CLAMP { lo, .. }, [a]) if a.re < *lo as f64 => c(*lo as f64),
+        (Op::CLAMP { hi, .. }, [a]) if a.re > *hi as f64 => c(*hi as f64),
+        (Op::CLAMP { .. }, [a]) => *a,
+        (Op::GELU, [a]) => {
+            let inner = c(0.797_884_560_802_865_4) * (*a + c(0.044715) * a.powi(3));
+            c(0.5) * *a * (c(1.0) + inner.tanh())
+        }
+        (Op::SOFTPLUS, [a]) => (c(1.0) + a.exp()).ln(),
+        (Op::SILU, [a]) => *a * a.sigmoid(),
+        (Op::ELU(alpha), [a]) if a.re <= 0.0 => c(*alpha as f64) * (a.exp() - c(1.0)),
+        (Op::ELU(_), [a]) => *a,
+        (Op::SQRT, [a]) => a.powf(0.5),
+        (Op::LN, [a]) => a.ln(),
+        (Op::LOG2, [a]) => a.ln() / c(2f64.ln()),
+        (Op::LOG10, [a]) => a.ln() / c(10f64.ln()),
+        (Op::EXP, [a]) => a.exp(),
+        _ => return None,
+    })
+}
+
+/// d`output`/d`input` for each of `inputs` by complex-step differentiation:
+/// the graph is re-evaluated in complex f64 arithmetic with one input moved
+/// to `x + ih`, and the derivative read off as `Im f(x + ih) / h`. With no
+/// subtraction involved it is accurate to f64 precision, a far tighter
+/// reference than finite differences. Returns `None` if the graph holds an op
+/// without a complex extension, or a child that has been dropped.
+pub fn complex_step_grad(output: &Scalar<f32>, inputs: &[Scalar<f32>]) -> Option<Vec<f64>> {
+    let nodes: Vec<Scalar<f32>> = output.iter_topological().collect();
+
+    inputs
+        .iter()
+        .map(|input| {
+            let mut values: HashMap<u64, Complex> = HashMap::new();
+
+            for node in &nodes {
+                let z = if node.ptr_eq(input) {
+                    Complex {
+                        re: node.data() as f64,
+                        im: COMPLEX_STEP,
+                    }
+                } else {
+                    match node.op() {
+                        None => Complex::real(node.data() as f64),
+                        Some(op) => {
+                            let args = node
+                                .children()
+                                .iter()
+                                .map(|c| values.get(&c.id()).copied())
+                                .collect::<Option<Vec<_>>>()?;
+
+                            complex_op(&op, &args)?
+                        }
+                    }
+                };
+
+                values.insert(node.id(), z);
+            }
+
+            Some(values[&output.id()].im / COMPLEX_STEP)
+        })
+        .collect()
+}
+
+/// Set to rewrite stored snapshots instead of comparing against them.
+pub const UPDATE_SNAPSHOTS: &str = "MICROGRAD_UPDATE_SNAPSHOTS";
+
+/// Canonical text form of a graph, for regression tests of code that builds
+/// graphs. One line per node, children before parents, numbered by position
+/// rather than node id so that rebuilding the same expression gives the same
+/// snapshot:
+///
+/// ```text
+/// %0 = leaf "a" 2.000
+/// %1 = leaf "b" -3.000
+/// %2 = MUL(%0, %1) -6.000
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphSnapshot(String);
+
+impl GraphSnapshot {
+    /// Snapshot of the graph rooted at `output`, with data rounded to
+    /// `decimals` places so harmless float noise doesn't show up as a change.
+    pub fn of(output: &Scalar<f32>, decimals: usize) -> Self {
+        let nodes: Vec<_> = output.iter_topological().collect();
+        let position: HashMap<_, _> = nodes.iter().enumerate().map(|(i, n)| (n.id(), i)).collect();
+        let mut text = String::new();
+
+        for (i, node) in nodes.iter().enumerate() {
+            let scale = 10f64.powi(decimals as i32);
+            // Adding zero turns a rounded -0 into 0.
+            let data = (node.data() as f64 * scale).round() / scale + 0.0;
+
+            let node_text = match node.op() {
+                None => format!("leaf {:?}", node.get_label()),
+                Some(op) => {
+                    let args: Vec<_> = node
+                        .children()
+                        .iter()
+                        .map(|c| format!("%{}", position[&c.id()]))
+                        .collect();
+
+                    format!("{op:?}({})", args.join(", "))
+                }
+            };
+
+            text += &format!("%{i} = {node_text} {data:.decimals$}\n");
+        }
+
+        Self(text)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Describes the first line where `self` and `expected` differ.
+    pub fn diff(&self, expected: &GraphSnapshot) -> Option<String> {
+        let (mut ours, mut theirs) = (self.0.lines(), expected.0.lines());
+
+        let mut line = 0;
+        loop {
+            line += 1;
+            match (ours.next(), theirs.next()) {
+                (None, None) => return None,
+                (a, b) if a == b => continue,
+                (a, b) => {
+                    return Some(format!(
+                        "line {line}: expected `{}`, got `{}`",
+                        b.unwrap_or("<end>"),
+                        a.unwrap_or("<end>")
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Compares against the snapshot stored at `path`, returning the first
+    /// difference. A missing file is created from `self` instead, as is any
+    /// file when the `MICROGRAD_UPDATE_SNAPSHOTS` environment variable is set.
+    pub fn check(&self, path: impl AsRef<Path>) -> io::Result<Option<String>> {
+        let path = path.as_ref();
+
+        if std::env::var_os(UPDATE_SNAPSHOTS).is_some() || !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, &self.0)?;
+
+            return Ok(None);
+        }
+
+        let stored = GraphSnapshot(fs::read_to_string(path)?);
+
+        Ok(self.diff(&stored))
+    }
+
+    /// Panics unless the graph matches the snapshot stored at `path`; see `check`.
+    pub fn assert_matches(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        match self.check(path) {
+            Ok(None) => {}
+            Ok(Some(diff)) => panic!(
+                "graph snapshot {} changed, {diff}; rerun with {UPDATE_SNAPSHOTS}=1 to accept",
+                path.display()
+            ),
+            Err(e) => panic!("graph snapshot {}: {e}", path.display()),
+        }
+    }
+}
+
+impl fmt::Display for GraphSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A small graph with known data and gradients, for checking ops and
+/// backward implementations.
+pub struct RefGraph {
+    pub name: &'static str,
+    pub inputs: Vec<Scalar<f32>>,
+    pub output: Scalar<f32>,
+    pub data: f32,
+    /// d`output`/d`input` for each input.
+    pub grads: Vec<f32>,
+}
+
+/// d`output`/d`input` for each of `inputs` by central differences
+/// `(f(x + eps) - f(x - eps)) / 2eps`, re-running `forward` with each input
+/// nudged in turn. The graph's data is restored before returning. Works for
+/// any op, at f32 precision: expect agreement with `backward` to about
+/// `1e-2` relative for an `eps` around `1e-3`.
+pub fn finite_difference_grad(output: &Scalar<f32>, inputs: &[Scalar<f32>], eps: f32) -> Vec<f32> {
+    let grads = inputs
+        .iter()
+        .map(|input| {
+            let x = input.data();
+            let at = |x: f32| {
+                input.set_data(x);
+                output.forward();
+                output.data()
+            };
+
+            let slope = (at(x + eps) - at(x - eps)) / (2.0 * eps);
+            input.set_data(x);
+
+            slope
+        })
+        .collect();
+
+    output.forward();
+
+    grads
+}
+
+/// Every op `random_graph` can build, with representative parameters; any
+/// other parameters work too.
+pub fn random_graph_ops() -> Vec<Op> {
+    vec![
+        Op::ADD,
+        Op::SUB,
+        Op::MUL,
+        Op::DIV,
+        Op::NEG,
+        Op::MAX,
+        Op::MIN,
+        Op::POWI(2),
+        Op::POWI(-1),
+        Op::POWF(1.5),
+        Op::POW,
+        Op::TANH,
+        Op::SIGMOID,
+        Op::LEAKYRELU(0.1),
+        Op::GELU,
+        Op::SOFTPLUS,
+        Op::SILU,
+        Op::ELU(1.0),
+        Op::SQRT,
+        Op::FLOOR,
+        Op::CEIL,
+        Op::ROUND,
+        Op::SIGN(false),
+        Op::CLAMP { lo: -1.0, hi: 1.0 },
+        Op::LN,
+        Op::LOG2,
+        Op::LOG10,
+        Op::EXP,
+    ]
+}
+
+/// Distance operands keep from kinks, jumps and domain edges, so that finite
+/// differences never straddle one.
+const MARGIN: f32 = 0.1;
+
+/// Applies `op` to `args`, or returns `None` if they are too close to where
+/// it is non-smooth or undefined.
+fn apply_op(op: &Op, args: &[Scalar<f32>]) -> Option<Scalar<f32>> {
+    let away = |x: f32, from: f32| (x - from).abs() > MARGIN;
+    let positive = |x: &Scalar<f32>| x.data() > 2.0 * MARGIN;
+    let off_grid = |x: f32, shift: f32| {
+        away((x + shift).fract().abs(), 0.0) && away((x + shift).fract().abs(), 1.0)
+    };
+
+    Some(match (op, args) {
+        (Op::ADD, [a, b]) => a.clone() + b.clone(),
+        (Op::SUB, [a, b]) => a.clone() - b.clone(),
+        (Op::MUL, [a, b]) => a.clone() * b.clone(),
+        (Op::DIV, [a, b]) if b.data().abs() > 0.5 => a.clone() / b.clone(),
+        (Op::NEG, [a]) => -a.clone(),
+        (Op::MAX, [a, b]) if away(a.data(), b.data()) => a.max(b),
+        (Op::MIN, [a, b]) if away(a.data(), b.data()) => a.min(b),
+        (Op::POWI(n), [a]) if *n >= 0 || a.data().abs() > 0.5 => a.powi(*n),
+        (Op::POWF(n), [a]) if positive(a) => a.powf(*n),
+        (Op::POW, [a, b]) if positive(a) => a.pow(b),
+        (Op::TANH, [a]) => a.tanh(),
+        (Op::SIGMOID, [a]) => a.sigmoid(),
+        (Op::LEAKYRELU(alpha), [a]) if away(a.data(), 0.0) => a.leaky_relu(*alpha),
+        (Op::GELU, [a]) => a.gelu(),
+        (Op::SOFTPLUS, [a]) => a.softplus(),
+        (Op::SILU, [a]) => a.silu(),
+        (Op::ELU(alpha), [a]) if away(a.data(), 0.0) => a.elu(*alpha),
+        (Op::SQRT, [a]) if positive(a) => a.sqrt(),
+        (Op::FLOOR, [a]) if off_grid(a.data(), 0.0) => a.floor(),
+        (Op::CEIL, [a]) if off_grid(a.data(), 0.0) => a.ceil(),
+        (Op::ROUND, [a]) if off_grid(a.data(), 0.5) => a.round(),
+        (Op::SIGN(false), [a]) if away(a.data(), 0.0) => a.sign(),
+        (Op::CLAMP { lo, hi }, [a]) if away(a.data(), *lo) && away(a.data(), *hi) => {
+            a.clamp(*lo, *hi)
+        }
+        (Op::LN, [a]) if positive(a) => a.ln(),
+        (Op::LOG2, [a]) if positive(a) => a.log2(),
+        (Op::LOG10, [a]) if positive(a) => a.log10(),
+        (Op::EXP, [a]) => a.exp(),
+        _ => return None,
+    })
+}
+
+/// A random DAG of `size` op nodes drawn from `ops`, over `size / 3 + 1`
+/// leaves in `[-2, 2]`. Each node takes its operands from any earlier nodes,
+/// so intermediates are freely shared; the output sums the nodes nothing
+/// else uses. Operands are only accepted where the op is smooth and defined,
+/// with a margin, and intermediates stay within `±10`, so `grads` (from
+/// `finite_difference_grad`) is a trustworthy reference for `backward`.
+///
+/// # Panics
+/// If `ops` is empty or holds `SELECT`, a custom op, or the straight-through
+/// `SIGN(true)` and `QUANT` whose gradients deliberately disagree with finite
+/// differences.
+pub fn random_graph(rng: &mut impl Rng, size: usize, ops: &[Op]) -> RefGraph {
+    let supported: Vec<_> = random_graph_ops().iter().map(mem::discriminant).collect();
+    for op in ops {
+        let rejected = matches!(
+            op,
+            Op::SELECT | Op::CUSTOM(_) | Op::SIGN(true) | Op::QUANT { .. }
+        );
+        assert!(
+            !rejected && supported.contains(&mem::discriminant(op)),
+            "random_graph can't check {op:?} by finite differences"
+        );
+    }
+    assert!(!ops.is_empty(), "random_graph needs at least one op");
+
+    let inputs: Vec<_> = (0..size / 3 + 1)
+        .map(|i| Scalar::new(rng.gen_range(-2.0..2.0), &format!("x{i}")))
+        .collect();
+    let mut nodes = inputs.clone();
+    let mut used = vec![false; nodes.len()];
+
+    // Unlucky operands are redrawn; give up on some nodes rather than loop forever.
+    for _ in 0..size * 100 {
+        if nodes.len() == inputs.len() + size {
+            break;
+        }
+
+        let op = ops.choose(rng).unwrap();
+        let arity = op.arity().unwrap_or(1);
+        let picks: Vec<usize> = (0..arity).map(|_| rng.gen_range(0..nodes.len())).collect();
+        let args: Vec<_> = picks.iter().map(|&i| nodes[i].clone()).collect();
+
+        match apply_op(op, &args) {
+            Some(node) if node.data().abs() <= 10.0 => {
+                picks.iter().for_each(|&i| used[i] = true);
+                nodes.push(node);
+                used.push(false);
+            }
+            _ => continue,
+        }
+    }
+
+    let sinks: Vec<_> = nodes[inputs.len()..]
+        .iter()
+        .zip(&used[inputs.len()..])
+        .filter(|(_, used)| !**used)
+        .map(|(n, _)| n.clone())
+        .collect();
+    let output = sum(if sinks.is_empty() { &inputs } else { &sinks });
+
+    RefGraph {
+        name: "random",
+        data: output.data(),
+        grads: finite_difference_grad(&output, &inputs, 1e-3),
+        inputs,
+        output,
+    }
+}
+
+pub fn reference_graphs() -> Vec<RefGraph> {
+    let mut graphs = vec![];
+
+    // y = a * b + a
+    let (a, b) = (Scalar::new(2.0, "a"), Scalar::new(-3.0, "b"));
+    graphs.push(RefGraph {
+        name: "mul_add",
+        output: a.clone() * b.clone() + a.clone(),
+        inputs: vec![a, b],
+        data: -4.0,
+        grads: vec![-2.0, 2.0],
+    });
+
+    // y = (x * x) * (x * x), sharing the intermediate node
+    let x = Scalar::new(1.5, "x");
+    let sq = x.clone() * x.clone();
+    graphs.push(RefGraph {
+        name: "shared",
+        output: sq.clone() * sq,
+        inputs: vec![x],
+        data: 5.0625,
+        grads: vec![13.5],
+    });
+
+    // y = tanh(w * x + b)
+    let (w, x, b) = (
+        Scalar::new(0.5, "w"),
+        Scalar::new(2.0, "x"),
+        Scalar::new(-1.0, "b"),
+    );
+    graphs.push(RefGraph {
+        name: "neuron",
+        output: (w.clone() * x.clone() + b.clone()).tanh(),
+        inputs: vec![w, x, b],
+        data: 0.0,
+        grads: vec![2.0, 0.5, 1.0],
+    });
+
+    graphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn random_graphs() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(5);
+        let ops = random_graph_ops();
+
+        for size in [1, 5, 20, 40] {
+            for _ in 0..10 {
+                let g = random_graph(&mut rng, size, &ops);
+                g.output.backward();
+
+                for (input, expected) in g.inputs.iter().zip(&g.grads) {
+                    let tol = 2e-2 * (1.0 + expected.abs());
+                    assert_scalar_close!(input.grad(), expected, tol);
+                }
+                assert_eq!(g.output.data(), g.data);
+            }
+        }
+
+        // Restricted op sets, e.g. when stress-testing a single new op.
+        let g = random_graph(&mut rng, 30, &[Op::MUL, Op::TANH]);
+        let ops: Vec<_> = g.output.iter_topological().filter_map(|n| n.op()).collect();
+        assert!(ops
+            .iter()
+            .all(|op| matches!(op, Op::MUL | Op::TANH | Op::ADD)));
+        assert!(ops.len() >= 30);
+
+        // Parameters other than the listed ones are fine too.
+        random_graph(&mut rng, 10, &[Op::POWI(3), Op::LEAKYRELU(0.3)]);
+        for op in [Op::SIGN(true), Op::SELECT] {
+            let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                random_graph(&mut rand::thread_rng(), 5, &[op]);
+            }));
+            assert!(caught.is_err());
+        }
+    }
+
+    #[test]
+    fn finite_differences() {
+        for g in reference_graphs() {
+            let before = g.output.data();
+            let grads = finite_difference_grad(&g.output, &g.inputs, 1e-3);
+
+            for (fd, expected) in grads.iter().zip(&g.grads) {
+                assert_scalar_close!(fd, expected, 1e-2);
+            }
+            assert_eq!(g.output.data(), before);
+        }
+    }
+
+    #[test]
+    fn graph_snapshot() {
+        let build = |b: f32| {
+            let (a, b) = (Scalar::new(2.0, "a"), Scalar::new(b, "b"));
+            (a.clone() * b + a).powi(2).tanh()
+        };
+
+        let snapshot = GraphSnapshot::of(&build(-3.0), 3);
+        assert_eq!(
+            snapshot.as_str(),
+            "%0 = leaf \"a\" 2.000\n\
+             %1 = leaf \"b\" -3.000\n\
+             %2 = MUL(%0, %1) -6.000\n\
+             %3 = ADD(%2, %0) -4.000\n\
+             %4 = POWI(2)(%3) 16.000\n\
+             %5 = TANH(%4) 1.000\n"
+        );
+        assert_eq!(GraphSnapshot::of(&build(-3.0000001), 3), snapshot);
+
+        let changed = GraphSnapshot::of(&build(-2.0), 3);
+        assert_eq!(
+            changed.diff(&snapshot).unwrap(),
+            "line 2: expected `%1 = leaf \"b\" -3.000`, got `%1 = leaf \"b\" -2.000`"
+        );
+
+        let path = std::env::temp_dir().join(format!("micrograd-graph-{}", std::process::id()));
+        assert_eq!(snapshot.check(&path).unwrap(), None);
+        snapshot.assert_matches(&path);
+        assert!(changed.check(&path).unwrap().is_some());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reference_graphs_backward() {
+        for g in reference_graphs() {
+            g.output.backward();
+
+            assert_scalar_close!(g.output, g.data, 1e-6);
+            assert_grads_close!(g.inputs, g.grads, 1e-5);
+        }
+
+        assert_scalar_close!(f32::NAN, f64::NAN, 0.0);
+    }
+
+    #[test]
+    fn complex_step() {
+        for g in reference_graphs() {
+            let grads = complex_step_grad(&g.output, &g.inputs).unwrap();
+            for (c, e) in grads.iter().zip(&g.grads) {
+                assert!(close(*c, *e as f64, 1e-6));
+            }
+        }
+
+        let (a, b) = (Scalar::new(1.3, "a"), Scalar::new(-0.4, "b"));
+        let y = (a.exp() / b.sigmoid()).ln() + a.powf(1.5) * b.gelu() + a.pow(&b).sqrt()
+            - (a.clone() * b.clone()).tanh().powi(-2)
+            + b.leaky_relu(0.2)
+            + b.softplus()
+            + a.silu()
+            + b.elu(0.7)
+            + -a.clone()
+            + a.max(&b) * b.min(&a)
+            + a.clamp(-5.0, 5.0) * b.clamp(-0.1, 0.1);
+        y.backward();
+
+        let reference = complex_step_grad(&y, &[a.clone(), b.clone()]).unwrap();
+        assert_grads_close!([a.clone(), b.clone()], reference, 1e-4);
+
+        // Gradient with respect to an intermediate node.
+        let h = a.clone() * b.clone();
+        let y = h.clone() * 3.0;
+        assert_eq!(complex_step_grad(&y, &[h]), Some(vec![3.0]));
+
+        assert!(complex_step_grad(&a.floor(), &[a]).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "gradient 1")]
+    fn grads_mismatch() {
+        let g = &reference_graphs()[0];
+        g.output.backward();
+
+        assert_grads_close!(g.inputs, [-2.0, 2.5], 1e-3);
+    }
+}
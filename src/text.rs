@@ -0,0 +1,111 @@
+//! Character vocabularies for toy language models.
+
+use std::collections::HashMap;
+
+/// A bidirectional mapping between characters and token ids `0..len()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vocab {
+    chars: Vec<char>,
+    ids: HashMap<char, usize>,
+}
+
+impl Vocab {
+    /// Ids follow the order of first appearance in `chars`; repeats are ignored.
+    pub fn new(chars: impl IntoIterator<Item = char>) -> Self {
+        let mut vocab = Self {
+            chars: vec![],
+            ids: HashMap::new(),
+        };
+
+        for c in chars {
+            if !vocab.ids.contains_key(&c) {
+                vocab.ids.insert(c, vocab.chars.len());
+                vocab.chars.push(c);
+            }
+        }
+
+        vocab
+    }
+
+    /// The distinct characters of `text`, sorted, after `boundary` which gets
+    /// id 0 and marks the start and end of words.
+    pub fn from_text(text: &str, boundary: char) -> Self {
+        let mut chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        chars.sort_unstable();
+
+        Self::new(std::iter::once(boundary).chain(chars))
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    pub fn id(&self, c: char) -> Option<usize> {
+        self.ids.get(&c).cloned()
+    }
+
+    pub fn char(&self, id: usize) -> Option<char> {
+        self.chars.get(id).cloned()
+    }
+
+    /// The ids of `text`, or `None` if it has a character outside the vocabulary.
+    pub fn encode(&self, text: &str) -> Option<Vec<usize>> {
+        text.chars().map(|c| self.id(c)).collect()
+    }
+
+    /// The characters of `ids`, skipping unknown ones.
+    pub fn decode(&self, ids: &[usize]) -> String {
+        ids.iter().filter_map(|id| self.char(*id)).collect()
+    }
+
+    /// Inputs for a dense layer: 1 at `id`, 0 elsewhere.
+    pub fn one_hot(&self, id: usize) -> Vec<f32> {
+        let mut v = vec![0.0; self.len()];
+
+        if let Some(x) = v.get_mut(id) {
+            *x = 1.0;
+        }
+
+        v
+    }
+}
+
+/// `(current, next)` id pairs of every word, each wrapped in `boundary` at
+/// both ends, as training data for a bigram model. Words with characters
+/// outside `vocab` are skipped.
+pub fn bigrams<'a>(
+    vocab: &Vocab,
+    words: impl IntoIterator<Item = &'a str>,
+    boundary: char,
+) -> Vec<(usize, usize)> {
+    words
+        .into_iter()
+        .filter_map(|w| vocab.encode(&format!("{boundary}{w}{boundary}")))
+        .flat_map(|ids| ids.windows(2).map(|p| (p[0], p[1])).collect::<Vec<_>>())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vocab() {
+        let vocab = Vocab::from_text("emma\nava", '.');
+
+        assert_eq!(vocab.len(), 5);
+        assert_eq!(vocab.decode(&[0, 1, 2, 3, 4]), ".aemv");
+        assert_eq!(vocab.encode("ava"), Some(vec![1, 4, 1]));
+        assert_eq!(vocab.encode("bob"), None);
+        assert_eq!(vocab.one_hot(2), vec![0.0, 0.0, 1.0, 0.0, 0.0]);
+
+        assert_eq!(
+            bigrams(&vocab, ["ma", "zed"], '.'),
+            vec![(0, 3), (3, 1), (1, 0)]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Output directories for training runs, so repeated experiments don't
+//! overwrite each other:
+//!
+//! ```text
+//! runs/
+//!   index.csv
+//!   20261016-093012/
+//!     config.toml
+//!     metrics.csv
+//!     checkpoints/epoch-200.ckpt
+//!     plots/loss.svg
+//! ```
+//!
+//! Every `Run::new` adds a row to `index.csv` with the run directory and its
+//! start time in seconds since the Unix epoch.
+
+use super::{
+    optim::Optimizer,
+    train::{CheckpointError, History, Trainer},
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const INDEX: &str = "index.csv";
+
+#[derive(Debug)]
+pub struct Run {
+    dir: PathBuf,
+    started: u64,
+}
+
+impl Run {
+    /// Creates a fresh run directory under `root`, named after the current
+    /// UTC time with a `-2`, `-3`, … suffix if runs start within the same
+    /// second, and records it in `root/index.csv`.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        fs::create_dir_all(root)?;
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let stamp = timestamp(started);
+
+        let mut n = 1;
+        let dir = loop {
+            let name = match n {
+                1 => stamp.clone(),
+                _ => format!("{stamp}-{n}"),
+            };
+            let dir = root.join(&name);
+
+            match fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e),
+            }
+        };
+
+        let index = root.join(INDEX);
+        let fresh = !index.exists();
+        let mut index = OpenOptions::new().create(true).append(true).open(index)?;
+        if fresh {
+            writeln!(index, "run,started")?;
+        }
+        let name = dir.file_name().unwrap_or_default().to_string_lossy();
+        writeln!(index, "{name},{started}")?;
+
+        Ok(Self { dir, started })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Start time in seconds since the Unix epoch.
+    pub fn started(&self) -> u64 {
+        self.started
+    }
+
+    /// Writes `contents` to `name` inside the run directory, creating any
+    /// subdirectories in it.
+    pub fn write(&self, name: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<PathBuf> {
+        let path = self.dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&path, contents)?;
+
+        Ok(path)
+    }
+
+    /// Copies the experiment spec into the run as `config.<ext>`, keeping the
+    /// original extension so it can be loaded again.
+    pub fn save_config(&self, spec: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let spec = spec.as_ref();
+        let name = match spec.extension() {
+            Some(ext) => format!("config.{}", ext.to_string_lossy()),
+            None => "config".to_string(),
+        };
+
+        self.write(name, fs::read(spec)?)
+    }
+
+    /// Writes `History::to_csv` as `metrics.csv`, replacing earlier calls.
+    pub fn save_metrics(&self, history: &History) -> io::Result<PathBuf> {
+        self.write("metrics.csv", history.to_csv())
+    }
+
+    /// Saves a checkpoint of `trainer` as `checkpoints/epoch-<n>.ckpt`.
+    pub fn checkpoint<O: Optimizer>(
+        &self,
+        trainer: &Trainer<O>,
+    ) -> Result<PathBuf, CheckpointError> {
+        let dir = self.dir.join("checkpoints");
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!("epoch-{}.ckpt", trainer.epoch()));
+        trainer.save(&path)?;
+
+        Ok(path)
+    }
+
+    /// Writes an SVG as `plots/<name>.svg`.
+    pub fn save_svg(&self, name: &str, svg: &str) -> io::Result<PathBuf> {
+        self.write(format!("plots/{name}.svg"), svg)
+    }
+}
+
+/// `YYYYMMDD-HHMMSS` in UTC.
+fn timestamp(secs: u64) -> String {
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        rem / 3_600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nn::MLP, optim::SGD};
+
+    #[test]
+    fn run_layout() {
+        assert_eq!(timestamp(0), "19700101-000000");
+        assert_eq!(timestamp(951_827_696), "20000229-123456");
+
+        let root = std::env::temp_dir().join(format!("micrograd-runs-{}", std::process::id()));
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0]];
+        let ys = vec![vec![1.0], vec![-1.0]];
+        let mut trainer = Trainer::new(MLP::seeded(2, &[3, 1], 1), SGD::new(0.1));
+        let history = trainer.fit(&xs, &ys, 3, &mut []).unwrap();
+
+        let first = Run::new(&root).unwrap();
+        let second = Run::new(&root).unwrap();
+        assert_ne!(first.dir(), second.dir());
+
+        let metrics = first.save_metrics(&history).unwrap();
+        let checkpoint = first.checkpoint(&trainer).unwrap();
+        let plot = first
+            .save_svg("loss", &history.to_svg(200.0, 100.0))
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(metrics).unwrap(), history.to_csv());
+        assert!(checkpoint.ends_with("checkpoints/epoch-3.ckpt"));
+        assert!(trainer.resume(&checkpoint).is_ok());
+        assert!(plot.ends_with("plots/loss.svg"));
+
+        let index = fs::read_to_string(root.join(INDEX)).unwrap();
+        let runs: Vec<_> = index
+            .lines()
+            .skip(1)
+            .map(|l| l.split(',').next().unwrap())
+            .collect();
+        assert!(index.starts_with("run,started\n"));
+        assert_eq!(runs.len(), 2);
+        assert!(second.dir().ends_with(runs[1]));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
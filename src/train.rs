@@ -0,0 +1,980 @@
+use super::{
+    engine::{check_temperature, cross_entropy, sum, EngineError, Scalar},
+    nn::{NeuronError, MLP},
+    optim::Optimizer,
+};
+use std::{
+    fmt::Write,
+    fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+pub trait Callback {
+    /// Called with the loss graph right after its backward pass.
+    fn on_graph(&mut self, _epoch: usize, _loss: &Scalar<f32>) {}
+
+    /// Called after the backward pass, before the optimizer step.
+    fn on_backward(&mut self, _epoch: usize, _model: &MLP) {}
+
+    /// Called after the optimizer step with the epoch's loss.
+    fn on_epoch_end(&mut self, _epoch: usize, _loss: f32) {}
+}
+
+type Metric = Box<dyn Fn(&MLP) -> f32>;
+type Curriculum = Box<dyn Fn(usize, usize) -> f32>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Loss {
+    /// Sum of squared errors against the targets. Not divided by the number
+    /// of outputs or samples, so it grows with both. Also read as `"mse"`.
+    #[default]
+    #[cfg_attr(feature = "config", serde(alias = "mse"))]
+    SumSquared,
+    /// Summed softmax cross-entropy; each target is a single class index.
+    CrossEntropy,
+}
+
+pub struct Trainer<O: Optimizer> {
+    model: MLP,
+    optimizer: O,
+    metrics: Vec<(String, Metric)>,
+    loss: Loss,
+    curriculum: Option<Curriculum>,
+    epoch: usize,
+}
+
+impl<O: Optimizer> Trainer<O> {
+    pub fn new(model: MLP, optimizer: O) -> Self {
+        Self {
+            model,
+            optimizer,
+            metrics: vec![],
+            loss: Loss::default(),
+            curriculum: None,
+            epoch: 0,
+        }
+    }
+
+    pub fn with_loss(mut self, loss: Loss) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    /// Weights each sample's loss by `weight(epoch, sample)`, re-evaluated at
+    /// the start of every epoch, for curriculum learning: e.g. a weight of
+    /// zero for samples harder than the epoch allows leaves them out until
+    /// later. Samples weighted zero aren't evaluated at all that epoch.
+    pub fn with_curriculum(mut self, weight: impl Fn(usize, usize) -> f32 + 'static) -> Self {
+        self.curriculum = Some(Box::new(weight));
+        self
+    }
+
+    /// Evaluates `metric` on the model after every epoch, recording it in the `History`.
+    pub fn with_metric(mut self, name: &str, metric: impl Fn(&MLP) -> f32 + 'static) -> Self {
+        self.metrics.push((name.to_string(), Box::new(metric)));
+        self
+    }
+
+    pub fn model(&self) -> &MLP {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut MLP {
+        &mut self.model
+    }
+
+    pub fn into_model(self) -> MLP {
+        self.model
+    }
+
+    /// Number of epochs trained so far, including those before a `resume`.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Writes the model weights, optimizer state and epoch counter to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let join = |values: Vec<String>| values.join(" ");
+
+        let checkpoint = format!(
+            "epoch {}\nparams {}\noptimizer {}\n",
+            self.epoch,
+            join(
+                self.model
+                    .parameters()
+                    .iter()
+                    .map(|p| p.data().to_string())
+                    .collect()
+            ),
+            join(
+                self.optimizer
+                    .state()
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect()
+            ),
+        );
+
+        fs::write(path, checkpoint)?;
+
+        Ok(())
+    }
+
+    /// Restores a checkpoint written by `save` into this trainer, which must
+    /// have the same architecture and optimizer, so that the next `fit`
+    /// continues exactly where the saved run stopped.
+    pub fn resume(&mut self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let checkpoint = fs::read_to_string(path)?;
+        let mut epoch = None;
+        let mut params = None;
+        let mut state = None;
+
+        for line in checkpoint.lines() {
+            let (key, values) = line.split_once(' ').unwrap_or((line, ""));
+            let values = values.split_whitespace();
+
+            match key {
+                "epoch" => epoch = Some(values.collect::<String>().parse()?),
+                "params" => params = Some(values.map(str::parse).collect::<Result<Vec<f32>, _>>()?),
+                "optimizer" => {
+                    state = Some(values.map(str::parse).collect::<Result<Vec<f64>, _>>()?)
+                }
+                _ => return Err(CheckpointError::FormatErr),
+            }
+        }
+
+        let (Some(epoch), Some(params), Some(state)) = (epoch, params, state) else {
+            return Err(CheckpointError::FormatErr);
+        };
+
+        let parameters = self.model.parameters();
+
+        if params.len() != parameters.len() {
+            return Err(CheckpointError::ParamLenErr);
+        }
+
+        if !self.optimizer.load_state(&state) {
+            return Err(CheckpointError::OptimizerStateErr);
+        }
+
+        for (p, d) in parameters.iter().zip(params) {
+            p.set_data(d);
+        }
+
+        self.epoch = epoch;
+
+        Ok(())
+    }
+
+    /// Full-batch training on the trainer's `Loss`, squared error by default.
+    pub fn fit(
+        &mut self,
+        xs: &[Vec<f32>],
+        ys: &[Vec<f32>],
+        epochs: usize,
+        callbacks: &mut [&mut dyn Callback],
+    ) -> Result<History, NeuronError> {
+        if xs.len() != ys.len() {
+            return Err(NeuronError::BatchLenErr {
+                inputs: xs.len(),
+                targets: ys.len(),
+            });
+        }
+
+        let start = Instant::now();
+        let mut history = History {
+            metric_names: self.metrics.iter().map(|(name, _)| name.clone()).collect(),
+            epochs: vec![],
+        };
+
+        for epoch in self.epoch..self.epoch + epochs {
+            let weights: Option<Vec<f32>> = self
+                .curriculum
+                .as_ref()
+                .map(|weight| (0..xs.len()).map(|sample| weight(epoch, sample)).collect());
+            let weights = weights.as_deref();
+            // With every sample weighted out the loss is a constant, and its
+            // backward pass wouldn't reset the last epoch's gradients.
+            let active = (0..xs.len()).any(|i| weights.is_none_or(|w| w[i] != 0.0));
+
+            let loss = batch_loss(&mut self.model, self.loss, xs, ys, weights)?;
+            if active {
+                loss.backward();
+            } else {
+                self.model.zero_grad();
+            }
+
+            for callback in callbacks.iter_mut() {
+                callback.on_graph(epoch, &loss);
+                callback.on_backward(epoch, &self.model);
+            }
+
+            if active {
+                let params = self.model.parameters();
+                let (model, kind) = (&mut self.model, self.loss);
+                self.optimizer.step_with_closure(&params, &mut || {
+                    let loss = batch_loss(model, kind, xs, ys, weights)
+                        .expect("batch checked by the first pass");
+                    loss.backward();
+                    loss
+                });
+            }
+
+            let loss = loss.data();
+
+            history.epochs.push(EpochRecord {
+                epoch,
+                loss,
+                lr: self.optimizer.lr(),
+                elapsed: start.elapsed(),
+                metrics: self.metrics.iter().map(|(_, m)| m(&self.model)).collect(),
+            });
+
+            for callback in callbacks.iter_mut() {
+                callback.on_epoch_end(epoch, loss);
+            }
+
+            self.epoch = epoch + 1;
+        }
+
+        Ok(history)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EpochRecord {
+    pub epoch: usize,
+    pub loss: f32,
+    pub lr: Option<f32>,
+    /// Time since the start of `fit` at the end of this epoch.
+    pub elapsed: Duration,
+    /// Values of the trainer's metrics, in `History::metric_names` order.
+    pub metrics: Vec<f32>,
+}
+
+/// Per-epoch record of a `Trainer::fit` run.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub metric_names: Vec<String>,
+    pub epochs: Vec<EpochRecord>,
+}
+
+impl History {
+    pub fn losses(&self) -> Vec<f32> {
+        self.epochs.iter().map(|e| e.loss).collect()
+    }
+
+    pub fn last_loss(&self) -> Option<f32> {
+        self.epochs.last().map(|e| e.loss)
+    }
+
+    pub fn metric(&self, name: &str) -> Option<Vec<f32>> {
+        let i = self.metric_names.iter().position(|n| n == name)?;
+
+        Some(self.epochs.iter().map(|e| e.metrics[i]).collect())
+    }
+
+    /// One row per epoch: `epoch,loss,lr,elapsed_ms` followed by the metrics.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("epoch,loss,lr,elapsed_ms");
+
+        for name in &self.metric_names {
+            write!(csv, ",{name}").unwrap();
+        }
+        csv.push('\n');
+
+        for e in &self.epochs {
+            let lr = e.lr.map_or(String::new(), |lr| lr.to_string());
+            write!(
+                csv,
+                "{},{},{},{}",
+                e.epoch,
+                e.loss,
+                lr,
+                e.elapsed.as_secs_f64() * 1000.0
+            )
+            .unwrap();
+
+            for m in &e.metrics {
+                write!(csv, ",{m}").unwrap();
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// Line plot of the loss per epoch as a standalone SVG document.
+    pub fn to_svg(&self, width: f32, height: f32) -> String {
+        let losses = self.losses();
+        let max = losses.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let min = losses.iter().cloned().fold(f32::INFINITY, f32::min);
+        let range = if max > min { max - min } else { 1.0 };
+        let step = width / (losses.len().max(2) - 1) as f32;
+
+        let points: Vec<String> = losses
+            .iter()
+            .enumerate()
+            .map(|(i, l)| {
+                format!(
+                    "{:.2},{:.2}",
+                    i as f32 * step,
+                    height - (l - min) / range * height
+                )
+            })
+            .collect();
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">\
+             <polyline fill=\"none\" stroke=\"black\" points=\"{}\"/></svg>",
+            points.join(" ")
+        )
+    }
+}
+
+/// A training graph built once over placeholder leaves. Each `step` writes new
+/// input and target data into the leaves and recomputes the existing graph
+/// instead of rebuilding it.
+pub struct StaticGraph {
+    inputs: Vec<Vec<Scalar<f32>>>,
+    targets: Vec<Vec<Scalar<f32>>>,
+    outputs: Vec<Vec<Scalar<f32>>>,
+    loss: Scalar<f32>,
+}
+
+impl StaticGraph {
+    /// Builds the squared error graph for a batch shaped like `xs` and `ys`.
+    pub fn new(model: &mut MLP, xs: &[Vec<f32>], ys: &[Vec<f32>]) -> Result<Self, NeuronError> {
+        if xs.len() != ys.len() {
+            return Err(NeuronError::BatchLenErr {
+                inputs: xs.len(),
+                targets: ys.len(),
+            });
+        }
+
+        let leaves = |data: &[Vec<f32>]| -> Vec<Vec<Scalar<f32>>> {
+            data.iter()
+                .map(|row| row.iter().map(|d| Scalar::new(*d, "")).collect())
+                .collect()
+        };
+
+        let inputs = leaves(xs);
+        let targets = leaves(ys);
+        let mut outputs = vec![];
+        let mut terms = vec![];
+
+        for (sample, (x, y)) in inputs.iter().zip(&targets).enumerate() {
+            let ypred = model.output(x.clone())?;
+
+            if ypred.len() != y.len() {
+                return Err(NeuronError::TargetLenErr {
+                    sample,
+                    expected: ypred.len(),
+                    actual: y.len(),
+                });
+            }
+
+            for (yp, yt) in ypred.iter().zip(y) {
+                terms.push((yp.clone() - yt.clone()).powi(2));
+            }
+
+            outputs.push(ypred);
+        }
+
+        let loss = sum(&terms);
+        loss.label("loss");
+
+        Ok(Self {
+            inputs,
+            targets,
+            outputs,
+            loss,
+        })
+    }
+
+    /// Loads a batch shaped like the one the graph was built with, runs
+    /// forward and backward, and returns the loss.
+    pub fn step(&self, xs: &[Vec<f32>], ys: &[Vec<f32>]) -> Result<f32, NeuronError> {
+        if xs.len() != self.inputs.len() || ys.len() != self.targets.len() {
+            return Err(NeuronError::BatchLenErr {
+                inputs: xs.len(),
+                targets: ys.len(),
+            });
+        }
+
+        for (sample, (x, y)) in xs.iter().zip(ys).enumerate() {
+            if x.len() != self.inputs[sample].len() {
+                return Err(NeuronError::input_len(self.inputs[sample].len(), x.len()));
+            }
+
+            if y.len() != self.targets[sample].len() {
+                return Err(NeuronError::TargetLenErr {
+                    sample,
+                    expected: self.targets[sample].len(),
+                    actual: y.len(),
+                });
+            }
+        }
+
+        for (leaves, data) in [(&self.inputs, xs), (&self.targets, ys)] {
+            for (l, d) in leaves.iter().flatten().zip(data.iter().flatten()) {
+                l.set_data(*d);
+            }
+        }
+
+        self.loss.forward();
+        self.loss.backward();
+
+        Ok(self.loss.data())
+    }
+
+    pub fn outputs(&self) -> &[Vec<Scalar<f32>>] {
+        &self.outputs
+    }
+
+    pub fn loss(&self) -> &Scalar<f32> {
+        &self.loss
+    }
+}
+
+/// Mean negative log-likelihood of `targets` under `logits` scaled by `temperature`.
+/// A `temperature` that isn't positive and finite is a `DomainErr`, as in
+/// `cross_entropy`.
+pub fn nll(logits: &[Vec<f32>], targets: &[usize], temperature: f32) -> Result<f32, EngineError> {
+    if logits.len() != targets.len() {
+        return Err(EngineError::InputLenErr);
+    }
+
+    check_temperature(temperature)?;
+
+    let mut total = 0.0;
+
+    for (l, t) in logits.iter().zip(targets) {
+        let l: Vec<Scalar<f32>> = l.iter().map(|d| Scalar::new(*d, "")).collect();
+        total += cross_entropy(&l, *t, temperature)?.data();
+    }
+
+    Ok(total / logits.len() as f32)
+}
+
+/// Post-training calibration: the softmax temperature minimizing the
+/// validation NLL of a trained classifier's `logits`, found by golden-section
+/// search over `log(temperature)` in `[-4, 4]`.
+pub fn fit_temperature(logits: &[Vec<f32>], targets: &[usize]) -> Result<f32, EngineError> {
+    let ratio = (5f32.sqrt() - 1.0) / 2.0;
+    let loss = |log_t: f32| nll(logits, targets, log_t.exp());
+
+    let (mut lo, mut hi) = (-4.0f32, 4.0f32);
+
+    for _ in 0..40 {
+        let a = hi - ratio * (hi - lo);
+        let b = lo + ratio * (hi - lo);
+
+        if loss(a)? < loss(b)? {
+            hi = b;
+        } else {
+            lo = a;
+        }
+    }
+
+    Ok(((lo + hi) / 2.0).exp())
+}
+
+/// Summed `loss` of the model's predictions over the batch, each sample's
+/// terms scaled by its weight when there are any.
+fn batch_loss(
+    model: &mut MLP,
+    loss: Loss,
+    xs: &[Vec<f32>],
+    ys: &[Vec<f32>],
+    weights: Option<&[f32]>,
+) -> Result<Scalar<f32>, NeuronError> {
+    let mut terms = vec![];
+
+    for (sample, (x, y)) in xs.iter().zip(ys).enumerate() {
+        let weight = weights.map_or(1.0, |w| w[sample]);
+        if weight == 0.0 {
+            continue;
+        }
+
+        let start = terms.len();
+        let ypred = model.output(x.iter().map(|d| Scalar::new(*d, "")).collect())?;
+
+        match loss {
+            Loss::SumSquared => {
+                if ypred.len() != y.len() {
+                    return Err(NeuronError::TargetLenErr {
+                        sample,
+                        expected: ypred.len(),
+                        actual: y.len(),
+                    });
+                }
+
+                for (yp, yt) in ypred.into_iter().zip(y) {
+                    terms.push((yp - Scalar::new(*yt, "")).powi(2));
+                }
+            }
+            Loss::CrossEntropy => {
+                let [class] = y[..] else {
+                    return Err(NeuronError::TargetLenErr {
+                        sample,
+                        expected: 1,
+                        actual: y.len(),
+                    });
+                };
+
+                let err = NeuronError::ClassErr {
+                    sample,
+                    class,
+                    classes: ypred.len(),
+                };
+                // An `as` cast would truncate fractions and saturate
+                // negatives and NaN to a valid index.
+                if class.fract() != 0.0 || class < 0.0 || class >= ypred.len() as f32 {
+                    return Err(err);
+                }
+
+                terms.push(cross_entropy(&ypred, class as usize, 1.0).map_err(|_| err)?);
+            }
+        }
+
+        if weight != 1.0 {
+            for term in &mut terms[start..] {
+                *term = term.clone() * weight;
+            }
+        }
+    }
+
+    Ok(sum(&terms))
+}
+
+/// Inputs and targets, one row per sample.
+pub type Dataset = (Vec<Vec<f32>>, Vec<Vec<f32>>);
+
+/// Reads a CSV file whose rows hold the inputs followed by `ntargets` target
+/// columns. A first row that doesn't parse as numbers is taken as a header.
+pub fn read_csv(path: impl AsRef<Path>, ntargets: usize) -> Result<Dataset, DataError> {
+    let mut xs = vec![];
+    let mut ys = vec![];
+
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let row: Result<Vec<f32>, _> = line.split(',').map(|v| v.trim().parse()).collect();
+        let row = match row {
+            Ok(row) => row,
+            Err(_) if i == 0 => continue,
+            Err(_) => return Err(DataError::ParseErr(i + 1)),
+        };
+
+        if row.len() <= ntargets {
+            return Err(DataError::ColumnsErr(i + 1));
+        }
+
+        let (x, y) = row.split_at(row.len() - ntargets);
+        xs.push(x.to_vec());
+        ys.push(y.to_vec());
+    }
+
+    Ok((xs, ys))
+}
+
+#[derive(Error, Debug)]
+pub enum DataError {
+    #[error("data io error")]
+    IoErr(#[from] io::Error),
+    #[error("line {0}: not a number")]
+    ParseErr(usize),
+    #[error("line {0}: too few columns")]
+    ColumnsErr(usize),
+}
+
+#[derive(Error, Debug)]
+pub enum CheckpointError {
+    #[error("checkpoint io error")]
+    IoErr(#[from] io::Error),
+    #[error("malformed checkpoint")]
+    FormatErr,
+    #[error("checkpoint parameter count does not match the model")]
+    ParamLenErr,
+    #[error("checkpoint optimizer state does not match the optimizer")]
+    OptimizerStateErr,
+}
+
+impl From<std::num::ParseIntError> for CheckpointError {
+    fn from(_: std::num::ParseIntError) -> Self {
+        CheckpointError::FormatErr
+    }
+}
+
+impl From<std::num::ParseFloatError> for CheckpointError {
+    fn from(_: std::num::ParseFloatError) -> Self {
+        CheckpointError::FormatErr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optim::{
+        Clamp, Constrained, CoordinateDescent, GradientNoise, LineSearch, Linear, OnePlusOneES,
+        RandomSearch, Scheduled, SGD,
+    };
+
+    #[test]
+    fn static_graph() {
+        let mut rng = rand::thread_rng();
+        let mut mlp = MLP::new(2, &[3, 1], &mut rng);
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0]];
+        let ys = vec![vec![1.0], vec![-1.0]];
+
+        let graph = StaticGraph::new(&mut mlp, &xs, &ys).unwrap();
+        let mut sgd = SGD::new(0.05);
+
+        for _ in 0..3 {
+            let loss = graph.step(&xs, &ys).unwrap();
+            let grads: Vec<f32> = mlp.parameters().iter().map(|p| p.grad()).collect();
+
+            let rebuilt = StaticGraph::new(&mut mlp, &xs, &ys).unwrap();
+            rebuilt.loss().backward();
+
+            assert!((loss - rebuilt.loss().data()).abs() < 1e-5);
+            for (g, p) in grads.iter().zip(mlp.parameters()) {
+                assert!((g - p.grad()).abs() < 1e-5);
+            }
+
+            sgd.step(&mlp.parameters());
+        }
+
+        assert!(graph.step(&xs[..1], &ys[..1]).is_err());
+    }
+
+    #[test]
+    fn temperature_calibration() {
+        // An overconfident classifier: 73% of its [2, 0] predictions are
+        // right, which matches softmax([1, 0]), i.e. a temperature of ~2.
+        let logits = vec![vec![2.0, 0.0]; 100];
+        let targets: Vec<usize> = (0..100).map(|i| usize::from(i >= 73)).collect();
+
+        let t = fit_temperature(&logits, &targets).unwrap();
+
+        assert!((t - 2.0 / (73.0f32 / 27.0).ln()).abs() < 1e-2);
+        assert!(nll(&logits, &targets, t).unwrap() < nll(&logits, &targets, 1.0).unwrap());
+        assert!(nll(&logits, &targets[1..], 1.0).is_err());
+        assert!(matches!(
+            nll(&logits, &targets, 0.0),
+            Err(EngineError::DomainErr { .. })
+        ));
+        assert!(nll(&[], &[], f32::NAN).is_err());
+    }
+
+    #[test]
+    fn history() {
+        let mut rng = rand::thread_rng();
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0]];
+        let ys = vec![vec![1.0], vec![-1.0]];
+        let mut trainer = Trainer::new(MLP::new(2, &[3, 1], &mut rng), SGD::new(0.05))
+            .with_metric("params", |m| m.parameters().len() as f32);
+
+        let history = trainer.fit(&xs, &ys, 5, &mut []).unwrap();
+        let losses = history.losses();
+
+        assert_eq!(losses.len(), 5);
+        assert!(losses[4] < losses[0]);
+        assert_eq!(history.last_loss(), Some(losses[4]));
+        assert_eq!(history.epochs[0].lr, Some(0.05));
+        assert_eq!(history.metric("params"), Some(vec![13.0; 5]));
+        assert_eq!(history.metric("accuracy"), None);
+
+        let csv = history.to_csv();
+        assert!(csv.starts_with("epoch,loss,lr,elapsed_ms,params\n"));
+        assert_eq!(csv.lines().count(), 6);
+        assert!(history.to_svg(200.0, 100.0).contains("<polyline"));
+    }
+
+    #[test]
+    fn closure_step() {
+        // Halves the step until the re-evaluated loss improves.
+        struct Backtracking(Vec<f32>);
+
+        impl Optimizer for Backtracking {
+            fn step(&mut self, _params: &[Scalar<f32>]) {}
+
+            fn step_with_closure(
+                &mut self,
+                params: &[Scalar<f32>],
+                closure: &mut dyn FnMut() -> Scalar<f32>,
+            ) {
+                let start: Vec<f32> = params.iter().map(|p| p.data()).collect();
+                let grads: Vec<f32> = params.iter().map(|p| p.grad()).collect();
+                let before = closure().data();
+                let mut lr = 1.0;
+
+                loop {
+                    for (p, (d, g)) in params.iter().zip(start.iter().zip(&grads)) {
+                        p.set_data(d - lr * g);
+                    }
+
+                    let after = closure().data();
+                    self.0.push(after);
+                    if after < before {
+                        break;
+                    }
+                    lr /= 2.0;
+                }
+            }
+        }
+
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0]];
+        let ys = vec![vec![1.0], vec![-1.0]];
+        let mut trainer = Trainer::new(MLP::seeded(2, &[3, 1], 4), Backtracking(vec![]));
+
+        let losses = trainer.fit(&xs, &ys, 3, &mut []).unwrap().losses();
+        let evaluated = &trainer.optimizer.0;
+
+        assert!(losses.windows(2).all(|w| w[1] < w[0]));
+        assert!(evaluated.len() >= 3);
+        assert!(*evaluated.last().unwrap() < losses[2]);
+    }
+
+    #[test]
+    fn line_search() {
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0], vec![-1.0, 0.0]];
+        let ys = vec![vec![0.5], vec![-0.5], vec![0.2]];
+        let run = |optimizer: Box<dyn Optimizer>| {
+            let mut trainer = Trainer::new(MLP::seeded(2, &[4, 1], 6), optimizer);
+            trainer.fit(&xs, &ys, 20, &mut []).unwrap().losses()
+        };
+
+        // Too large a fixed rate for SGD; the search shrinks it as needed.
+        let sgd = run(Box::new(SGD::new(2.0)));
+        let searched = run(Box::new(LineSearch::new(2.0)));
+
+        assert!(searched.windows(2).all(|w| w[1] <= w[0]));
+        assert!(searched[5] < sgd[5]);
+        assert!(searched[19] < 0.1 * searched[0]);
+    }
+
+    #[test]
+    fn derivative_free() {
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0], vec![-1.0, 0.0]];
+        let ys = vec![vec![0.5], vec![-0.5], vec![0.2]];
+        let run = |optimizer: Box<dyn Optimizer>| {
+            let mut trainer = Trainer::new(MLP::seeded(2, &[4, 1], 6), optimizer);
+            trainer.fit(&xs, &ys, 15, &mut []).unwrap().losses()
+        };
+
+        for optimizer in [
+            Box::new(CoordinateDescent::new(0.1)) as Box<dyn Optimizer>,
+            Box::new(RandomSearch::new(0.1, 5, 1)),
+            Box::new(OnePlusOneES::new(0.1, 1)),
+        ] {
+            let losses = run(optimizer);
+            assert!(losses.windows(2).all(|w| w[1] <= w[0]));
+            assert!(losses[14] < losses[0]);
+        }
+
+        assert_eq!(
+            run(Box::new(OnePlusOneES::new(0.1, 1))),
+            run(Box::new(OnePlusOneES::new(0.1, 1)))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "RandomSearch needs step_with_closure")]
+    fn derivative_free_step_without_closure() {
+        // Without a closure there is nothing to evaluate.
+        let p = Scalar::new(1.0, "p");
+        p.set_grad(1.0);
+        RandomSearch::new(0.1, 5, 1).step(std::slice::from_ref(&p));
+    }
+
+    #[test]
+    fn wrapped_closure_step() {
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0], vec![-1.0, 0.0]];
+        let ys = vec![vec![0.5], vec![-0.5], vec![0.2]];
+        let run = |optimizer: Box<dyn Optimizer>| {
+            let mut trainer = Trainer::new(MLP::seeded(2, &[4, 1], 6), optimizer);
+            trainer.fit(&xs, &ys, 15, &mut []).unwrap().losses()
+        };
+        let wide = Clamp::new(-100.0, 100.0);
+
+        // The wrappers must reach the closure, or these fall back to plain
+        // (diverging) SGD and to no-ops respectively.
+        let searched = run(Box::new(LineSearch::new(2.0)));
+        assert_eq!(
+            run(Box::new(
+                Constrained::new(LineSearch::new(2.0)).with(&[], wide)
+            )),
+            searched
+        );
+        assert_eq!(
+            run(Box::new(Scheduled::new(LineSearch::new(0.0), |_| 2.0))),
+            searched
+        );
+        assert!(searched[14] < 0.1 * searched[0]);
+
+        let noisy = run(Box::new(GradientNoise::new(
+            LineSearch::new(2.0),
+            1e-4,
+            0.55,
+            3,
+        )));
+        assert!(noisy[14] < 0.1 * noisy[0]);
+
+        for optimizer in [
+            Box::new(GradientNoise::new(
+                RandomSearch::new(0.1, 5, 1),
+                0.1,
+                0.55,
+                3,
+            )) as Box<dyn Optimizer>,
+            Box::new(Scheduled::new(RandomSearch::new(0.1, 5, 1), |_| 0.1)),
+            Box::new(Constrained::new(RandomSearch::new(0.1, 5, 1))),
+        ] {
+            let losses = run(optimizer);
+            assert!(losses.windows(2).all(|w| w[1] <= w[0]));
+            assert!(losses[14] < losses[0]);
+        }
+    }
+
+    #[test]
+    fn curriculum() {
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0], vec![-1.0, 0.0]];
+        let ys = vec![vec![0.5], vec![-0.5], vec![0.2]];
+        let losses = |trainer: Trainer<SGD>| {
+            let mut trainer = trainer;
+            trainer.fit(&xs, &ys, 4, &mut []).unwrap().losses()
+        };
+        let trainer = || Trainer::new(MLP::seeded(2, &[3, 1], 2), SGD::new(0.0));
+
+        // Sample `i` joins at epoch `i`; with a zero learning rate only the
+        // weighting changes the loss.
+        let plain = losses(trainer());
+        let staged = losses(trainer().with_curriculum(
+            |epoch, sample| {
+                if sample <= epoch {
+                    1.0
+                } else {
+                    0.0
+                }
+            },
+        ));
+        assert!(staged[0] < staged[1] && staged[1] < staged[2]);
+        assert_eq!(staged[3], plain[3]);
+
+        let halved = losses(trainer().with_curriculum(|_, _| 0.5));
+        assert!((halved[0] - plain[0] / 2.0).abs() < 1e-6);
+
+        // Epochs without any active sample leave the model alone rather than
+        // stepping on the previous epoch's gradients.
+        let params = |trainer: &Trainer<SGD>| -> Vec<f32> {
+            trainer
+                .model()
+                .parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect()
+        };
+        let mut once = Trainer::new(MLP::seeded(2, &[3, 1], 2), SGD::new(0.1));
+        once.fit(&xs, &ys, 1, &mut []).unwrap();
+        let mut paused = Trainer::new(MLP::seeded(2, &[3, 1], 2), SGD::new(0.1))
+            .with_curriculum(|epoch, _| if epoch == 0 { 1.0 } else { 0.0 });
+        let history = paused.fit(&xs, &ys, 3, &mut []).unwrap();
+
+        assert_eq!(params(&paused), params(&once));
+        assert!(paused.model().parameters().iter().all(|p| p.grad() == 0.0));
+        assert_eq!(history.losses()[1..], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn cross_entropy_loss() {
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0]];
+        let mut trainer =
+            Trainer::new(MLP::seeded(2, &[3, 2], 3), SGD::new(0.1)).with_loss(Loss::CrossEntropy);
+
+        let losses = trainer
+            .fit(&xs, &[vec![0.0], vec![1.0]], 5, &mut [])
+            .unwrap()
+            .losses();
+
+        assert!(losses[4] < losses[0]);
+        assert_eq!(
+            trainer
+                .fit(&xs, &[vec![0.0], vec![2.0]], 1, &mut [])
+                .unwrap_err()
+                .to_string(),
+            "sample 1: class 2 out of range for 2 outputs"
+        );
+        for bad in [-1.0, 0.5, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                trainer.fit(&xs, &[vec![0.0], vec![bad]], 1, &mut []),
+                Err(NeuronError::ClassErr { sample: 1, .. })
+            ));
+        }
+        assert!(matches!(
+            trainer.fit(&xs, &vec![vec![0.0, 1.0]; 2], 1, &mut []),
+            Err(NeuronError::TargetLenErr { sample: 0, .. })
+        ));
+        assert!(matches!(
+            trainer.fit(&xs, &[vec![0.0]], 1, &mut []),
+            Err(NeuronError::BatchLenErr {
+                inputs: 2,
+                targets: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn resume() {
+        let mut rng = rand::thread_rng();
+        let xs = vec![vec![1.0, -1.0], vec![0.5, 2.0]];
+        let ys = vec![vec![1.0], vec![-1.0]];
+        let optimizer = || {
+            let schedule = Linear {
+                start: 0.1,
+                end: 0.01,
+                steps: 10,
+            };
+
+            GradientNoise::new(Scheduled::new(SGD::new(0.0), schedule), 0.01, 0.55, 1)
+        };
+        let path = std::env::temp_dir().join(format!("micrograd-resume-{}", std::process::id()));
+
+        let mut trainer = Trainer::new(MLP::new(2, &[3, 1], &mut rng), optimizer());
+        trainer.fit(&xs, &ys, 3, &mut []).unwrap();
+        trainer.save(&path).unwrap();
+        let expected = trainer.fit(&xs, &ys, 2, &mut []).unwrap();
+
+        let mut resumed = Trainer::new(MLP::new(2, &[3, 1], &mut rng), optimizer());
+        resumed.resume(&path).unwrap();
+        assert_eq!(resumed.epoch(), 3);
+        let history = resumed.fit(&xs, &ys, 2, &mut []).unwrap();
+
+        assert_eq!(history.losses(), expected.losses());
+        assert_eq!(history.epochs[0].epoch, 3);
+        assert_eq!(resumed.epoch(), 5);
+
+        let mut wider = Trainer::new(MLP::new(2, &[4, 1], &mut rng), optimizer());
+        assert!(matches!(
+            wider.resume(&path),
+            Err(CheckpointError::ParamLenErr)
+        ));
+        let mut plain = Trainer::new(MLP::new(2, &[3, 1], &mut rng), SGD::new(0.1));
+        assert!(matches!(
+            plain.resume(&path),
+            Err(CheckpointError::OptimizerStateErr)
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+//! JavaScript bindings exposing `Scalar` graph construction, backward and SVG
+//! export to the browser. The crate is a plain rlib by default; build the
+//! module as a cdylib and generate the JS glue with:
+//!
+//! ```text
+//! cargo rustc --lib --crate-type cdylib --release --target wasm32-unknown-unknown --features wasm
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/micrograd.wasm
+//! ```
+//!
+//! `Instant` is unavailable on wasm32-unknown-unknown, so `try_backward`,
+//! `profile` and `Trainer::fit`, which time themselves, aren't exposed.
+
+use super::engine::Scalar;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(js_name = Scalar)]
+pub struct JsScalar(Scalar<f32>);
+
+#[wasm_bindgen(js_class = Scalar)]
+impl JsScalar {
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: f32, label: &str) -> JsScalar {
+        JsScalar(Scalar::new(data, label))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> f32 {
+        self.0.data()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_data(&self, data: f32) {
+        self.0.set_data(data);
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn grad(&self) -> f32 {
+        self.0.grad()
+    }
+
+    pub fn label(&self, label: &str) {
+        self.0.label(label);
+    }
+
+    pub fn add(&self, other: &JsScalar) -> JsScalar {
+        JsScalar(self.0.clone() + other.0.clone())
+    }
+
+    pub fn sub(&self, other: &JsScalar) -> JsScalar {
+        JsScalar(self.0.clone() - other.0.clone())
+    }
+
+    pub fn mul(&self, other: &JsScalar) -> JsScalar {
+        JsScalar(self.0.clone() * other.0.clone())
+    }
+
+    pub fn powi(&self, n: i32) -> JsScalar {
+        JsScalar(self.0.powi(n))
+    }
+
+    pub fn tanh(&self) -> JsScalar {
+        JsScalar(self.0.tanh())
+    }
+
+    pub fn exp(&self) -> JsScalar {
+        JsScalar(self.0.exp())
+    }
+
+    pub fn ln(&self) -> JsScalar {
+        JsScalar(self.0.ln())
+    }
+
+    pub fn backward(&self) {
+        self.0.backward();
+    }
+
+    /// Recomputes the graph after leaves were changed through `data`.
+    pub fn forward(&self) {
+        self.0.forward();
+    }
+
+    /// The graph rooted here as an SVG document.
+    pub fn draw(&self) -> String {
+        self.0.draw()
+    }
+
+    #[wasm_bindgen(js_name = toDot)]
+    pub fn to_dot(&self) -> String {
+        self.0.to_dot()
+    }
+}